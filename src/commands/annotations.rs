@@ -0,0 +1,220 @@
+use crate::commands::users::find_user;
+use crate::find_pokemon;
+use chrono::Utc;
+use clap::Args;
+use pokedb::db::annotations::{normalize_tag, AnnotationFilter, Note};
+use pokedb::db::connection::AppConfig;
+use pokedb::db::query::PokemonSearch;
+use pokedb::error::Error;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// The filters of the favorites, tags and notes, shared by the searches and the analyses
+#[derive(Args)]
+pub struct AnnotationArgs {
+    /// Only the favorites of the user
+    #[arg(long)]
+    pub favorites: bool,
+    /// Only the pokemon the user tagged with it, e.g. ou-candidates. It can be given several times
+    #[arg(long)]
+    pub tagged: Vec<String>,
+    /// Only the pokemon whose note has the text
+    #[arg(long)]
+    pub note_contains: Option<String>,
+    /// Whose favorites, tags and notes (POKEDB_USER by default)
+    #[arg(long)]
+    pub user: Option<String>,
+}
+
+/* The filter of the favorites, tags and notes of the flags, None without them. The user is only
+needed when there is a filter*/
+pub async fn annotation_filter(
+    config: &AppConfig,
+    args: AnnotationArgs,
+) -> Result<Option<AnnotationFilter>, Error> {
+    if !args.favorites && args.tagged.is_empty() && args.note_contains.is_none() {
+        return Ok(None);
+    }
+    let user = find_user(config, args.user).await?;
+
+    Ok(Some(AnnotationFilter {
+        user_id: user.id,
+        favorites: args.favorites,
+        tags: args.tagged.iter().map(|tag| normalize_tag(tag)).collect(),
+        note: args.note_contains,
+    }))
+}
+
+// Every pokemon the user annotated that matches the filter, with its tags and the start of its note
+pub async fn list_annotations(config: &AppConfig, args: AnnotationArgs) -> Result<(), Error> {
+    let user = find_user(config, args.user.clone()).await?;
+    let filter = annotation_filter(config, args)
+        .await?
+        .unwrap_or(AnnotationFilter {
+            user_id: user.id,
+            ..AnnotationFilter::default()
+        });
+    let annotations: Vec<_> = config
+        .store
+        .list_annotations(user.id)
+        .await?
+        .into_iter()
+        .filter(|annotation| filter.matches(annotation))
+        .collect();
+    if annotations.is_empty() {
+        println!(
+            "{} doesn't have {}, see 'pokedb favorite', 'tag' and 'note'",
+            user.name,
+            filter.label()
+        );
+        return Ok(());
+    }
+
+    let names = config.store.display_names().await?;
+    let slugs: HashMap<i32, String> = config
+        .store
+        .search(&PokemonSearch::default())
+        .await?
+        .pokemon
+        .into_iter()
+        .map(|pokemon| (pokemon.pokedex_number, pokemon.name))
+        .collect();
+    for annotation in annotations {
+        let number = annotation.pokedex_number;
+        let slug = slugs.get(&number).map_or("", String::as_str);
+        let favorite = if annotation.favorite { "*" } else { " " };
+        let note = annotation
+            .note
+            .as_ref()
+            .and_then(|note| note.text.lines().next())
+            .unwrap_or_default();
+        let line = format!(
+            "{favorite} #{number:<4} {:<14} {:<30} {note}",
+            names.get(number, slug),
+            annotation.tags.join(", ")
+        );
+        println!("{}", line.trim_end());
+    }
+
+    Ok(())
+}
+
+pub async fn favorite_pokemon(
+    config: &AppConfig,
+    pokemon: &str,
+    user: Option<String>,
+    remove: bool,
+) -> Result<(), Error> {
+    let user = find_user(config, user).await?;
+    let pokemon = find_pokemon(config, pokemon).await?;
+    let mut annotation = config
+        .store
+        .get_annotation(user.id, pokemon.pokedex_number)
+        .await?;
+    annotation.favorite = !remove;
+    config.store.save_annotation(user.id, &annotation).await?;
+    let names = config.store.display_names().await?;
+    let name = names.get(pokemon.pokedex_number, &pokemon.name);
+    if remove {
+        println!("{name} removed from the favorites of {}", user.name);
+    } else {
+        println!("{name} added to the favorites of {}", user.name);
+    }
+
+    Ok(())
+}
+
+// Adding and removing tags of a pokemon, and showing the ones it has after that
+pub async fn tag_pokemon(
+    config: &AppConfig,
+    pokemon: &str,
+    add: &[String],
+    remove: &[String],
+    user: Option<String>,
+) -> Result<(), Error> {
+    let user = find_user(config, user).await?;
+    let pokemon = find_pokemon(config, pokemon).await?;
+    let mut annotation = config
+        .store
+        .get_annotation(user.id, pokemon.pokedex_number)
+        .await?;
+    let remove: Vec<String> = remove.iter().map(|tag| normalize_tag(tag)).collect();
+    annotation.tags.retain(|tag| !remove.contains(tag));
+    for tag in add.iter().map(|tag| normalize_tag(tag)) {
+        if tag.is_empty() {
+            return Err(Error::Input("A tag can't be empty".to_string()));
+        }
+        if !annotation.tags.contains(&tag) {
+            annotation.tags.push(tag);
+        }
+    }
+    annotation.tags.sort();
+    if !add.is_empty() || !remove.is_empty() {
+        config.store.save_annotation(user.id, &annotation).await?;
+    }
+    let names = config.store.display_names().await?;
+    let name = names.get(pokemon.pokedex_number, &pokemon.name);
+    if annotation.tags.is_empty() {
+        println!("{name} doesn't have tags of {}", user.name);
+    } else {
+        println!("Tags of {name}: {}", annotation.tags.join(", "));
+    }
+
+    Ok(())
+}
+
+/* Saving the note of the text or the file, or removing it with 'clear'. Without them the saved
+note is shown*/
+pub async fn write_note(
+    config: &AppConfig,
+    pokemon: &str,
+    text: Option<String>,
+    file: Option<PathBuf>,
+    clear: bool,
+    user: Option<String>,
+) -> Result<(), Error> {
+    let user = find_user(config, user).await?;
+    let pokemon = find_pokemon(config, pokemon).await?;
+    let mut annotation = config
+        .store
+        .get_annotation(user.id, pokemon.pokedex_number)
+        .await?;
+    let names = config.store.display_names().await?;
+    let name = names.get(pokemon.pokedex_number, &pokemon.name);
+    let text = match (text, file) {
+        (Some(text), _) => Some(text),
+        (None, Some(file)) if file == Path::new("-") => Some(io::read_to_string(io::stdin())?),
+        (None, Some(file)) => {
+            Some(fs::read_to_string(&file).map_err(|e| Error::file(e, "read", &file))?)
+        }
+        (None, None) => None,
+    };
+
+    if clear {
+        annotation.note = None;
+        config.store.save_annotation(user.id, &annotation).await?;
+        println!("Note of {} about {name} removed", user.name);
+    } else if let Some(text) = text {
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            return Err(Error::Input(
+                "The note is empty, use --clear to remove it".to_string(),
+            ));
+        }
+        annotation.note = Some(Note {
+            text,
+            updated_at: Utc::now(),
+        });
+        config.store.save_annotation(user.id, &annotation).await?;
+        println!("Note of {} about {name} saved", user.name);
+    } else {
+        match &annotation.note {
+            Some(note) => println!("{}", note.text),
+            None => println!("{} doesn't have a note about {name}", user.name),
+        }
+    }
+
+    Ok(())
+}
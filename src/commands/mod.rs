@@ -0,0 +1,6 @@
+// The handlers of the commands that have their own group of subcommands or flags
+pub mod annotations;
+pub mod settings;
+pub mod snapshot;
+pub mod teams;
+pub mod users;
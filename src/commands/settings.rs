@@ -0,0 +1,150 @@
+use crate::Cli;
+use clap::Subcommand;
+use pokedb::config::{
+    normalize_api_url, redact_password, remove_profile, save_profile, user_config_path, Config,
+    Profile, PROJECT_CONFIG_FILE,
+};
+use pokedb::error::Error;
+use pokedb::users::user_config::prompt_profile;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+#[derive(Subcommand)]
+pub enum ProfileCommand {
+    /// List the profiles, the selected one with '*'
+    List,
+    /// Add a profile, or replace it, with --database-url, --api-url and --output-dir. Without them
+    /// the same questions of the first run are asked
+    Add {
+        name: String,
+        /// Save it in pokedb.toml of this directory instead of the user config file
+        #[arg(long)]
+        project: bool,
+    },
+    /// Remove a profile from the config file it's in
+    Remove { name: String },
+}
+
+// The settings after every layer, the password of the database isn't shown
+pub fn show_config(settings: &Config) {
+    if settings.files.is_empty() {
+        println!("Config files: none");
+    } else {
+        println!("Config files:");
+        for file in &settings.files {
+            println!("  {}", file.display());
+        }
+    }
+    let database = settings
+        .database_url
+        .as_deref()
+        .map_or("-".to_string(), redact_password);
+    println!(
+        "profile             {}",
+        settings.profile.as_deref().unwrap_or("-")
+    );
+    println!("database.url        {database}");
+    let path = |path: &Option<PathBuf>| {
+        path.as_ref()
+            .map_or("-".to_string(), |path| path.display().to_string())
+    };
+    println!(
+        "database.sslmode    {}",
+        settings.ssl.mode.map_or("-", |mode| mode.as_str())
+    );
+    println!("database.sslrootcert {}", path(&settings.ssl.root_cert));
+    println!("database.sslcert    {}", path(&settings.ssl.client_cert));
+    println!("database.sslkey     {}", path(&settings.ssl.client_key));
+    println!("api.base_url        {}", settings.api.base_url);
+    println!("api.concurrency     {}", settings.api.concurrency);
+    println!(
+        "game                {}",
+        settings.game.as_deref().unwrap_or("-")
+    );
+    println!(
+        "user                {}",
+        settings.user.as_deref().unwrap_or("-")
+    );
+    println!("cache.enabled       {}", settings.cache.enabled);
+    println!("cache.dir           {}", settings.cache.dir.display());
+    println!("cache.max_age_hours {}", settings.cache.max_age_hours);
+    println!("output_dir          {}", settings.output_dir.display());
+    println!("plots.scale         {}", settings.plots.scale);
+    println!("plots.font          {}", settings.plots.font);
+}
+
+// Listing, adding or removing the profiles of the config files, without opening the database
+pub async fn manage_profiles(
+    cli: &Cli,
+    settings: &Config,
+    action: &ProfileCommand,
+) -> Result<(), Error> {
+    match action {
+        ProfileCommand::List => {
+            if settings.profiles.is_empty() {
+                println!("No profiles, add one with 'pokedb profile add'");
+            }
+            for (name, profile) in &settings.profiles {
+                let selected = if settings.profile.as_deref() == Some(name) {
+                    '*'
+                } else {
+                    ' '
+                };
+                println!("{selected} {name} ({})", profile.file.display());
+                if let Some(url) = &profile.database_url {
+                    println!("    database_url  {}", redact_password(url));
+                }
+                if let Some(url) = &profile.api_base_url {
+                    println!("    api_base_url  {url}");
+                }
+                if let Some(dir) = &profile.output_dir {
+                    println!("    output_dir    {}", dir.display());
+                }
+            }
+        }
+        ProfileCommand::Add { name, project } => {
+            let path = match (&cli.config, project) {
+                (Some(path), _) => path.clone(),
+                (None, true) => PathBuf::from(PROJECT_CONFIG_FILE),
+                (None, false) => user_config_path().ok_or_else(|| {
+                    Error::Config("There isn't a user config directory".to_string())
+                })?,
+            };
+            let profile = if cli.database_url.is_none()
+                && cli.api_url.is_none()
+                && cli.output_dir.is_none()
+            {
+                if !io::stdin().is_terminal() {
+                    return Err(Error::Config(
+                        "Give the profile --database-url, --api-url or --output-dir".to_string(),
+                    ));
+                }
+                prompt_profile(&settings.ssl).await?
+            } else {
+                Profile {
+                    database_url: cli.database_url.clone(),
+                    api_base_url: cli.api_url.as_deref().map(normalize_api_url),
+                    output_dir: cli.output_dir.clone(),
+                    ..Profile::default()
+                }
+            };
+            save_profile(&path, name, &profile)?;
+            println!("Profile '{name}' saved in {}", path.display());
+        }
+        ProfileCommand::Remove { name } => {
+            let profile = settings
+                .profiles
+                .get(name)
+                .ok_or_else(|| Error::Config(format!("The profile '{name}' doesn't exist")))?;
+            if !remove_profile(&profile.file, name)? {
+                return Err(Error::Config(format!(
+                    "The profile '{name}' isn't in {}",
+                    profile.file.display()
+                )));
+            }
+            println!("Profile '{name}' removed from {}", profile.file.display());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use pokedb::db::connection::AppConfig;
+use pokedb::db::snapshot::{export_snapshot, import_snapshot, SnapshotManifest};
+use pokedb::error::Error;
+use std::path::Path;
+
+pub async fn export_to_file(config: &AppConfig, path: &Path) -> Result<(), Error> {
+    let manifest = export_snapshot(config.store.as_ref(), path).await?;
+    println!("Snapshot saved in {}", path.display());
+    print_row_counts(&manifest);
+
+    Ok(())
+}
+
+pub async fn import_from_file(config: &AppConfig, path: &Path) -> Result<(), Error> {
+    let manifest = import_snapshot(config.store.as_ref(), path).await?;
+    println!(
+        "Snapshot of {} imported",
+        manifest.created_at.format("%Y-%m-%d %H:%M:%S")
+    );
+    print_row_counts(&manifest);
+
+    Ok(())
+}
+
+fn print_row_counts(manifest: &SnapshotManifest) {
+    for (file, rows) in &manifest.row_counts {
+        println!("  {file:<24} {rows:>6} rows");
+    }
+}
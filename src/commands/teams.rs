@@ -0,0 +1,156 @@
+use crate::commands::users::find_user;
+use clap::Subcommand;
+use pokedb::db::connection::AppConfig;
+use pokedb::db::teams::{check_team, format_showdown, parse_showdown, resolve_species, Team};
+use pokedb::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand)]
+pub enum TeamCommand {
+    /// Import a team pasted from Showdown, its moves are checked for the game of --game
+    Import {
+        /// The file with the team, "-" for the standard input
+        file: PathBuf,
+        /// The name of the team, a team of the user with the same name is replaced
+        #[arg(long)]
+        name: String,
+        /// The user (POKEDB_USER by default)
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Write a team in the format of Showdown, to the standard output without a file
+    Export {
+        name: String,
+        file: Option<PathBuf>,
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// List the teams of a user
+    List {
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Show a team and what isn't valid in it with the saved data
+    Show {
+        name: String,
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Remove a team of a user
+    Remove {
+        name: String,
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+pub async fn manage_teams(config: &AppConfig, action: TeamCommand) -> Result<(), Error> {
+    match action {
+        TeamCommand::Import { file, name, user } => {
+            let user = find_user(config, user).await?;
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(Error::Input(
+                    "The name of the team can't be empty".to_string(),
+                ));
+            }
+            let text = if file == Path::new("-") {
+                io::read_to_string(io::stdin())?
+            } else {
+                fs::read_to_string(&file).map_err(|e| Error::file(e, "read", &file))?
+            };
+            let mut members = parse_showdown(&text)?;
+            resolve_species(config.store.as_ref(), &mut members).await?;
+            let team = Team {
+                name: name.to_string(),
+                game: config.game().await?.map(|game| game.name().to_string()),
+                members,
+            };
+
+            let problems = check_team(config.store.as_ref(), &team).await?;
+            if !problems.is_empty() {
+                for problem in &problems {
+                    eprintln!("  {problem}");
+                }
+                return Err(Error::Input(format!(
+                    "The team '{name}' isn't valid, it wasn't saved"
+                )));
+            }
+            config.store.save_team(user.id, &team).await?;
+            println!(
+                "Team '{name}' saved for {} with {} pokemon",
+                user.name,
+                team.members.len()
+            );
+        }
+        TeamCommand::Export { name, file, user } => {
+            let team = find_team(config, user, &name).await?;
+            let names = config.store.display_names().await?;
+            let text = format_showdown(&team, &names);
+            match file {
+                Some(file) => {
+                    fs::write(&file, text).map_err(|e| Error::file(e, "write", &file))?;
+                    println!("Team '{name}' saved in {}", file.display());
+                }
+                None => print!("{text}"),
+            }
+        }
+        TeamCommand::List { user } => {
+            let user = find_user(config, user).await?;
+            let teams = config.store.list_teams(user.id).await?;
+            if teams.is_empty() {
+                println!(
+                    "{} doesn't have teams yet, import one with 'pokedb team import'",
+                    user.name
+                );
+            }
+            let names = config.store.display_names().await?;
+            for team in teams {
+                let members: Vec<String> = team
+                    .members
+                    .iter()
+                    .map(|member| names.get(member.pokedex_number, &member.species))
+                    .collect();
+                let game = team.game.as_deref().unwrap_or("any game");
+                println!("{:<20} {:<14} {}", team.name, game, members.join(", "));
+            }
+        }
+        TeamCommand::Show { name, user } => {
+            let team = find_team(config, user, &name).await?;
+            let names = config.store.display_names().await?;
+            let game = team.game.as_deref().unwrap_or("any game");
+            println!("Team '{}' for {game}\n", team.name);
+            print!("{}", format_showdown(&team, &names));
+            // The data can change with a sync after the import
+            let problems = check_team(config.store.as_ref(), &team).await?;
+            if !problems.is_empty() {
+                println!("The team isn't valid anymore:");
+                for problem in problems {
+                    println!("  {problem}");
+                }
+            }
+        }
+        TeamCommand::Remove { name, user } => {
+            let user = find_user(config, user).await?;
+            if config.store.delete_team(user.id, &name).await? {
+                println!("Team '{name}' of {} removed", user.name);
+            } else {
+                println!("{} doesn't have a team '{name}'", user.name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn find_team(config: &AppConfig, user: Option<String>, name: &str) -> Result<Team, Error> {
+    let user = find_user(config, user).await?;
+    config.store.get_team(user.id, name).await?.ok_or_else(|| {
+        Error::Input(format!(
+            "{} doesn't have a team '{name}', see 'pokedb team list'",
+            user.name
+        ))
+    })
+}
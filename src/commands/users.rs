@@ -0,0 +1,226 @@
+use crate::find_pokemon;
+use chrono::Utc;
+use clap::Subcommand;
+use pokedb::db::collection::{
+    completion_by_generation, completion_of_dex, normalize_form, CaughtPokemon, User,
+};
+use pokedb::db::connection::AppConfig;
+use pokedb::db::display_names::DisplayNames;
+use pokedb::db::query::PokemonSearch;
+use pokedb::error::Error;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// Add a user with an empty collection
+    Add { name: String },
+    /// List the users with the pokemon they have caught
+    List,
+}
+
+pub async fn manage_users(config: &AppConfig, action: &UserCommand) -> Result<(), Error> {
+    match action {
+        UserCommand::Add { name } => {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(Error::Input(
+                    "The name of the user can't be empty".to_string(),
+                ));
+            }
+            config.store.create_user(name).await?;
+            println!("User '{name}' added");
+        }
+        UserCommand::List => {
+            let users = config.store.list_users().await?;
+            if users.is_empty() {
+                println!("There are no users yet, add one with 'pokedb user add'");
+            }
+            for user in users {
+                let caught = config.store.list_caught(user.id).await?;
+                let species: HashSet<i32> = caught.iter().map(|p| p.pokedex_number).collect();
+                println!(
+                    "{:<20} {:>5} pokemon, {:>5} species",
+                    user.name,
+                    caught.len(),
+                    species.len()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// The user of --user or of the config, it must have been added
+pub async fn find_user(config: &AppConfig, user: Option<String>) -> Result<User, Error> {
+    let Some(name) = user.or_else(|| config.settings.user.clone()) else {
+        return Err(Error::Config(
+            "Which user? Give --user, POKEDB_USER or 'user' in the config, see 'pokedb user list'"
+                .to_string(),
+        ));
+    };
+    config.store.find_user(&name).await?.ok_or_else(|| {
+        Error::Input(format!(
+            "The user '{name}' doesn't exist, add it with 'pokedb user add {name}'"
+        ))
+    })
+}
+
+// Saving a pokemon in the collection of the user, from the game of the config
+pub async fn mark_pokemon(
+    config: &AppConfig,
+    pokemon: &str,
+    user: Option<String>,
+    form: &str,
+    shiny: bool,
+) -> Result<(), Error> {
+    let user = find_user(config, user).await?;
+    let pokemon = find_pokemon(config, pokemon).await?;
+    let game = config.game().await?;
+    let caught = CaughtPokemon {
+        pokedex_number: pokemon.pokedex_number,
+        form: normalize_form(form),
+        shiny,
+        game: game.as_ref().map(|game| game.name().to_string()),
+        caught_at: Utc::now(),
+    };
+    config.store.mark_caught(user.id, &caught).await?;
+    let names = config.store.display_names().await?;
+    println!(
+        "{} saved in the collection of {}",
+        caught_label(&names, &pokemon.name, &caught),
+        user.name
+    );
+
+    Ok(())
+}
+
+pub async fn unmark_pokemon(
+    config: &AppConfig,
+    pokemon: &str,
+    user: Option<String>,
+    form: &str,
+    shiny: bool,
+) -> Result<(), Error> {
+    let user = find_user(config, user).await?;
+    let pokemon = find_pokemon(config, pokemon).await?;
+    let form = normalize_form(form);
+    let removed = config
+        .store
+        .unmark_caught(user.id, pokemon.pokedex_number, &form, shiny)
+        .await?;
+    let names = config.store.display_names().await?;
+    let caught = CaughtPokemon {
+        pokedex_number: pokemon.pokedex_number,
+        form,
+        shiny,
+        game: None,
+        caught_at: Utc::now(),
+    };
+    let label = caught_label(&names, &pokemon.name, &caught);
+    if removed {
+        println!("{label} removed from the collection of {}", user.name);
+    } else {
+        println!("{} doesn't have {label}", user.name);
+    }
+
+    Ok(())
+}
+
+// e.g. "Vulpix (alola, shiny)" or "Pikachu from sword"
+fn caught_label(names: &DisplayNames, slug: &str, caught: &CaughtPokemon) -> String {
+    let mut label = names.get(caught.pokedex_number, slug);
+    let details: Vec<&str> = [
+        (!caught.form.is_empty()).then_some(caught.form.as_str()),
+        caught.shiny.then_some("shiny"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !details.is_empty() {
+        label = format!("{label} ({})", details.join(", "));
+    }
+    if let Some(game) = &caught.game {
+        label = format!("{label} from {game}");
+    }
+
+    label
+}
+
+pub async fn show_collection(
+    config: &AppConfig,
+    user: Option<String>,
+    dex: Option<String>,
+    shiny: bool,
+    missing: bool,
+) -> Result<(), Error> {
+    let user = find_user(config, user).await?;
+    let caught = config.store.list_caught(user.id).await?;
+    let completions = match &dex {
+        Some(name) => {
+            let dex = config.store.get_regional_dex(name).await?.ok_or_else(|| {
+                Error::Input(format!(
+                    "Unknown pokedex '{name}'. See 'pokedb dexes' for the saved ones"
+                ))
+            })?;
+            vec![completion_of_dex(&dex, &caught, shiny)]
+        }
+        None => {
+            let species = config.store.list_species().await?;
+            if species.is_empty() {
+                println!("There are no species yet. Run 'pokedb sync' first");
+                return Ok(());
+            }
+            completion_by_generation(&species, &caught, shiny)
+        }
+    };
+
+    let kind = if shiny {
+        "Shiny living dex"
+    } else {
+        "Living dex"
+    };
+    println!("{kind} of {}", user.name);
+    let names = config.store.display_names().await?;
+    let slugs: HashMap<i32, String> = config
+        .store
+        .search(&PokemonSearch::default())
+        .await?
+        .pokemon
+        .into_iter()
+        .map(|pokemon| (pokemon.pokedex_number, pokemon.name))
+        .collect();
+    for completion in &completions {
+        println!(
+            "  {:<14} {:>4} / {:<4} {:>5.1}%",
+            completion.label,
+            completion.caught,
+            completion.total,
+            completion.percent()
+        );
+        if missing {
+            for pokedex_number in &completion.missing {
+                // Without the pokemon or its species saved there isn't a name
+                let slug = slugs.get(pokedex_number).map_or("", String::as_str);
+                let line = format!(
+                    "      #{pokedex_number:<4} {}",
+                    names.get(*pokedex_number, slug)
+                );
+                println!("{}", line.trim_end());
+            }
+        }
+    }
+    if completions.len() > 1 {
+        let caught: usize = completions.iter().map(|c| c.caught).sum();
+        let total: usize = completions.iter().map(|c| c.total).sum();
+        println!(
+            "  {:<14} {:>4} / {:<4} {:>5.1}%",
+            "Total",
+            caught,
+            total,
+            caught as f64 * 100.0 / total as f64
+        );
+    }
+
+    Ok(())
+}
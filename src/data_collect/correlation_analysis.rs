@@ -1,7 +1,14 @@
+use crate::config::PlotConfig;
+use crate::error::Error;
 use plotters::prelude::*;
 use polars::prelude::*;
+use std::path::Path;
 
-pub fn correlation_analysis(df: &DataFrame) -> Result<(), Box<dyn std::error::Error>> {
+pub fn correlation_analysis(
+    df: &DataFrame,
+    plots: &PlotConfig,
+    output_dir: &Path,
+) -> Result<(), Error> {
     // Fetching the hp and height and weight columns
     let corr_hp = df.columns(["hp", "height", "weight"])?;
 
@@ -19,7 +26,9 @@ pub fn correlation_analysis(df: &DataFrame) -> Result<(), Box<dyn std::error::Er
         .collect();
 
     // Calculating the middle of the columns
-    let hp_mean = hp.mean().unwrap();
+    let hp_mean = hp
+        .mean()
+        .ok_or_else(|| Error::Plot("There are no HP values to correlate".to_string()))?;
     let height_mean = height_f64.iter().sum::<f64>() / height_f64.len() as f64;
     let weight_mean = weight_f64.iter().sum::<f64>() / weight_f64.len() as f64;
 
@@ -32,8 +41,8 @@ pub fn correlation_analysis(df: &DataFrame) -> Result<(), Box<dyn std::error::Er
 
     for ((h, ht), w) in hp
         .into_iter()
-        .zip(height_f64.into_iter())
-        .zip(weight_f64.into_iter())
+        .zip(height_f64)
+        .zip(weight_f64)
     {
         if let Some(h) = h {
             let h_f64 = h as f64;
@@ -52,13 +61,18 @@ pub fn correlation_analysis(df: &DataFrame) -> Result<(), Box<dyn std::error::Er
             hp vs height: {correlation_hp_height}
             hp vs weight: {correlation_hp_weight}"
     );
-    plot_scatter(df)?;
+    plot_scatter(df, plots, output_dir)?;
 
     Ok(())
 }
 
-fn plot_scatter(df: &DataFrame) -> Result<(), Box<dyn std::error::Error>> {
-    let root = BitMapBackend::new("pokemon_scatter_plots.png", (1600, 800)).into_drawing_area();
+fn plot_scatter(
+    df: &DataFrame,
+    plots: &PlotConfig,
+    output_dir: &Path,
+) -> Result<(), Error> {
+    let path = output_dir.join("pokemon_scatter_plots.png");
+    let root = BitMapBackend::new(&path, plots.size(1600, 800)).into_drawing_area();
     root.fill(&WHITE)?;
 
     let areas = root.split_evenly((1, 2));
@@ -74,8 +88,7 @@ fn plot_scatter(df: &DataFrame) -> Result<(), Box<dyn std::error::Error>> {
             DataType::Int32 => series
                 .i32()?
                 .into_iter()
-                .map(|v| v.map(|i| i as f64))
-                .flatten()
+                .flat_map(|v| v.map(|i| i as f64))
                 .collect(),
             DataType::String => series
                 .str()?
@@ -102,7 +115,10 @@ fn plot_scatter(df: &DataFrame) -> Result<(), Box<dyn std::error::Error>> {
             .margin(5)
             .set_label_area_size(LabelAreaPosition::Left, 40)
             .set_label_area_size(LabelAreaPosition::Bottom, 40)
-            .caption(format!("HP vs {}", column), ("sans-serif", 40))
+            .caption(
+                format!("HP vs {}", column),
+                (plots.font.as_str(), plots.font_size(40.0)),
+            )
             .build_cartesian_2d(x_min..x_max, y_min..y_max)?;
 
         chart.configure_mesh().draw()?;
@@ -126,6 +142,6 @@ fn plot_scatter(df: &DataFrame) -> Result<(), Box<dyn std::error::Error>> {
 
     root.present()?;
 
-    println!("Scatter plots have been saved to pokemon_scatter_plots.png");
+    println!("Scatter plots have been saved to {}", path.display());
     Ok(())
 }
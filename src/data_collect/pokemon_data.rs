@@ -1,9 +1,17 @@
+use crate::config::PlotConfig;
 use crate::data_collect::correlation_analysis::correlation_analysis;
+use crate::db::annotations::AnnotationFilter;
+use crate::db::display_names::{name_from_slug, type_display_name};
+use crate::db::games::Game;
+use crate::db::pokemon_tables::PokemonHistory;
+use crate::db::query::GenerationRange;
+use crate::db::store::PokedexStore;
+use crate::error::Error;
 use plotters::prelude::*;
 use polars::prelude::*;
-use postgres::Client;
-use std::collections::HashMap;
-use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
 
 // Adding a struct for more control in the creation of the Vectors
 struct PokemonAttribs {
@@ -11,6 +19,8 @@ struct PokemonAttribs {
     random_id: Vec<i32>,
     pokedex_number: Vec<i32>,
     name: Vec<String>,
+    // The name as it's shown, e.g. "Mr. Mime"
+    display_name: Vec<String>,
     height: Vec<String>,
     weight: Vec<String>,
     hp: Vec<i32>,
@@ -20,20 +30,115 @@ struct PokemonAttribs {
     special_defense: Vec<i32>,
     speed: Vec<i32>,
     types: Vec<String>,
+    generation: Vec<Option<i32>>,
+    regional_number: Vec<Option<i32>>,
 }
 
-pub fn load_pokemon_data(client: &mut Client) -> Result<DataFrame, Box<dyn std::error::Error>> {
-    let rows = client.query(
-        "
-            SELECT p.*,
-                string_agg(DISTINCT t.name, ', 'ORDER BY t.name) as types
-            FROM pokemon p
-            LEFT JOIN pokemon_types pt ON p.id = pt.pokemon_id
-            LEFT JOIN types t ON pt.type_id = t.id
-            GROUP BY p.id
-            ",
-        &[],
-    )?;
+// Which pokemon are analyzed and plotted, every filter that is set must match
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnalysisFilter {
+    pub generations: Option<GenerationRange>,
+    // Name of a pokedex of the API, e.g. "galar"
+    pub regional_dex: Option<String>,
+    // The pokemon that existed then, with the types and stats they had
+    pub as_of_generation: Option<i32>,
+    // Only the favorites, the tags or the notes of a user, e.g. the pokemon tagged "ou-candidates"
+    pub annotations: Option<AnnotationFilter>,
+}
+
+impl AnalysisFilter {
+    /* Like the searches, without generations only the pokemon that exist in the target game, and
+    with the types and stats they have in it*/
+    pub fn for_game(mut self, game: Option<&Game>) -> Self {
+        if let Some(game) = game {
+            if self.generations.is_none() {
+                self.generations = game.generations();
+            }
+            if self.as_of_generation.is_none() {
+                self.as_of_generation = game.generation();
+            }
+        }
+
+        self
+    }
+
+    /* For the titles of the plots, e.g. "Generation 1-3, galar pokedex, as of generation 5, favorites
+    tagged ou-candidates"*/
+    fn label(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(range) = self.generations {
+            parts.push(format!("Generation {range}"));
+        }
+        if let Some(dex) = &self.regional_dex {
+            parts.push(format!("{} pokedex", name_from_slug(dex)));
+        }
+        if let Some(generation) = self.as_of_generation {
+            parts.push(format!("as of generation {generation}"));
+        }
+        if let Some(annotations) = &self.annotations {
+            parts.push(annotations.label());
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+pub async fn load_pokemon_data(
+    store: &dyn PokedexStore,
+    filter: &AnalysisFilter,
+    plots: &PlotConfig,
+    output_dir: &Path,
+) -> Result<DataFrame, Error> {
+    let mut rows = store.load_pokemon_rows().await?;
+    if let Some(range) = filter.generations {
+        rows.retain(|row| row.generation.is_some_and(|generation| range.contains(generation)));
+    }
+    if let Some(as_of) = filter.as_of_generation {
+        let history: HashMap<i32, PokemonHistory> = store
+            .list_pokemon_history()
+            .await?
+            .into_iter()
+            .map(|history| (history.pokedex_number, history))
+            .collect();
+        rows = rows
+            .into_iter()
+            .filter(|row| row.generation.is_some_and(|generation| generation <= as_of))
+            .map(|row| match history.get(&row.pokedex_number) {
+                Some(history) => row.in_generation(history, as_of),
+                None => row,
+            })
+            .collect();
+    }
+    if let Some(annotations) = &filter.annotations {
+        let annotated: HashSet<i32> = store
+            .list_annotations(annotations.user_id)
+            .await?
+            .iter()
+            .filter(|annotation| annotations.matches(annotation))
+            .map(|annotation| annotation.pokedex_number)
+            .collect();
+        rows.retain(|row| annotated.contains(&row.pokedex_number));
+    }
+    // The regional number of each pokemon of the pokedex, by national number
+    let mut regional_numbers: HashMap<i32, i32> = HashMap::new();
+    if let Some(name) = &filter.regional_dex {
+        let dex = store.get_regional_dex(name).await?.ok_or_else(|| {
+            Error::Input(format!(
+                "The pokedex '{name}' isn't saved. Run 'pokedb sync' or see 'pokedb dexes'"
+            ))
+        })?;
+        regional_numbers = dex
+            .entries
+            .iter()
+            .map(|entry| (entry.pokedex_number, entry.entry_number))
+            .collect();
+        rows.retain(|row| regional_numbers.contains_key(&row.pokedex_number));
+        rows.sort_by_key(|row| regional_numbers[&row.pokedex_number]);
+    }
+    if rows.is_empty() {
+        if let Some(label) = filter.label() {
+            return Err(Error::Input(format!("There are no pokemon of {label}")));
+        }
+    }
 
     // Vectors for the creation of the DataFrame
     let mut pokemon_attribs = PokemonAttribs {
@@ -41,6 +146,7 @@ pub fn load_pokemon_data(client: &mut Client) -> Result<DataFrame, Box<dyn std::
         random_id: Vec::new(),
         pokedex_number: Vec::new(),
         name: Vec::new(),
+        display_name: Vec::new(),
         height: Vec::new(),
         weight: Vec::new(),
         hp: Vec::new(),
@@ -50,33 +156,33 @@ pub fn load_pokemon_data(client: &mut Client) -> Result<DataFrame, Box<dyn std::
         special_defense: Vec::new(),
         speed: Vec::new(),
         types: Vec::new(),
+        generation: Vec::new(),
+        regional_number: Vec::new(),
     };
 
     // Fill the vectors with the data of the DB
+    let names = store.display_names().await?;
     for row in rows {
-        pokemon_attribs.id.push(row.get::<_, i32>("id"));
-        pokemon_attribs
-            .random_id
-            .push(row.get::<_, i32>("random_id"));
+        pokemon_attribs.id.push(row.id);
+        pokemon_attribs.random_id.push(row.random_id);
+        pokemon_attribs.pokedex_number.push(row.pokedex_number);
         pokemon_attribs
-            .pokedex_number
-            .push(row.get::<_, i32>("pokedex_number"));
-        pokemon_attribs.name.push(row.get::<_, String>("name"));
-        pokemon_attribs.height.push(row.get::<_, String>("height"));
-        pokemon_attribs.weight.push(row.get::<_, String>("weight"));
-        pokemon_attribs.hp.push(row.get::<_, i32>("hp"));
-        pokemon_attribs.attack.push(row.get::<_, i32>("attack"));
-        pokemon_attribs.defense.push(row.get::<_, i32>("defense"));
+            .display_name
+            .push(names.get(row.pokedex_number, &row.name));
+        pokemon_attribs.name.push(row.name);
+        pokemon_attribs.height.push(row.height);
+        pokemon_attribs.weight.push(row.weight);
+        pokemon_attribs.hp.push(row.hp);
+        pokemon_attribs.attack.push(row.attack);
+        pokemon_attribs.defense.push(row.defense);
+        pokemon_attribs.special_attack.push(row.special_attack);
+        pokemon_attribs.special_defense.push(row.special_defense);
+        pokemon_attribs.speed.push(row.speed);
+        pokemon_attribs.types.push(row.types);
+        pokemon_attribs.generation.push(row.generation);
         pokemon_attribs
-            .special_attack
-            .push(row.get::<_, i32>("special_attack"));
-        pokemon_attribs
-            .special_defense
-            .push(row.get::<_, i32>("special_defense"));
-        pokemon_attribs.speed.push(row.get::<_, i32>("speed"));
-        pokemon_attribs
-            .types
-            .push(row.get::<_, Option<String>>("types").unwrap_or_default());
+            .regional_number
+            .push(regional_numbers.get(&row.pokedex_number).copied());
     }
 
     // Creating the DataFrame with 'Polars'
@@ -85,6 +191,7 @@ pub fn load_pokemon_data(client: &mut Client) -> Result<DataFrame, Box<dyn std::
         Series::new("random_id".into(), pokemon_attribs.random_id),
         Series::new("pokedex_number".into(), pokemon_attribs.pokedex_number),
         Series::new("name".into(), pokemon_attribs.name),
+        Series::new("display_name".into(), pokemon_attribs.display_name),
         Series::new("height".into(), pokemon_attribs.height),
         Series::new("weight".into(), pokemon_attribs.weight),
         Series::new("hp".into(), pokemon_attribs.hp),
@@ -94,19 +201,30 @@ pub fn load_pokemon_data(client: &mut Client) -> Result<DataFrame, Box<dyn std::
         Series::new("special_defense".into(), pokemon_attribs.special_defense),
         Series::new("speed".into(), pokemon_attribs.speed),
         Series::new("types".into(), pokemon_attribs.types),
+        Series::new("generation".into(), pokemon_attribs.generation),
+        Series::new("regional_number".into(), pokemon_attribs.regional_number),
     ])?;
 
-    plot_distributions(&df.clone())?;
-    plot_type_combinations(&df.clone())?;
-    correlation_analysis(&df.clone())?;
+    // The plots are saved in the output directory of the config
+    fs::create_dir_all(output_dir).map_err(|e| {
+        Error::Plot(format!("Can't create the output directory {}: {e}", output_dir.display()))
+    })?;
+    plot_distributions(&df.clone(), plots, output_dir)?;
+    plot_type_combinations(&df.clone(), filter.label(), plots, output_dir)?;
+    correlation_analysis(&df.clone(), plots, output_dir)?;
 
     Ok(df)
 }
 
 // Plotting the distributions in a PNG image
-fn plot_distributions(df: &DataFrame) -> Result<(), Box<dyn std::error::Error>> {
+fn plot_distributions(
+    df: &DataFrame,
+    plots: &PlotConfig,
+    output_dir: &Path,
+) -> Result<(), Error> {
     // Creating the drawing area (Canvas)
-    let root = BitMapBackend::new("pokemon_distribution.png", (1600, 800)).into_drawing_area();
+    let path = output_dir.join("pokemon_distribution.png");
+    let root = BitMapBackend::new(&path, plots.size(1600, 800)).into_drawing_area();
     root.fill(&WHITE)?;
 
     // Split the canvas in a 2x4 grid
@@ -207,7 +325,10 @@ fn plot_distributions(df: &DataFrame) -> Result<(), Box<dyn std::error::Error>>
             .margin(5)
             .set_label_area_size(LabelAreaPosition::Left, 40)
             .set_label_area_size(LabelAreaPosition::Bottom, 30)
-            .caption(format!("Distribution of {}", column), ("sans-serif", 20))
+            .caption(
+                format!("Distribution of {}", column),
+                (plots.font.as_str(), plots.font_size(20.0)),
+            )
             .build_cartesian_2d((min..max).step(bin_size), 0f64..(y_max * 1.1))?;
 
         chart.configure_mesh().draw()?;
@@ -228,8 +349,14 @@ fn plot_distributions(df: &DataFrame) -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
-fn plot_type_combinations(df: &DataFrame) -> Result<(), Box<dyn Error>> {
-    let root = BitMapBackend::new("type_combinations.png", (2000, 1000)).into_drawing_area();
+fn plot_type_combinations(
+    df: &DataFrame,
+    filter_label: Option<String>,
+    plots: &PlotConfig,
+    output_dir: &Path,
+) -> Result<(), Error> {
+    let path = output_dir.join("type_combinations.png");
+    let root = BitMapBackend::new(&path, plots.size(2000, 1000)).into_drawing_area();
     root.fill(&WHITE)?;
 
     // Fetching the colum "types" from the DataFrame
@@ -246,22 +373,26 @@ fn plot_type_combinations(df: &DataFrame) -> Result<(), Box<dyn Error>> {
 
     // Casting the HashMap to vector and sorting from largest to smallest and taken the first 20
     let mut type_count_vec: Vec<_> = type_counts.into_iter().collect();
-    type_count_vec.sort_by(|a, b| b.1.cmp(&a.1));
+    type_count_vec.sort_by_key(|b| std::cmp::Reverse(b.1));
     type_count_vec.truncate(20);
 
     // Separating the names of the types and their counts in different vectors
     let type_names: Vec<String> = type_count_vec
         .iter()
-        .map(|(name, _)| name.clone())
+        .map(|(name, _)| type_display_name(name))
         .collect();
     let counts: Vec<u32> = type_count_vec.iter().map(|(_, count)| *count).collect();
 
     let max_count = *counts.iter().max().unwrap_or(&0);
+    let caption = match filter_label {
+        Some(label) => format!("Top 20 Type Combinations ({label})"),
+        None => "Top 20 Type Combinations".to_string(),
+    };
     let mut chart = ChartBuilder::on(&root)
         .x_label_area_size(40)
         .y_label_area_size(100)
         .margin(5)
-        .caption("Top 20 Type Combinations", ("sans-serif", 50.0))
+        .caption(caption, (plots.font.as_str(), plots.font_size(50.0)))
         .build_cartesian_2d(0..max_count, 0..type_names.len())?;
 
     chart
@@ -270,7 +401,7 @@ fn plot_type_combinations(df: &DataFrame) -> Result<(), Box<dyn Error>> {
         .disable_y_mesh()
         .y_desc("Type Combination")
         .x_desc("Count")
-        .axis_desc_style(("sans-serif", 30))
+        .axis_desc_style((plots.font.as_str(), plots.font_size(30.0)))
         .y_labels(type_names.len())
         .draw()?;
 
@@ -288,7 +419,9 @@ fn plot_type_combinations(df: &DataFrame) -> Result<(), Box<dyn Error>> {
         Text::new(
             name.to_string(),
             (0, i),
-            ("sans-serif", 20).into_font().color(&BLACK),
+            (plots.font.as_str(), plots.font_size(20.0))
+                .into_font()
+                .color(&BLACK),
         )
     }))?;
 
@@ -303,6 +436,6 @@ fn plot_type_combinations(df: &DataFrame) -> Result<(), Box<dyn Error>> {
 
     root.present()?;
 
-    println!("Chart has been saved to type_combinations.png");
+    println!("Chart has been saved to {}", path.display());
     Ok(())
 }
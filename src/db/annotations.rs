@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// A note of a user about a pokemon, in markdown
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub text: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/* What a user wrote about a pokemon: if it's a favorite, their tags and their note. It's saved in
+'user_favorites', 'user_tags' and 'user_notes', a pokemon without any of them isn't annotated*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub pokedex_number: i32,
+    pub favorite: bool,
+    // Sorted, e.g. ["ou-candidates", "rain"]
+    pub tags: Vec<String>,
+    pub note: Option<Note>,
+}
+
+impl Annotation {
+    pub fn new(pokedex_number: i32) -> Self {
+        Annotation {
+            pokedex_number,
+            favorite: false,
+            tags: Vec::new(),
+            note: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.favorite && self.tags.is_empty() && self.note.is_none()
+    }
+}
+
+/* The annotations of the rows of the three tables, by pokedex number. Used by the SQL backends,
+they read each table on its own*/
+pub fn group_annotations(
+    favorites: Vec<i32>,
+    tags: Vec<(i32, String)>,
+    notes: Vec<(i32, Note)>,
+) -> Vec<Annotation> {
+    let mut annotations: BTreeMap<i32, Annotation> = BTreeMap::new();
+    for pokedex_number in favorites {
+        entry(&mut annotations, pokedex_number).favorite = true;
+    }
+    for (pokedex_number, tag) in tags {
+        entry(&mut annotations, pokedex_number).tags.push(tag);
+    }
+    for (pokedex_number, note) in notes {
+        entry(&mut annotations, pokedex_number).note = Some(note);
+    }
+
+    annotations
+        .into_values()
+        .map(|mut annotation| {
+            annotation.tags.sort();
+            annotation
+        })
+        .collect()
+}
+
+fn entry(annotations: &mut BTreeMap<i32, Annotation>, pokedex_number: i32) -> &mut Annotation {
+    annotations
+        .entry(pokedex_number)
+        .or_insert_with(|| Annotation::new(pokedex_number))
+}
+
+// The tag as it's saved, e.g. "OU Candidates" is "ou-candidates"
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/* The pokemon a user annotated, for the searches and the analyses. Every filter that is set must
+match, without any of them every annotated pokemon matches*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnnotationFilter {
+    pub user_id: i32,
+    pub favorites: bool,
+    // The pokemon must have every tag
+    pub tags: Vec<String>,
+    // Text the note must have, without caring about the case
+    pub note: Option<String>,
+}
+
+impl AnnotationFilter {
+    // Used by the backends without SQL
+    pub fn matches(&self, annotation: &Annotation) -> bool {
+        !annotation.is_empty()
+            && (!self.favorites || annotation.favorite)
+            && self.tags.iter().all(|tag| annotation.tags.contains(tag))
+            && self.note.as_ref().is_none_or(|text| {
+                annotation
+                    .note
+                    .as_ref()
+                    .is_some_and(|note| note.text.to_lowercase().contains(&text.to_lowercase()))
+            })
+    }
+
+    /* The LIKE pattern of the note for the SQL backends, in lowercase and with "%", "_" and "\"
+    escaped so they are searched as they are*/
+    pub fn note_pattern(&self) -> Option<String> {
+        self.note.as_ref().map(|text| {
+            let escaped = text
+                .to_lowercase()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{escaped}%")
+        })
+    }
+
+    // For the titles of the plots, e.g. "favorites tagged ou-candidates"
+    pub fn label(&self) -> String {
+        let mut parts = vec![if self.favorites {
+            "favorites".to_string()
+        } else {
+            "annotations".to_string()
+        }];
+        if !self.tags.is_empty() {
+            parts.push(format!("tagged {}", self.tags.join(", ")));
+        }
+        if let Some(text) = &self.note {
+            parts.push(format!("with '{text}' in the note"));
+        }
+        parts.join(" ")
+    }
+}
@@ -0,0 +1,63 @@
+use crate::db::pokemon_tables::{
+    Learnset, PokemonForm, PokemonHistory, PokemonRecord, SpeciesRecord,
+};
+use crate::db::store::PokedexStore;
+use crate::error::Error;
+
+/* Parsing again the pokemon documents saved in 'raw_documents' and upserting them, with the forms of
+the varieties. When a new column is added to the tables it can be filled from the archive without
+requests to the API. Returns how many pokemon were upserted*/
+pub async fn backfill_pokemon_from_archive(
+    store: &dyn PokedexStore,
+    api_base_url: &str,
+) -> Result<usize, Error> {
+    let documents = store.load_raw_documents(api_base_url).await?;
+    let mut upserted = 0;
+
+    for document in documents {
+        match PokemonForm::from_api(&document.body) {
+            Ok(forms) => {
+                for form in forms {
+                    store.upsert_form(&form).await?;
+                }
+            }
+            Err(e) => eprintln!("Skipping the forms of {}: {e}", document.url),
+        }
+        // The varieties are forms of their species, not pokemon of the pokedex
+        if document.body["is_default"].as_bool() == Some(false) {
+            continue;
+        }
+
+        match PokemonRecord::from_api(&document.body) {
+            Ok(pokemon) => {
+                store.upsert_pokemon(&pokemon).await?;
+                upserted += 1;
+            }
+            Err(e) => {
+                eprintln!("Skipping archived document {}: {e}", document.url);
+                continue;
+            }
+        }
+        match PokemonHistory::from_api(&document.body) {
+            Ok(history) => store.upsert_pokemon_history(&history).await?,
+            Err(e) => eprintln!("Skipping the past values of {}: {e}", document.url),
+        }
+        match Learnset::from_api(&document.body) {
+            Ok(learnset) => store.upsert_learnset(&learnset).await?,
+            Err(e) => eprintln!("Skipping the moves of {}: {e}", document.url),
+        }
+
+        // The names of the species too, if its document was archived
+        let Some(species_url) = document.body["species"]["url"].as_str() else {
+            continue;
+        };
+        for species_document in store.load_raw_documents(species_url).await? {
+            match SpeciesRecord::from_api(&species_document.body) {
+                Ok(species) => store.upsert_species(&species).await?,
+                Err(e) => eprintln!("Skipping archived document {}: {e}", species_document.url),
+            }
+        }
+    }
+
+    Ok(upserted)
+}
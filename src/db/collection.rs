@@ -0,0 +1,229 @@
+use crate::db::annotations::Annotation;
+use crate::db::pokemon_tables::{RegionalDex, SpeciesRecord};
+use crate::db::teams::Team;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+// A user of the pokedex, each one has their own collection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+}
+
+/* A row of 'user_pokemon', a pokemon a user has caught. The same species can be saved again with
+another form or shiny, e.g. vulpix and alolan vulpix. The default form is an empty string*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaughtPokemon {
+    pub pokedex_number: i32,
+    pub form: String,
+    pub shiny: bool,
+    // The game it came from, e.g. "sword"
+    pub game: Option<String>,
+    pub caught_at: DateTime<Utc>,
+}
+
+/* A user with every pokemon they caught, their teams and their favorites, tags and notes, how the
+users are saved in the snapshots*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserCollection {
+    pub name: String,
+    pub pokemon: Vec<CaughtPokemon>,
+    // Only in the snapshots of version 7
+    #[serde(default)]
+    pub teams: Vec<Team>,
+    // Only in the snapshots of version 8
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+}
+
+// The form as it's saved, e.g. "Alola" is "alola". The API names them like that
+pub fn normalize_form(form: &str) -> String {
+    form.trim().to_lowercase().replace(' ', "-")
+}
+
+// The species of a living dex a user has, e.g. 140 of the 151 of generation 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    // The generation or the pokedex, e.g. "Generation 1" or "galar"
+    pub label: String,
+    pub caught: usize,
+    pub total: usize,
+    // The pokedex numbers that are missing, in order
+    pub missing: Vec<i32>,
+}
+
+impl Completion {
+    fn new(label: String, species: impl Iterator<Item = i32>, caught: &HashSet<i32>) -> Self {
+        let mut total = 0;
+        let mut missing = Vec::new();
+        for pokedex_number in species {
+            total += 1;
+            if !caught.contains(&pokedex_number) {
+                missing.push(pokedex_number);
+            }
+        }
+
+        Completion {
+            label,
+            caught: total - missing.len(),
+            total,
+            missing,
+        }
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.caught as f64 * 100.0 / self.total as f64
+    }
+}
+
+/* The species that count for a living dex, one of each with any form. With 'shiny' only the shiny
+ones count*/
+fn species_caught(caught: &[CaughtPokemon], shiny: bool) -> HashSet<i32> {
+    caught
+        .iter()
+        .filter(|pokemon| !shiny || pokemon.shiny)
+        .map(|pokemon| pokemon.pokedex_number)
+        .collect()
+}
+
+// The living dex of every generation, from the generation of the saved species
+pub fn completion_by_generation(
+    species: &[SpeciesRecord],
+    caught: &[CaughtPokemon],
+    shiny: bool,
+) -> Vec<Completion> {
+    let caught = species_caught(caught, shiny);
+    let mut generations: BTreeMap<i32, Vec<i32>> = BTreeMap::new();
+    for record in species {
+        if let Some(generation) = record.generation {
+            generations
+                .entry(generation)
+                .or_default()
+                .push(record.pokedex_number);
+        }
+    }
+
+    generations
+        .into_iter()
+        .map(|(generation, mut numbers)| {
+            numbers.sort();
+            Completion::new(
+                format!("Generation {generation}"),
+                numbers.into_iter(),
+                &caught,
+            )
+        })
+        .collect()
+}
+
+// The living dex of a pokedex, e.g. "galar", its species in the order of the pokedex
+pub fn completion_of_dex(dex: &RegionalDex, caught: &[CaughtPokemon], shiny: bool) -> Completion {
+    let caught = species_caught(caught, shiny);
+    Completion::new(
+        dex.name.clone(),
+        dex.entries.iter().map(|entry| entry.pokedex_number),
+        &caught,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::pokemon_tables::RegionalDexEntry;
+
+    fn caught(pokedex_number: i32, form: &str, shiny: bool) -> CaughtPokemon {
+        CaughtPokemon {
+            pokedex_number,
+            form: form.to_string(),
+            shiny,
+            game: None,
+            caught_at: Utc::now(),
+        }
+    }
+
+    fn species(pokedex_number: i32, generation: Option<i32>) -> SpeciesRecord {
+        SpeciesRecord {
+            pokedex_number,
+            generation,
+            names: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn normalizes_the_forms() {
+        assert_eq!(normalize_form("Alola"), "alola");
+        assert_eq!(normalize_form(" Galar Zen "), "galar-zen");
+        assert_eq!(normalize_form(""), "");
+    }
+
+    #[test]
+    fn counts_each_species_once_by_generation() {
+        let species = [
+            species(152, Some(2)),
+            species(1, Some(1)),
+            species(37, Some(1)),
+            species(4, Some(1)),
+            species(10000, None),
+        ];
+        let caught = [
+            caught(37, "", false),
+            caught(37, "alola", true),
+            caught(4, "", false),
+        ];
+        let completion = completion_by_generation(&species, &caught, false);
+
+        assert_eq!(completion.len(), 2);
+        assert_eq!(completion[0].label, "Generation 1");
+        assert_eq!(completion[0].caught, 2);
+        assert_eq!(completion[0].total, 3);
+        assert_eq!(completion[0].missing, [1]);
+        assert!((completion[0].percent() - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(completion[1].missing, [152]);
+        assert_eq!(completion[1].percent(), 0.0);
+    }
+
+    #[test]
+    fn only_the_shiny_ones_count_for_a_shiny_dex() {
+        let species = [species(1, Some(1)), species(37, Some(1))];
+        let caught = [caught(1, "", false), caught(37, "alola", true)];
+        let completion = completion_by_generation(&species, &caught, true);
+
+        assert_eq!(completion[0].caught, 1);
+        assert_eq!(completion[0].missing, [1]);
+    }
+
+    #[test]
+    fn keeps_the_order_of_the_regional_dex() {
+        let dex = RegionalDex {
+            name: "galar".to_string(),
+            region: Some("galar".to_string()),
+            is_main_series: true,
+            entries: [810, 25, 52]
+                .into_iter()
+                .enumerate()
+                .map(|(i, pokedex_number)| RegionalDexEntry {
+                    entry_number: i as i32 + 1,
+                    pokedex_number,
+                })
+                .collect(),
+        };
+        let completion = completion_of_dex(&dex, &[caught(25, "", false)], false);
+
+        assert_eq!(completion.label, "galar");
+        assert_eq!(completion.missing, [810, 52]);
+        assert_eq!((completion.caught, completion.total), (1, 3));
+    }
+
+    #[test]
+    fn an_empty_dex_is_zero_percent() {
+        let completion = Completion::new("empty".to_string(), std::iter::empty(), &HashSet::new());
+
+        assert_eq!(completion.total, 0);
+        assert_eq!(completion.percent(), 0.0);
+    }
+}
@@ -1,79 +1,139 @@
-use crate::{db::pokemon_tables::create_pokemon_tables, users::user_config::setup_env_file};
-use postgres::{Client, Error, NoTls};
+use crate::config::Config;
+use crate::db::games::Game;
+use crate::db::pokemon_tables::RawDocument;
+use crate::db::store::{open_store, PokedexStore};
+use crate::error::Error;
+use chrono::{DateTime, Utc};
 use reqwest::Client as ReqwestClient;
 use serde_json::Value;
-use std::env;
+use std::fs::{self, File};
 
-// Struct for the Pokemon API and the client of the DB
-#[allow(dead_code)]
+// Struct for the Pokemon API and the store of the DB
 pub struct AppConfig {
-    pub db_client: Client,
+    pub store: Box<dyn PokedexStore>,
+    // One HTTP client for every request, it keeps the connections with the API open
     pub api_client: ReqwestClient,
-    pub api_base_url: String,
+    // Every setting, from the config files, the environment and the CLI
+    pub settings: Config,
 }
 
 // Encapsulated all the configurations
-#[allow(dead_code)]
 impl AppConfig {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let db_client = Self::enable_connection()?;
-        let (api_client, api_base_url) = Self::config_pokemon_api()?;
+    pub async fn new(settings: Config) -> Result<Self, Error> {
+        // Establishing connection, the backend depends on the scheme of the URL
+        let store = open_store(settings.database_url()?, &settings.ssl).await?;
 
         Ok(AppConfig {
-            db_client,
-            api_client,
-            api_base_url,
+            store,
+            api_client: ReqwestClient::new(),
+            settings,
         })
     }
 
-    pub fn init_database(&mut self) -> Result<(), Error> {
-        create_pokemon_tables(&mut self.db_client)
+    pub async fn init_database(&self) -> Result<(), Error> {
+        self.store.create_tables().await
     }
 
-    pub fn setup_env() -> Result<(), Box<dyn std::error::Error>> {
-        setup_env_file()?;
-
-        Ok(())
+    // The URL of the pokemon endpoint of the API
+    pub fn api_base_url(&self) -> &str {
+        &self.settings.api.base_url
     }
 
-    // Connecting to the database URL
-    fn enable_connection() -> Result<Client, Error> {
-        // Get the databse URL from the environment variable
-        let database_url =
-            env::var("DATABASE_URL").expect("DATABASE_URL must be set in the .env file. Denied");
-        // Establishing connection
-        Client::connect(&database_url, NoTls)
-    }
+    // The target game with its version group, None without one
+    pub async fn game(&self) -> Result<Option<Game>, Error> {
+        let Some(name) = &self.settings.game else {
+            return Ok(None);
+        };
 
-    fn config_pokemon_api() -> Result<(ReqwestClient, String), Box<dyn std::error::Error>> {
-        let api_base_url = env::var("POKEMON_BASE_API_URL")
-            .expect("POKEMON_BASE_API_URL must be set in .env file");
+        match self.store.find_game(name).await? {
+            Some(game) => Ok(Some(game)),
+            None => Err(Error::Config(format!(
+                "Unknown game '{name}'. Run 'pokedb sync' or see 'pokedb games'"
+            ))),
+        }
+    }
 
-        let client = ReqwestClient::new();
+    pub async fn fetch_pokemon(&self, pokemon_id: u32) -> Result<Option<Value>, Error> {
+        let url = format!("{}{}", self.api_base_url(), pokemon_id);
+        self.fetch_resource(&url).await
+    }
 
-        Ok((client, api_base_url))
+    /* The URL of another resource of the API, next to the pokemon one. The base URL is the one
+    of the pokemon, e.g. "https://pokeapi.co/api/v2/pokemon/" gives ".../api/v2/pokedex/"*/
+    pub fn resource_url(&self, resource: &str) -> String {
+        let base = self.api_base_url().trim_end_matches('/');
+        let root = base.strip_suffix("/pokemon").unwrap_or(base);
+        format!("{root}/{resource}/")
     }
 
-    pub fn fetch_pokemon(
-        &self,
-        pokemon_id: u32,
-    ) -> Result<Option<Value>, Box<dyn std::error::Error>> {
-        // Connecting with the .env variable
-        let api_base_url = env::var("POKEMON_BASE_API_URL")
-            .expect("POKEMON_BASE_API_URL must be set in .env file.");
-        // Like the postgres crate uses 'Client' too, at compiling show an error. Just avoiding that
-        let client = reqwest::blocking::Client::new();
-        let url = format!("{}{}", api_base_url, pokemon_id);
+    /* Every request to the API goes through here, so each document is archived in
+    'raw_documents' before it's parsed. None when the API doesn't have the resource. With the
+    cache enabled, a response saved before is used while it's newer than the max age*/
+    pub async fn fetch_resource(&self, url: &str) -> Result<Option<Value>, Error> {
+        if let Some((bytes, fetched_at)) = self.read_cache(url) {
+            /* The document keeps the time it was fetched, so the archive has the same row and
+            not a fetch that didn't happen. It's still archived for the databases that don't
+            have it, e.g. a new one with the same cache*/
+            let document = RawDocument {
+                fetched_at,
+                ..RawDocument::new(url, &bytes)?
+            };
+            self.store.archive_document(&document).await?;
+            return Ok(Some(document.body));
+        }
+
         // Obtain a GET method for the HTTP request
-        let response = client.get(&url).send()?;
+        let response = self.api_client.get(url).send().await?;
 
         // Enabling connection with the API data
-        if response.status().is_success() {
-            let pokemon_data = response.json::<Value>()?;
-            Ok(Some(pokemon_data))
-        } else {
-            eprintln!("Error fetching pokemon {}{}", pokemon_id, response.status());
-            Ok(None)
+        if !response.status().is_success() {
+            eprintln!("Error fetching {url}: {}", response.status());
+            return Ok(None);
+        }
+
+        // The hash is of the bytes received, before they are parsed
+        let bytes = response.bytes().await?;
+        let document = RawDocument::new(url, &bytes)?;
+        self.store.archive_document(&document).await?;
+        self.write_cache(url, &bytes, document.fetched_at)?;
+
+        Ok(Some(document.body))
+    }
+
+    /* The cached response of the URL with the time it was fetched, None when there isn't one or
+    it's too old. The time of the file is the one of the fetch, 'write_cache' sets it*/
+    fn read_cache(&self, url: &str) -> Option<(Vec<u8>, DateTime<Utc>)> {
+        let cache = &self.settings.cache;
+        if !cache.enabled {
+            return None;
+        }
+        let path = cache.path_of(url);
+        let modified = fs::metadata(&path).ok()?.modified().ok()?;
+        if modified.elapsed().ok()? > cache.max_age() {
+            return None;
+        }
+
+        Some((fs::read(path).ok()?, modified.into()))
+    }
+
+    fn write_cache(&self, url: &str, bytes: &[u8], fetched_at: DateTime<Utc>) -> Result<(), Error> {
+        let cache = &self.settings.cache;
+        if !cache.enabled {
+            return Ok(());
         }
+        fs::create_dir_all(&cache.dir).map_err(|e| {
+            Error::Config(format!(
+                "Can't create the cache directory {}: {e}",
+                cache.dir.display()
+            ))
+        })?;
+        let path = cache.path_of(url);
+        fs::write(&path, bytes)?;
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(fetched_at.into())?;
+
+        Ok(())
     }
 }
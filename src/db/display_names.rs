@@ -0,0 +1,181 @@
+use crate::db::pokemon_tables::SpeciesRecord;
+use std::collections::HashMap;
+
+/* The slugs whose name can't be made from their words, because of punctuation, symbols, accents
+or dashes that are part of the name*/
+const CURATED_NAMES: &[(&str, &str)] = &[
+    ("nidoran-f", "Nidoran♀"),
+    ("nidoran-m", "Nidoran♂"),
+    ("farfetchd", "Farfetch'd"),
+    ("mr-mime", "Mr. Mime"),
+    ("ho-oh", "Ho-Oh"),
+    ("mime-jr", "Mime Jr."),
+    ("porygon-z", "Porygon-Z"),
+    ("flabebe", "Flabébé"),
+    ("type-null", "Type: Null"),
+    ("jangmo-o", "Jangmo-o"),
+    ("hakamo-o", "Hakamo-o"),
+    ("kommo-o", "Kommo-o"),
+    ("sirfetchd", "Sirfetch'd"),
+    ("mr-rime", "Mr. Rime"),
+    ("wo-chien", "Wo-Chien"),
+    ("chien-pao", "Chien-Pao"),
+    ("ting-lu", "Ting-Lu"),
+    ("chi-yu", "Chi-Yu"),
+];
+
+// The first letter in uppercase, e.g. "koko" is "Koko"
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// The words of a slug capitalized, e.g. "tapu-koko" is "Tapu Koko"
+fn capitalize_words(slug: &str) -> String {
+    slug.split('-')
+        .filter(|word| !word.is_empty())
+        .map(capitalize)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/* The name of a slug of the API when the species doesn't have an English name. The curated ones
+first, also when the slug is one of their forms, e.g. "mr-mime-galar" is "Mr. Mime Galar". The
+others are their words capitalized*/
+pub fn name_from_slug(slug: &str) -> String {
+    for (curated, name) in CURATED_NAMES {
+        if slug == *curated {
+            return name.to_string();
+        }
+        if let Some(form) = slug
+            .strip_prefix(curated)
+            .and_then(|rest| rest.strip_prefix('-'))
+        {
+            return format!("{name} {}", capitalize_words(form));
+        }
+    }
+
+    capitalize_words(slug)
+}
+
+// A type or a combination of types as they are shown, e.g. "grass, poison" is "Grass / Poison"
+pub fn type_display_name(types: &str) -> String {
+    types
+        .split(", ")
+        .map(capitalize)
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/* The names of the pokemon as they are shown in the CLI and the plots. The English name of the
+species when it was fetched, e.g. "Mr. Mime", and the name of the slug when it wasn't*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayNames {
+    // By pokedex number
+    english: HashMap<i32, String>,
+}
+
+impl DisplayNames {
+    pub fn from_species(species: &[SpeciesRecord]) -> Self {
+        let english = species
+            .iter()
+            .filter_map(|record| {
+                record
+                    .names
+                    .iter()
+                    .find(|name| name.language == "en")
+                    .map(|name| (record.pokedex_number, name.name.clone()))
+            })
+            .collect();
+
+        DisplayNames { english }
+    }
+
+    pub fn get(&self, pokedex_number: i32, slug: &str) -> String {
+        match self.english.get(&pokedex_number) {
+            Some(name) => name.clone(),
+            None => name_from_slug(slug),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::pokemon_tables::LocalizedName;
+
+    #[test]
+    fn the_curated_slugs_keep_their_symbols() {
+        assert_eq!(name_from_slug("mr-mime"), "Mr. Mime");
+        assert_eq!(name_from_slug("nidoran-f"), "Nidoran♀");
+        assert_eq!(name_from_slug("nidoran-m"), "Nidoran♂");
+        assert_eq!(name_from_slug("farfetchd"), "Farfetch'd");
+        assert_eq!(name_from_slug("type-null"), "Type: Null");
+        assert_eq!(name_from_slug("jangmo-o"), "Jangmo-o");
+        assert_eq!(name_from_slug("flabebe"), "Flabébé");
+    }
+
+    #[test]
+    fn the_other_slugs_are_their_words() {
+        assert_eq!(name_from_slug("pikachu"), "Pikachu");
+        assert_eq!(name_from_slug("tapu-koko"), "Tapu Koko");
+        assert_eq!(name_from_slug("vulpix-alola"), "Vulpix Alola");
+        assert_eq!(name_from_slug("meowth-galar"), "Meowth Galar");
+        assert_eq!(name_from_slug(""), "");
+    }
+
+    #[test]
+    fn the_forms_of_the_curated_slugs_keep_their_symbols() {
+        assert_eq!(name_from_slug("mr-mime-galar"), "Mr. Mime Galar");
+        assert_eq!(name_from_slug("farfetchd-galar"), "Farfetch'd Galar");
+        // Only a whole word of the slug is a form, "mr-mimes" isn't one of "mr-mime"
+        assert_eq!(name_from_slug("mr-mimes"), "Mr Mimes");
+    }
+
+    #[test]
+    fn the_types_are_capitalized() {
+        assert_eq!(type_display_name("fire"), "Fire");
+        assert_eq!(type_display_name("grass, poison"), "Grass / Poison");
+    }
+
+    #[test]
+    fn the_english_name_of_the_species_comes_first() {
+        let species = [
+            SpeciesRecord {
+                pokedex_number: 122,
+                generation: Some(1),
+                names: vec![
+                    LocalizedName {
+                        language: "fr".to_string(),
+                        name: "M. Mime".to_string(),
+                    },
+                    LocalizedName {
+                        language: "en".to_string(),
+                        name: "Mr. Mime".to_string(),
+                    },
+                ],
+            },
+            SpeciesRecord {
+                pokedex_number: 32,
+                generation: Some(1),
+                names: vec![LocalizedName {
+                    language: "ja".to_string(),
+                    name: "ニドラン♂".to_string(),
+                }],
+            },
+        ];
+        let names = DisplayNames::from_species(&species);
+
+        assert_eq!(names.get(122, "mr-mime"), "Mr. Mime");
+        // Without an English name, and for the species that weren't fetched, it's the slug
+        assert_eq!(names.get(32, "nidoran-m"), "Nidoran♂");
+        assert_eq!(names.get(29, "nidoran-f"), "Nidoran♀");
+        assert_eq!(
+            DisplayNames::default().get(37, "vulpix-alola"),
+            "Vulpix Alola"
+        );
+    }
+}
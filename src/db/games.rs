@@ -0,0 +1,55 @@
+use crate::db::pokemon_tables::{GameVersion, VersionGroup};
+use crate::db::query::GenerationRange;
+
+/* A game resolved with its version group. It's the "target game" of the queries and analyses, so
+everything that changes between the games is read for the same one*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Game {
+    // None when the game was given by its version group, e.g. "sword-shield"
+    pub version: Option<GameVersion>,
+    pub group: VersionGroup,
+}
+
+impl Game {
+    // The version, e.g. "sword", or the group when there isn't one
+    pub fn name(&self) -> &str {
+        self.version
+            .as_ref()
+            .map_or(&self.group.name, |version| &version.name)
+    }
+
+    pub fn generation(&self) -> Option<i32> {
+        self.group.generation
+    }
+
+    // The generations of the pokemon that exist in the game, from the first one to its own
+    pub fn generations(&self) -> Option<GenerationRange> {
+        self.generation().map(|generation| GenerationRange {
+            from: 1,
+            to: generation,
+        })
+    }
+
+    // The main pokedex of the game, e.g. "galar" for sword
+    pub fn pokedex(&self) -> Option<&str> {
+        self.group.pokedexes.first().map(String::as_str)
+    }
+}
+
+/* Finding a game by its version or its version group, e.g. "sword", "Sword" or "sword-shield".
+Spaces are taken as dashes, like in the names of the API*/
+pub fn find_game(groups: &[VersionGroup], versions: &[GameVersion], name: &str) -> Option<Game> {
+    let name = name.trim().to_lowercase().replace(' ', "-");
+    let group_named = |group_name: &str| groups.iter().find(|group| group.name == group_name);
+
+    if let Some(version) = versions.iter().find(|version| version.name == name) {
+        return group_named(&version.version_group).map(|group| Game {
+            version: Some(version.clone()),
+            group: group.clone(),
+        });
+    }
+    group_named(&name).map(|group| Game {
+        version: None,
+        group: group.clone(),
+    })
+}
@@ -0,0 +1,199 @@
+use crate::db::connection::AppConfig;
+use crate::db::pokemon_tables::{
+    GameVersion, IngestionRun, Learnset, PokemonForm, PokemonHistory, PokemonRecord, RegionalDex,
+    SpeciesRecord, UpsertOutcome, VersionGroup,
+};
+use crate::error::Error;
+use chrono::Utc;
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/* Fetching the pokemon from 1 to 'total' and upserting them. Several pokemon are requested at
+once, every task shares the HTTP client and the store. The run is saved in 'ingestion_runs' and
+every field an upsert changes in 'pokemon_changes'*/
+pub async fn sync_pokemon(
+    config: &AppConfig,
+    total: u32,
+    concurrency: usize,
+) -> Result<IngestionRun, Error> {
+    let mut run = config
+        .store
+        .start_ingestion_run(config.api_base_url())
+        .await?;
+    let run_id = run.id;
+
+    let mut inserts = stream::iter(1..=total)
+        .map(|i| async move {
+            let result = match config.fetch_pokemon(i).await {
+                Ok(Some(pokemon_data)) => ingest_pokemon(config, run_id, &pokemon_data)
+                    .await
+                    .map(Some),
+                Ok(None) => Ok(None),
+                Err(e) => Err(e),
+            };
+            (i, result)
+        })
+        .buffer_unordered(concurrency);
+
+    while let Some((i, result)) = inserts.next().await {
+        match result {
+            Ok(Some(outcome)) => {
+                match outcome {
+                    UpsertOutcome::Inserted => run.inserted += 1,
+                    UpsertOutcome::Updated(_) => run.updated += 1,
+                    UpsertOutcome::Unchanged => run.unchanged += 1,
+                }
+                println!("Inserted pokemon {i}");
+            }
+            Ok(None) => {
+                run.skipped += 1;
+                println!("Unavaiable to obtain data from pokemon {i}. Skkiping");
+            }
+            Err(e) => {
+                run.failed += 1;
+                eprintln!("Error to fetching pokemon {i}: {e}");
+            }
+        }
+    }
+
+    let finished_at = Utc::now();
+    run.duration_ms = Some((finished_at - run.started_at).num_milliseconds());
+    run.finished_at = Some(finished_at);
+    config.store.finish_ingestion_run(&run).await?;
+
+    Ok(run)
+}
+
+/* The URLs of every resource of a list of the API, e.g. every pokedex. The list comes in pages,
+the 'next' link of each one is followed until it's null*/
+async fn list_resource_urls(config: &AppConfig, resource: &str) -> Result<Vec<String>, Error> {
+    let mut urls = Vec::new();
+    let mut next = Some(format!("{}?limit=100", config.resource_url(resource)));
+    let mut seen = HashSet::new();
+    // A mirror that links a page to itself would loop forever
+    while let Some(page_url) = next.filter(|url| seen.insert(url.clone())) {
+        let Some(page) = config.fetch_resource(&page_url).await? else {
+            return Err(Error::Http {
+                message: format!("The API doesn't have the list of {resource}"),
+                url: page_url,
+            });
+        };
+
+        urls.extend(
+            page["results"]
+                .as_array()
+                .ok_or_else(|| Error::missing_field("results"))?
+                .iter()
+                .filter_map(|result| result["url"].as_str())
+                .map(str::to_string),
+        );
+        next = page["next"].as_str().map(str::to_string);
+    }
+
+    Ok(urls)
+}
+
+/* Fetching every pokedex of the API (national, kanto, galar...) with the number of each species
+in it. A pokedex that can't be fetched is skipped, the others are still saved. Returns how many
+were saved*/
+pub async fn sync_regional_dexes(config: &AppConfig) -> Result<usize, Error> {
+    let mut saved = 0;
+    for url in list_resource_urls(config, "pokedex").await? {
+        let dex = match config.fetch_resource(&url).await {
+            Ok(Some(pokedex_data)) => RegionalDex::from_api(&pokedex_data),
+            Ok(None) => continue,
+            Err(e) => Err(e),
+        };
+        match dex {
+            Ok(dex) => {
+                config.store.upsert_regional_dex(&dex).await?;
+                saved += 1;
+            }
+            Err(e) => eprintln!("Error to fetching pokedex {url}: {e}"),
+        }
+    }
+
+    Ok(saved)
+}
+
+/* Fetching the version groups (red-blue, sword-shield...) with their generation, regions and
+pokedexes, and then the versions of each one. Returns how many groups and versions were saved*/
+pub async fn sync_games(config: &AppConfig) -> Result<(usize, usize), Error> {
+    let mut groups = 0;
+    for url in list_resource_urls(config, "version-group").await? {
+        let group = match config.fetch_resource(&url).await {
+            Ok(Some(group_data)) => VersionGroup::from_api(&group_data),
+            Ok(None) => continue,
+            Err(e) => Err(e),
+        };
+        match group {
+            Ok(group) => {
+                config.store.upsert_version_group(&group).await?;
+                groups += 1;
+            }
+            Err(e) => eprintln!("Error to fetching version group {url}: {e}"),
+        }
+    }
+
+    let mut versions = 0;
+    for url in list_resource_urls(config, "version").await? {
+        let version = match config.fetch_resource(&url).await {
+            Ok(Some(version_data)) => GameVersion::from_api(&version_data),
+            Ok(None) => continue,
+            Err(e) => Err(e),
+        };
+        match version {
+            Ok(version) => {
+                config.store.upsert_version(&version).await?;
+                versions += 1;
+            }
+            Err(e) => eprintln!("Error to fetching version {url}: {e}"),
+        }
+    }
+
+    Ok((groups, versions))
+}
+
+/* Upserting the pokemon, recording what changed and saving its past types and stats, the moves it
+learns, the localized names of its species and its forms. The species is another request to the
+API, its URL comes in the pokemon document, and so is each variety of the species*/
+async fn ingest_pokemon(
+    config: &AppConfig,
+    run_id: i32,
+    pokemon_data: &Value,
+) -> Result<UpsertOutcome, Error> {
+    let pokemon = PokemonRecord::from_api(pokemon_data)?;
+    let outcome = config.store.upsert_pokemon(&pokemon).await?;
+    if let UpsertOutcome::Updated(changes) = &outcome {
+        config
+            .store
+            .record_changes(run_id, &pokemon, changes)
+            .await?;
+    }
+    let history = PokemonHistory::from_api(pokemon_data)?;
+    config.store.upsert_pokemon_history(&history).await?;
+    let learnset = Learnset::from_api(pokemon_data)?;
+    config.store.upsert_learnset(&learnset).await?;
+
+    let species_url = pokemon_data["species"]["url"]
+        .as_str()
+        .ok_or_else(|| Error::missing_field("species.url"))?;
+    if let Some(species_data) = config.fetch_resource(species_url).await? {
+        let species = SpeciesRecord::from_api(&species_data)?;
+        config.store.upsert_species(&species).await?;
+        // The other pokemon of the species are its forms, e.g. ninetales-alola
+        for url in SpeciesRecord::variety_urls(&species_data) {
+            if let Some(variety_data) = config.fetch_resource(&url).await? {
+                for form in PokemonForm::from_api(&variety_data)? {
+                    config.store.upsert_form(&form).await?;
+                }
+            }
+        }
+    }
+    for form in PokemonForm::from_api(pokemon_data)? {
+        config.store.upsert_form(&form).await?;
+    }
+
+    Ok(outcome)
+}
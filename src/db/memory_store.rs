@@ -0,0 +1,672 @@
+use crate::db::annotations::Annotation;
+use crate::db::collection::{CaughtPokemon, User};
+use crate::db::pokemon_tables::{
+    FieldChange, GameVersion, IngestionRun, Learnset, PokemonChange, PokemonForm, PokemonHistory,
+    PokemonName, PokemonRecord, PokemonRow, RawDocument, RegionalDex, SpeciesRecord, UpsertOutcome,
+    VersionGroup, POKEMON_TYPES,
+};
+use crate::db::query::{generation_of, Pokemon, PokemonSearch, SearchResults};
+use crate::db::store::PokedexStore;
+use crate::db::summary::{summarize, StatSummary, SummaryView};
+use crate::db::teams::Team;
+use crate::error::{DatabaseError, Error};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+// A row of the 'pokemon' table, the record is saved as it comes from the API
+struct StoredPokemon {
+    id: i32,
+    random_id: i32,
+    record: PokemonRecord,
+}
+
+// The collections that take the place of the tables
+#[derive(Default)]
+struct Tables {
+    // Indexed by pokedex number, like the UNIQUE column in the SQL tables
+    pokemon: BTreeMap<i32, StoredPokemon>,
+    types: Vec<String>,
+    // The species with their generation and localized names, by pokedex number
+    species: BTreeMap<i32, SpeciesRecord>,
+    // The past types and stats, only of the pokemon that have some
+    history: BTreeMap<i32, PokemonHistory>,
+    // The pokedexes of the API by name
+    regional_dexes: BTreeMap<String, RegionalDex>,
+    // The games by name
+    version_groups: BTreeMap<String, VersionGroup>,
+    versions: BTreeMap<String, GameVersion>,
+    next_id: i32,
+    raw_documents: Vec<RawDocument>,
+    ingestion_runs: Vec<IngestionRun>,
+    pokemon_changes: Vec<PokemonChange>,
+    // The summaries of the last refresh
+    summaries: HashMap<SummaryView, Vec<StatSummary>>,
+    users: Vec<User>,
+    // The collections by user, pokedex number, form and shininess, like the primary key
+    user_pokemon: BTreeMap<(i32, i32, String, bool), CaughtPokemon>,
+    // The moves of the pokemon by pokedex number
+    learnsets: BTreeMap<i32, Learnset>,
+    // The teams by user and name
+    teams: BTreeMap<(i32, String), Team>,
+    // The favorites, tags and notes by user and pokedex number
+    annotations: BTreeMap<(i32, i32), Annotation>,
+    // The forms that aren't the default one by pokedex number and form
+    forms: BTreeMap<(i32, String), PokemonForm>,
+}
+
+impl Tables {
+    // The generation of the species, when it was saved
+    fn generation(&self, pokedex_number: i32) -> Option<i32> {
+        self.species
+            .get(&pokedex_number)
+            .and_then(|species| species.generation)
+    }
+}
+
+// A row that would break a foreign key or a unique column of the SQL backends
+fn constraint_error(message: String) -> Error {
+    Error::Database(DatabaseError::Memory(message))
+}
+
+/* The in-memory backend. Nothing is saved when the program ends, so it's useful for the tests and
+for trying the pokedex without a database*/
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl PokedexStore for MemoryStore {
+    async fn create_tables(&self) -> Result<(), Error> {
+        // There aren't tables to create, the collections already exist
+        Ok(())
+    }
+
+    async fn reset_types_table(&self) -> Result<(), Error> {
+        let mut tables = self.tables();
+        if tables.types.iter().eq(POKEMON_TYPES.iter()) {
+            return Ok(());
+        }
+
+        // Like the CASCADE in the SQL backends, the types of each pokemon are removed too
+        for stored in tables.pokemon.values_mut() {
+            stored.record.types.clear();
+        }
+        tables.types = POKEMON_TYPES.iter().map(|name| name.to_string()).collect();
+
+        Ok(())
+    }
+
+    async fn upsert_pokemon(&self, pokemon: &PokemonRecord) -> Result<UpsertOutcome, Error> {
+        let mut tables = self.tables();
+        if let Some(unknown) = pokemon
+            .types
+            .iter()
+            .find(|name| !tables.types.contains(name))
+        {
+            return Err(constraint_error(format!(
+                "Type '{unknown}' not found in the types table"
+            )));
+        }
+
+        // The record replaces the saved one, types and abilities included
+        match tables.pokemon.get_mut(&pokemon.pokedex_number) {
+            Some(stored) => {
+                let outcome = UpsertOutcome::between(Some(&stored.record), pokemon);
+                stored.record = pokemon.clone();
+                Ok(outcome)
+            }
+            None => {
+                tables.next_id += 1;
+                let id = tables.next_id;
+                tables.pokemon.insert(
+                    pokemon.pokedex_number,
+                    StoredPokemon {
+                        id,
+                        random_id: id,
+                        record: pokemon.clone(),
+                    },
+                );
+                Ok(UpsertOutcome::Inserted)
+            }
+        }
+    }
+
+    async fn search(&self, search: &PokemonSearch) -> Result<SearchResults, Error> {
+        if let Some(generation) = search.as_of_generation {
+            return self.search_as_of(search, generation).await;
+        }
+        let tables = self.tables();
+        let regional_numbers: HashMap<i32, i32> = search
+            .regional_dex
+            .as_ref()
+            .and_then(|name| tables.regional_dexes.get(name))
+            .map(|dex| {
+                dex.entries
+                    .iter()
+                    .map(|entry| (entry.pokedex_number, entry.entry_number))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // The pokemon the user annotated, when the search has annotations
+        let annotated: Option<Vec<i32>> = search.annotations.as_ref().map(|filter| {
+            tables
+                .annotations
+                .iter()
+                .filter(|((user, _), annotation)| {
+                    *user == filter.user_id && filter.matches(annotation)
+                })
+                .map(|(_, annotation)| annotation.pokedex_number)
+                .collect()
+        });
+        let pokemon = tables
+            .pokemon
+            .values()
+            .filter(|stored| {
+                annotated
+                    .as_ref()
+                    .is_none_or(|numbers| numbers.contains(&stored.record.pokedex_number))
+            })
+            .map(|stored| {
+                let number = stored.record.pokedex_number;
+                let mut pokemon =
+                    Pokemon::from_record(stored.id, &stored.record, tables.generation(number));
+                pokemon.regional_number = regional_numbers.get(&number).copied();
+                pokemon
+            })
+            .collect();
+
+        Ok(search.apply(pokemon))
+    }
+
+    async fn upsert_species(&self, species: &SpeciesRecord) -> Result<(), Error> {
+        self.tables()
+            .species
+            .insert(species.pokedex_number, species.clone());
+
+        Ok(())
+    }
+
+    async fn list_species(&self) -> Result<Vec<SpeciesRecord>, Error> {
+        Ok(self.tables().species.values().cloned().collect())
+    }
+
+    async fn upsert_pokemon_history(&self, history: &PokemonHistory) -> Result<(), Error> {
+        let mut tables = self.tables();
+        if history.is_empty() {
+            tables.history.remove(&history.pokedex_number);
+        } else {
+            tables
+                .history
+                .insert(history.pokedex_number, history.clone());
+        }
+
+        Ok(())
+    }
+
+    async fn list_pokemon_history(&self) -> Result<Vec<PokemonHistory>, Error> {
+        Ok(self.tables().history.values().cloned().collect())
+    }
+
+    async fn upsert_regional_dex(&self, dex: &RegionalDex) -> Result<(), Error> {
+        self.tables()
+            .regional_dexes
+            .insert(dex.name.clone(), dex.clone());
+
+        Ok(())
+    }
+
+    async fn list_regional_dexes(&self) -> Result<Vec<RegionalDex>, Error> {
+        Ok(self.tables().regional_dexes.values().cloned().collect())
+    }
+
+    async fn upsert_version_group(&self, group: &VersionGroup) -> Result<(), Error> {
+        self.tables()
+            .version_groups
+            .insert(group.name.clone(), group.clone());
+
+        Ok(())
+    }
+
+    async fn upsert_version(&self, version: &GameVersion) -> Result<(), Error> {
+        self.tables()
+            .versions
+            .insert(version.name.clone(), version.clone());
+
+        Ok(())
+    }
+
+    async fn list_version_groups(&self) -> Result<Vec<VersionGroup>, Error> {
+        let mut groups: Vec<VersionGroup> =
+            self.tables().version_groups.values().cloned().collect();
+        groups.sort_by_key(|group| group.order);
+
+        Ok(groups)
+    }
+
+    async fn list_versions(&self) -> Result<Vec<GameVersion>, Error> {
+        Ok(self.tables().versions.values().cloned().collect())
+    }
+
+    async fn list_names(&self) -> Result<Vec<PokemonName>, Error> {
+        let tables = self.tables();
+        let slugs = tables.pokemon.values().map(|stored| PokemonName {
+            pokedex_number: stored.record.pokedex_number,
+            language: None,
+            name: stored.record.name.clone(),
+        });
+        let localized = tables.species.values().flat_map(|species| {
+            species.names.iter().map(|name| PokemonName {
+                pokedex_number: species.pokedex_number,
+                language: Some(name.language.clone()),
+                name: name.name.clone(),
+            })
+        });
+
+        Ok(slugs.chain(localized).collect())
+    }
+
+    async fn load_pokemon_rows(&self) -> Result<Vec<PokemonRow>, Error> {
+        let tables = self.tables();
+        Ok(tables
+            .pokemon
+            .values()
+            .map(|stored| {
+                let record = &stored.record;
+                PokemonRow {
+                    id: stored.id,
+                    random_id: stored.random_id,
+                    pokedex_number: record.pokedex_number,
+                    name: record.name.clone(),
+                    height: record.height.clone(),
+                    weight: record.weight.clone(),
+                    hp: record.hp,
+                    attack: record.attack,
+                    defense: record.defense,
+                    special_attack: record.special_attack,
+                    special_defense: record.special_defense,
+                    speed: record.speed,
+                    types: record.types_label(),
+                    generation: tables
+                        .generation(record.pokedex_number)
+                        .or_else(|| generation_of(record.pokedex_number)),
+                }
+            })
+            .collect())
+    }
+
+    async fn archive_document(&self, document: &RawDocument) -> Result<(), Error> {
+        let mut tables = self.tables();
+        match tables
+            .raw_documents
+            .iter_mut()
+            .find(|saved| saved.url == document.url && saved.content_hash == document.content_hash)
+        {
+            Some(saved) => saved.fetched_at = document.fetched_at,
+            None => tables.raw_documents.push(document.clone()),
+        }
+
+        Ok(())
+    }
+
+    async fn load_raw_documents(&self, url_prefix: &str) -> Result<Vec<RawDocument>, Error> {
+        // Keeping the last version of each URL, the map sorts them by URL like the SQL backends
+        let mut latest: BTreeMap<&str, &RawDocument> = BTreeMap::new();
+        let tables = self.tables();
+        for document in tables
+            .raw_documents
+            .iter()
+            .filter(|document| document.url.starts_with(url_prefix))
+        {
+            let newer = latest
+                .get(document.url.as_str())
+                .is_none_or(|saved| saved.fetched_at < document.fetched_at);
+            if newer {
+                latest.insert(&document.url, document);
+            }
+        }
+
+        Ok(latest.into_values().cloned().collect())
+    }
+
+    async fn refresh_summaries(&self) -> Result<(), Error> {
+        let pokemon = self.search(&PokemonSearch::default()).await?.pokemon;
+        let summaries = SummaryView::ALL
+            .iter()
+            .map(|view| (*view, summarize(&pokemon, *view)))
+            .collect();
+        self.tables().summaries = summaries;
+
+        Ok(())
+    }
+
+    async fn load_summary(&self, view: SummaryView) -> Result<Vec<StatSummary>, Error> {
+        Ok(self
+            .tables()
+            .summaries
+            .get(&view)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn start_ingestion_run(&self, source: &str) -> Result<IngestionRun, Error> {
+        let mut tables = self.tables();
+        let run = IngestionRun {
+            // After the last one, restored runs can have gaps in their ids
+            id: tables
+                .ingestion_runs
+                .iter()
+                .map(|run| run.id)
+                .max()
+                .unwrap_or(0)
+                + 1,
+            source: source.to_string(),
+            started_at: Utc::now(),
+            finished_at: None,
+            inserted: 0,
+            updated: 0,
+            unchanged: 0,
+            skipped: 0,
+            failed: 0,
+            duration_ms: None,
+        };
+        tables.ingestion_runs.push(run.clone());
+
+        Ok(run)
+    }
+
+    async fn finish_ingestion_run(&self, run: &IngestionRun) -> Result<(), Error> {
+        let mut tables = self.tables();
+        let saved = tables
+            .ingestion_runs
+            .iter_mut()
+            .find(|saved| saved.id == run.id)
+            .ok_or_else(|| constraint_error(format!("Ingestion run {} not found", run.id)))?;
+        *saved = run.clone();
+
+        Ok(())
+    }
+
+    async fn record_changes(
+        &self,
+        run_id: i32,
+        pokemon: &PokemonRecord,
+        changes: &[FieldChange],
+    ) -> Result<(), Error> {
+        let mut tables = self.tables();
+        let changed_at = Utc::now();
+        tables
+            .pokemon_changes
+            .extend(changes.iter().map(|change| PokemonChange {
+                run_id,
+                pokedex_number: pokemon.pokedex_number,
+                pokemon_name: pokemon.name.clone(),
+                field: change.field.clone(),
+                old_value: change.old_value.clone(),
+                new_value: change.new_value.clone(),
+                changed_at,
+            }));
+
+        Ok(())
+    }
+
+    async fn restore_history(
+        &self,
+        runs: &[IngestionRun],
+        changes: &[PokemonChange],
+    ) -> Result<(), Error> {
+        let mut tables = self.tables();
+        if let Some(run) = runs
+            .iter()
+            .find(|run| tables.ingestion_runs.iter().any(|saved| saved.id == run.id))
+        {
+            return Err(constraint_error(format!(
+                "Ingestion run {} already exists",
+                run.id
+            )));
+        }
+
+        tables.ingestion_runs.extend(runs.iter().cloned());
+        tables.ingestion_runs.sort_by_key(|run| run.id);
+        tables.pokemon_changes.extend(changes.iter().cloned());
+
+        Ok(())
+    }
+
+    async fn list_ingestion_runs(&self) -> Result<Vec<IngestionRun>, Error> {
+        Ok(self.tables().ingestion_runs.iter().rev().cloned().collect())
+    }
+
+    async fn changes_between_runs(
+        &self,
+        from_run: i32,
+        to_run: i32,
+    ) -> Result<Vec<PokemonChange>, Error> {
+        let mut changes: Vec<PokemonChange> = self
+            .tables()
+            .pokemon_changes
+            .iter()
+            .filter(|change| change.run_id > from_run && change.run_id <= to_run)
+            .cloned()
+            .collect();
+        // Stable sort, the changes of a pokemon keep the order they were recorded
+        changes.sort_by_key(|change| (change.run_id, change.pokedex_number));
+
+        Ok(changes)
+    }
+
+    async fn create_user(&self, name: &str) -> Result<User, Error> {
+        let mut tables = self.tables();
+        if tables.users.iter().any(|user| user.name == name) {
+            return Err(Error::Input(format!("The user '{name}' already exists")));
+        }
+        let user = User {
+            id: tables.users.iter().map(|user| user.id).max().unwrap_or(0) + 1,
+            name: name.to_string(),
+        };
+        tables.users.push(user.clone());
+
+        Ok(user)
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, Error> {
+        let mut users = self.tables().users.clone();
+        users.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(users)
+    }
+
+    async fn mark_caught(&self, user_id: i32, pokemon: &CaughtPokemon) -> Result<(), Error> {
+        let mut tables = self.tables();
+        if !tables.users.iter().any(|user| user.id == user_id) {
+            return Err(constraint_error(format!(
+                "The user {user_id} doesn't exist"
+            )));
+        }
+        tables.user_pokemon.insert(
+            (
+                user_id,
+                pokemon.pokedex_number,
+                pokemon.form.clone(),
+                pokemon.shiny,
+            ),
+            pokemon.clone(),
+        );
+
+        Ok(())
+    }
+
+    async fn unmark_caught(
+        &self,
+        user_id: i32,
+        pokedex_number: i32,
+        form: &str,
+        shiny: bool,
+    ) -> Result<bool, Error> {
+        let key = (user_id, pokedex_number, form.to_string(), shiny);
+        Ok(self.tables().user_pokemon.remove(&key).is_some())
+    }
+
+    async fn list_caught(&self, user_id: i32) -> Result<Vec<CaughtPokemon>, Error> {
+        Ok(self
+            .tables()
+            .user_pokemon
+            .iter()
+            .filter(|((user, ..), _)| *user == user_id)
+            .map(|(_, pokemon)| pokemon.clone())
+            .collect())
+    }
+
+    async fn upsert_learnset(&self, learnset: &Learnset) -> Result<(), Error> {
+        self.tables()
+            .learnsets
+            .insert(learnset.pokedex_number, learnset.clone());
+
+        Ok(())
+    }
+
+    async fn get_learnset(&self, pokedex_number: i32) -> Result<Learnset, Error> {
+        Ok(self
+            .tables()
+            .learnsets
+            .get(&pokedex_number)
+            .cloned()
+            .unwrap_or_else(|| Learnset {
+                pokedex_number,
+                moves: Vec::new(),
+            }))
+    }
+
+    async fn list_learnsets(&self) -> Result<Vec<Learnset>, Error> {
+        Ok(self.tables().learnsets.values().cloned().collect())
+    }
+
+    async fn save_team(&self, user_id: i32, team: &Team) -> Result<(), Error> {
+        let mut tables = self.tables();
+        if !tables.users.iter().any(|user| user.id == user_id) {
+            return Err(constraint_error(format!(
+                "The user {user_id} doesn't exist"
+            )));
+        }
+        tables
+            .teams
+            .insert((user_id, team.name.clone()), team.clone());
+
+        Ok(())
+    }
+
+    async fn list_teams(&self, user_id: i32) -> Result<Vec<Team>, Error> {
+        Ok(self
+            .tables()
+            .teams
+            .iter()
+            .filter(|((user, _), _)| *user == user_id)
+            .map(|(_, team)| team.clone())
+            .collect())
+    }
+
+    async fn delete_team(&self, user_id: i32, name: &str) -> Result<bool, Error> {
+        let key = (user_id, name.to_string());
+        Ok(self.tables().teams.remove(&key).is_some())
+    }
+
+    async fn save_annotation(&self, user_id: i32, annotation: &Annotation) -> Result<(), Error> {
+        let mut tables = self.tables();
+        if !tables.users.iter().any(|user| user.id == user_id) {
+            return Err(constraint_error(format!(
+                "The user {user_id} doesn't exist"
+            )));
+        }
+        let key = (user_id, annotation.pokedex_number);
+        if annotation.is_empty() {
+            tables.annotations.remove(&key);
+        } else {
+            let mut annotation = annotation.clone();
+            annotation.tags.sort();
+            annotation.tags.dedup();
+            tables.annotations.insert(key, annotation);
+        }
+
+        Ok(())
+    }
+
+    async fn list_annotations(&self, user_id: i32) -> Result<Vec<Annotation>, Error> {
+        Ok(self
+            .tables()
+            .annotations
+            .iter()
+            .filter(|((user, _), _)| *user == user_id)
+            .map(|(_, annotation)| annotation.clone())
+            .collect())
+    }
+
+    async fn list_raw_documents(&self) -> Result<Vec<RawDocument>, Error> {
+        let mut documents = self.tables().raw_documents.clone();
+        // Stable, the documents fetched at the same time keep the order they were saved in
+        documents.sort_by_key(|document| document.fetched_at);
+
+        Ok(documents)
+    }
+
+    async fn clear_tables(&self) -> Result<(), Error> {
+        *self.tables() = Tables::default();
+
+        Ok(())
+    }
+
+    async fn has_data(&self) -> Result<bool, Error> {
+        let tables = self.tables();
+
+        Ok(!(tables.pokemon.is_empty()
+            && tables.species.is_empty()
+            && tables.history.is_empty()
+            && tables.regional_dexes.is_empty()
+            && tables.version_groups.is_empty()
+            && tables.versions.is_empty()
+            && tables.raw_documents.is_empty()
+            && tables.ingestion_runs.is_empty()
+            && tables.pokemon_changes.is_empty()
+            && tables.users.is_empty()
+            && tables.user_pokemon.is_empty()
+            && tables.learnsets.is_empty()
+            && tables.teams.is_empty()
+            && tables.annotations.is_empty()
+            && tables.forms.is_empty()))
+    }
+
+    async fn upsert_form(&self, form: &PokemonForm) -> Result<(), Error> {
+        self.tables()
+            .forms
+            .insert((form.pokedex_number, form.form.clone()), form.clone());
+
+        Ok(())
+    }
+
+    async fn get_forms(&self, pokedex_number: i32) -> Result<Vec<PokemonForm>, Error> {
+        Ok(self
+            .tables()
+            .forms
+            .values()
+            .filter(|form| form.pokedex_number == pokedex_number)
+            .cloned()
+            .collect())
+    }
+
+    async fn list_forms(&self) -> Result<Vec<PokemonForm>, Error> {
+        Ok(self.tables().forms.values().cloned().collect())
+    }
+}
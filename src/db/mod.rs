@@ -1,2 +1,20 @@
+pub mod annotations;
+pub mod archive;
+pub mod collection;
 pub mod connection;
+pub mod display_names;
+pub mod games;
+pub mod ingestion;
+pub mod memory_store;
+pub mod name_search;
+pub mod pg_credentials;
 pub mod pokemon_tables;
+pub mod postgres_store;
+pub mod query;
+pub mod snapshot;
+pub mod sqlite_store;
+pub mod store;
+pub mod summary;
+pub mod teams;
+pub mod tls;
+pub mod validation;
@@ -0,0 +1,201 @@
+use crate::db::pokemon_tables::PokemonName;
+use deunicode::deunicode;
+use std::collections::{BTreeMap, HashSet};
+use strsim::normalized_damerau_levenshtein;
+
+// Matches with a lower score are too different from the query to be useful
+pub const MIN_SCORE: f64 = 0.45;
+
+// The best name of a pokemon for a query, the score goes from 0 to 1 (same normalized name)
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameMatch {
+    pub pokedex_number: i32,
+    // The slug or the localized name that matched
+    pub name: String,
+    pub language: Option<String>,
+    pub score: f64,
+}
+
+/* The name without accents, punctuation, spaces and case, so "Mr. Mime", "mr mime" and "mr-mime"
+are the same. The gender signs of Nidoran are the "f" and "m" of their slugs*/
+pub fn normalize(name: &str) -> String {
+    let name = name.replace('♀', "f").replace('♂', "m");
+    deunicode(&name)
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+// The trigrams of a normalized name, padded like pg_trgm so the start of the word counts more
+fn trigrams(name: &str) -> HashSet<String> {
+    let padded: Vec<char> = format!("  {name} ").chars().collect();
+    padded
+        .windows(3)
+        .map(|window| window.iter().collect())
+        .collect()
+}
+
+// Trigrams that both names have over the trigrams of any of them
+pub fn trigram_similarity(a: &str, b: &str) -> f64 {
+    let a = trigrams(a);
+    let b = trigrams(b);
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/* How much a normalized name looks like the normalized query. The same name is 1, a name that
+starts with the query is between 0.8 and 1, and any other is the best of the trigram similarity
+and the edit distance, so typos like "charmnder" are found too*/
+pub fn score(query: &str, name: &str) -> f64 {
+    if query.is_empty() || name.is_empty() {
+        return 0.0;
+    }
+    if query == name {
+        return 1.0;
+    }
+    if name.starts_with(query) {
+        return 0.8 + 0.19 * query.len() as f64 / name.len() as f64;
+    }
+
+    trigram_similarity(query, name).max(normalized_damerau_levenshtein(query, name))
+}
+
+// The best name of each pokemon that matches, the best matches first
+fn best_by_pokemon(
+    names: &[PokemonName],
+    limit: usize,
+    score_of: impl Fn(&str) -> f64,
+) -> Vec<NameMatch> {
+    let mut best: BTreeMap<i32, NameMatch> = BTreeMap::new();
+    for name in names {
+        let score = score_of(&normalize(&name.name));
+        if score < MIN_SCORE {
+            continue;
+        }
+        let better = best
+            .get(&name.pokedex_number)
+            .is_none_or(|saved| score > saved.score);
+        if better {
+            best.insert(
+                name.pokedex_number,
+                NameMatch {
+                    pokedex_number: name.pokedex_number,
+                    name: name.name.clone(),
+                    language: name.language.clone(),
+                    score,
+                },
+            );
+        }
+    }
+
+    // The map is sorted by pokedex number, so the ties keep that order
+    let mut matches: Vec<NameMatch> = best.into_values().collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+    matches.truncate(limit);
+    matches
+}
+
+// The pokemon whose slug or localized names look like the query, e.g. "nidoran f" or "farfetchd"
+pub fn fuzzy_matches(names: &[PokemonName], query: &str, limit: usize) -> Vec<NameMatch> {
+    let query = normalize(query);
+    best_by_pokemon(names, limit, |name| score(&query, name))
+}
+
+// The pokemon with a name that starts with the prefix, the shortest names first
+pub fn prefix_matches(names: &[PokemonName], prefix: &str, limit: usize) -> Vec<NameMatch> {
+    let prefix = normalize(prefix);
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+    best_by_pokemon(names, limit, |name| {
+        if name.starts_with(&prefix) {
+            // 1 for the same name, less the more letters are missing
+            1.0 - (name.len() - prefix.len()) as f64 / (name.len() as f64 + 1.0) * (1.0 - MIN_SCORE)
+        } else {
+            0.0
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(pokedex_number: i32, language: Option<&str>, name: &str) -> PokemonName {
+        PokemonName {
+            pokedex_number,
+            language: language.map(str::to_string),
+            name: name.to_string(),
+        }
+    }
+
+    fn names() -> Vec<PokemonName> {
+        vec![
+            name(4, None, "charmander"),
+            name(5, None, "charmeleon"),
+            name(29, None, "nidoran-f"),
+            name(29, Some("en"), "Nidoran♀"),
+            name(32, None, "nidoran-m"),
+            name(122, None, "mr-mime"),
+            name(122, Some("fr"), "M. Mime"),
+        ]
+    }
+
+    #[test]
+    fn normalizes_the_names() {
+        assert_eq!(normalize("Mr. Mime"), "mrmime");
+        assert_eq!(normalize("mr-mime"), "mrmime");
+        assert_eq!(normalize("Nidoran♀"), "nidoranf");
+        assert_eq!(normalize("Nidoran♂"), "nidoranm");
+        assert_eq!(normalize("Flabébé"), "flabebe");
+        assert_eq!(normalize("Farfetch’d"), "farfetchd");
+    }
+
+    #[test]
+    fn scores_the_same_name_the_prefixes_and_the_typos() {
+        assert_eq!(score("pikachu", "pikachu"), 1.0);
+        let prefix = score("pika", "pikachu");
+        assert!((0.8..1.0).contains(&prefix));
+        assert!(score("pikach", "pikachu") > prefix);
+        assert!(score("charmnder", "charmander") >= MIN_SCORE);
+        assert!(score("bulbasaur", "charmander") < MIN_SCORE);
+        assert_eq!(score("", "pikachu"), 0.0);
+    }
+
+    #[test]
+    fn finds_the_best_name_of_each_pokemon() {
+        let matches = fuzzy_matches(&names(), "nidoran f", 5);
+
+        assert_eq!(matches[0].pokedex_number, 29);
+        assert_eq!(matches[0].score, 1.0);
+        assert_eq!(matches[1].pokedex_number, 32);
+        assert_eq!(matches.iter().filter(|m| m.pokedex_number == 29).count(), 1);
+    }
+
+    #[test]
+    fn finds_the_typos_and_the_localized_names() {
+        let typo = fuzzy_matches(&names(), "charmnder", 1);
+        assert_eq!(typo[0].name, "charmander");
+
+        let french = fuzzy_matches(&names(), "m mime", 1);
+        assert_eq!(french[0].pokedex_number, 122);
+        assert_eq!(french[0].language.as_deref(), Some("fr"));
+        assert!(fuzzy_matches(&names(), "zzzzzz", 5).is_empty());
+    }
+
+    #[test]
+    fn the_shortest_names_with_the_prefix_come_first() {
+        let matches = prefix_matches(&names(), "charm", 5);
+        let found: Vec<&str> = matches.iter().map(|m| m.name.as_str()).collect();
+
+        assert_eq!(found, ["charmander", "charmeleon"]);
+        assert!(matches[0].score > MIN_SCORE);
+        assert!(prefix_matches(&names(), "nid", 1).len() == 1);
+        assert!(prefix_matches(&names(), "--", 5).is_empty());
+    }
+}
@@ -1,220 +1,959 @@
-use postgres::{Client, Error};
+use crate::error::Error;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 
-// Queries are created here
-// Borrowing the client from the established connection
-pub fn create_pokemon_tables(client: &mut Client) -> Result<(), Error> {
-    // Query to create tables into the db
-    client.batch_execute(
-        "
-            -- Pokemon principle table
-            CREATE TABLE IF NOT EXISTS pokemon (
-                id   SERIAL PRIMARY KEY,
-                random_id   SERIAL UNIQUE NOT NULL,
-                pokedex_number INTEGER UNIQUE NOT NULL,
-                name    VARCHAR(100) NOT NULL,
-                height  VARCHAR(10),
-                weight  VARCHAR(10),
-                hp INTEGER,
-                attack INTEGER,
-                defense INTEGER,
-                special_attack INTEGER,
-                special_defense INTEGER,
-                speed INTEGER
-        );
-            -- Types of pokemon table
-            CREATE TABLE IF NOT EXISTS types (
-                id SERIAL PRIMARY KEY,
-                name VARCHAR(20) UNIQUE NOT NULL
+// The types are always inserted in this order so every backend gives them the same index
+pub const POKEMON_TYPES: [&str; 18] = [
+    "normal", "fighting", "flying", "poison", "ground", "rock", "bug", "ghost", "steel", "fire",
+    "water", "grass", "electric", "psychic", "ice", "dragon", "dark", "fairy",
+];
+
+// If the saved (id, name) of the types are the ones of POKEMON_TYPES, with the ids from 1
+pub fn types_in_order(saved: &[(i32, String)]) -> bool {
+    saved.len() == POKEMON_TYPES.len()
+        && saved
+            .iter()
+            .zip(POKEMON_TYPES.iter().enumerate())
+            .all(|((id, name), (index, expected))| *id == index as i32 + 1 && name == expected)
+}
+
+// An ability of a pokemon and if it's the hidden one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbilityRecord {
+    pub name: String,
+    pub is_hidden: bool,
+}
+
+// The abilities of a pokemon document, in the order of the API
+fn abilities_from_api(pokemon_data: &Value) -> Result<Vec<AbilityRecord>, Error> {
+    let mut abilities = Vec::new();
+    for (index, ability_data) in pokemon_data["abilities"]
+        .as_array()
+        .ok_or_else(|| Error::missing_field("abilities"))?
+        .iter()
+        .enumerate()
+    {
+        /* Fetching the pokemon abilities by 'abilities' and the name for the abilities like
+        "Flash fire" or "Cloud nine" */
+        let name = ability_data["ability"]["name"]
+            .as_str()
+            .ok_or_else(|| Error::missing_field(format!("abilities[{index}].ability.name")))?
+            .to_string();
+        let is_hidden = ability_data["is_hidden"]
+            .as_bool()
+            .ok_or_else(|| Error::missing_field(format!("abilities[{index}].is_hidden")))?;
+
+        abilities.push(AbilityRecord { name, is_hidden });
+    }
+
+    Ok(abilities)
+}
+
+// All the data of a pokemon that goes into the tables, already parsed from the API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PokemonRecord {
+    pub pokedex_number: i32,
+    pub name: String,
+    pub height: String,
+    pub weight: String,
+    pub hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub special_attack: i32,
+    pub special_defense: i32,
+    pub speed: i32,
+    pub types: Vec<String>,
+    pub abilities: Vec<AbilityRecord>,
+}
+
+impl PokemonRecord {
+    // Parsing the JSON of the API into a record that any backend can store
+    pub fn from_api(pokemon_data: &Value) -> Result<Self, Error> {
+        // Obtain the first data from the API as JSON
+        let pokedex_number = pokemon_data["id"]
+            .as_i64()
+            .ok_or_else(|| Error::missing_field("id"))? as i32;
+        let name = pokemon_data["name"]
+            .as_str()
+            .ok_or_else(|| Error::missing_field("name"))?
+            .to_string();
+
+        /*Obtaining the height and weight as JSON. Then, the data is parsed as 64-bit float and is
+        divided between 10.0. Division is for obtain the data as meters and kg*/
+        let height = format!(
+            "{:.2}",
+            pokemon_data["height"]
+                .as_f64()
+                .ok_or_else(|| Error::missing_field("height"))?
+                / 10.0
         );
-            -- Pokemon types table
-            CREATE TABLE IF NOT EXISTS pokemon_types (
-                pokemon_id SERIAL REFERENCES pokemon(id),
-                type_id INTEGER REFERENCES types(id),
-                PRIMARY KEY (pokemon_id, type_id)
+        let weight = format!(
+            "{:.2}",
+            pokemon_data["weight"]
+                .as_f64()
+                .ok_or_else(|| Error::missing_field("weight"))?
+                / 10.0
         );
-            -- Abilities of pokemon table
-            CREATE TABLE IF NOT EXISTS abilities (
-                id SERIAL PRIMARY KEY,
-                name VARCHAR(50) UNIQUE NOT NULL
+
+        let stats = pokemon_data["stats"]
+            .as_array()
+            .ok_or_else(|| Error::missing_field("stats"))?;
+        let stat = |index: usize| -> Result<i32, Error> {
+            let base_stat = stats
+                .get(index)
+                .and_then(|stat| stat["base_stat"].as_i64())
+                .ok_or_else(|| Error::missing_field(format!("stats[{index}].base_stat")))?;
+            Ok(base_stat as i32)
+        };
+
+        let types = pokemon_data["types"]
+            .as_array()
+            .ok_or_else(|| Error::missing_field("types"))?
+            .iter()
+            .enumerate()
+            // Fetching the pokemon type by 'type' and the name of the type like "bug" or "fire"
+            .map(|(index, type_pokemon)| {
+                type_pokemon["type"]["name"]
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| Error::missing_field(format!("types[{index}].type.name")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let abilities = abilities_from_api(pokemon_data)?;
+
+        Ok(PokemonRecord {
+            pokedex_number,
+            name,
+            height,
+            weight,
+            hp: stat(0)?,
+            attack: stat(1)?,
+            defense: stat(2)?,
+            special_attack: stat(3)?,
+            special_defense: stat(4)?,
+            speed: stat(5)?,
+            types,
+            abilities,
+        })
+    }
+
+    // The fields that are different in this record than in the saved one
+    pub fn changes_from(&self, old: &PokemonRecord) -> Vec<FieldChange> {
+        let mut changes = Vec::new();
+        let mut compare = |field: &str, old_value: String, new_value: String| {
+            if old_value != new_value {
+                changes.push(FieldChange {
+                    field: field.to_string(),
+                    old_value: Some(old_value),
+                    new_value: Some(new_value),
+                });
+            }
+        };
+
+        compare("name", old.name.clone(), self.name.clone());
+        compare("height", old.height.clone(), self.height.clone());
+        compare("weight", old.weight.clone(), self.weight.clone());
+        compare("hp", old.hp.to_string(), self.hp.to_string());
+        compare("attack", old.attack.to_string(), self.attack.to_string());
+        compare("defense", old.defense.to_string(), self.defense.to_string());
+        compare(
+            "special_attack",
+            old.special_attack.to_string(),
+            self.special_attack.to_string(),
         );
-            -- Abilities from each pokemon table
-            CREATE TABLE IF NOT EXISTS pokemon_abilities (
-                pokemon_id INTEGER REFERENCES pokemon(id),
-                ability_id INTEGER REFERENCES abilities(id),
-                is_hidden BOOLEAN NOT NULL,
-                PRIMARY KEY (pokemon_id, ability_id)
+        compare(
+            "special_defense",
+            old.special_defense.to_string(),
+            self.special_defense.to_string(),
         );
+        compare("speed", old.speed.to_string(), self.speed.to_string());
+        compare("types", old.types_label(), self.types_label());
+        compare("abilities", old.abilities_label(), self.abilities_label());
 
-        ",
-    )?;
-
-    println!("Tables created or uploaded.");
-
-    Ok(())
-}
-
-// Query for inserting the fetching pokemons
-pub fn insert_pokemon_data(
-    client: &mut Client,
-    pokemon_data: &Value,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Obtain the first data from the API as JSON
-    let pokedex_number = pokemon_data["id"].as_i64().ok_or("Missed")? as i32;
-    let name = pokemon_data["name"].as_str().ok_or("Missed")?.to_string();
-
-    /*Obtaining the height and weight as JSON. Then, the data is parsed as 64-bit float and is
-    divided between 10.0. Division is for obtain the data as meters and kg*/
-    let height = format!(
-        "{:.2}",
-        pokemon_data["height"].as_f64().ok_or("Missed")? / 10.0
-    );
-    let weight = format!(
-        "{:.2}",
-        pokemon_data["weight"].as_f64().ok_or("Missed")? / 10.0
-    );
-
-    let stats = pokemon_data["stats"].as_array().ok_or("Missed")?;
-    let hp = stats[0]["base_stat"].as_i64().ok_or("Missed")? as i32;
-    let attack = stats[1]["base_stat"].as_i64().ok_or("Missed")? as i32;
-    let defense = stats[2]["base_stat"].as_i64().ok_or("Missed")? as i32;
-    let special_attack = stats[3]["base_stat"].as_i64().ok_or("Missed")? as i32;
-    let special_defense = stats[4]["base_stat"].as_i64().ok_or("Missed")? as i32;
-    let speed = stats[5]["base_stat"].as_i64().ok_or("Missed")? as i32;
-
-    client.execute(
-        "INSERT INTO pokemon (pokedex_number, name, height, weight, hp, attack, defense, special_attack, special_defense, speed)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-        ON CONFLICT(pokedex_number) DO UPDATE SET
-            name = EXCLUDED.name,
-            height = EXCLUDED.height,
-            weight = EXCLUDED.weight,
-            hp = EXCLUDED.hp,
-            attack = EXCLUDED.attack,
-            defense = EXCLUDED.defense,
-            special_attack = EXCLUDED.special_attack,
-            special_defense = EXCLUDED.special_defense,
-            speed = EXCLUDED.speed
-        RETURNING id",
-        &[
-            &pokedex_number,
-            &name,
-            &height,
-            &weight,
-            &hp,
-            &attack,
-            &defense,
-            &special_attack,
-            &special_defense,
-            &speed
-        ],
-    )?;
-
-    let types = pokemon_data["types"]
-        .as_array()
-        .ok_or("Missing types array")?;
+        changes
+    }
+
+    // The types sorted by name, e.g. "grass, poison", so the order of the API doesn't matter
+    pub fn types_label(&self) -> String {
+        let mut types: Vec<&str> = self.types.iter().map(String::as_str).collect();
+        types.sort_unstable();
+        types.join(", ")
+    }
+
+    // The abilities sorted by name, the hidden one is marked, e.g. "chlorophyll (hidden), overgrow"
+    pub fn abilities_label(&self) -> String {
+        let mut abilities: Vec<String> = self
+            .abilities
+            .iter()
+            .map(|ability| {
+                if ability.is_hidden {
+                    format!("{} (hidden)", ability.name)
+                } else {
+                    ability.name.clone()
+                }
+            })
+            .collect();
+        abilities.sort_unstable();
+        abilities.join(", ")
+    }
+}
 
-    // Loop for insert the type for each pokemon in the db
-    for type_pokemon in types {
-        // Fetching the pokemon type by 'type' and the name of the type like "bug" or "fire"
-        let type_name = type_pokemon["type"]["name"]
+// A value of a pokemon that changed when it was upserted
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    // Column of the pokemon table, or "types" and "abilities"
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+// What the upsert did with the pokemon
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpsertOutcome {
+    Inserted,
+    Updated(Vec<FieldChange>),
+    Unchanged,
+}
+
+impl UpsertOutcome {
+    // Comparing the record saved before the upsert (if any) with the new one
+    pub fn between(old: Option<&PokemonRecord>, new: &PokemonRecord) -> Self {
+        match old {
+            None => UpsertOutcome::Inserted,
+            Some(old) => {
+                let changes = new.changes_from(old);
+                if changes.is_empty() {
+                    UpsertOutcome::Unchanged
+                } else {
+                    UpsertOutcome::Updated(changes)
+                }
+            }
+        }
+    }
+}
+
+// The id at the end of a resource URL of the API, e.g. 3 for ".../generation/3/"
+pub fn resource_id(url: &str) -> Option<i32> {
+    url.trim_end_matches('/').rsplit('/').next()?.parse().ok()
+}
+
+// A name of a pokemon in one language, e.g. ("fr", "Pikachu") or ("ja", "ピカチュウ")
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalizedName {
+    pub language: String,
+    pub name: String,
+}
+
+// The data of the species endpoint of the API, the species id is the national pokedex number
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeciesRecord {
+    pub pokedex_number: i32,
+    // The generation the species was introduced in
+    pub generation: Option<i32>,
+    pub names: Vec<LocalizedName>,
+}
+
+impl SpeciesRecord {
+    pub fn from_api(species_data: &Value) -> Result<Self, Error> {
+        let pokedex_number = species_data["id"]
+            .as_i64()
+            .ok_or_else(|| Error::missing_field("id"))? as i32;
+        let generation = species_data["generation"]["url"]
             .as_str()
-            .ok_or("Missing type name")?;
-
-        // Inserting the pokemon type on the 'types' table
-        let type_id: i32 = client
-            .query_one("SELECT id FROM types WHERE name = $1", &[&type_name])?
-            .get(0);
-
-        /*Like the rows variable, it's fetching the id from pokemon table and inserting the pokedex
-        number like the id in the table*/
-        let pokemon_rows = client.query(
-            "SELECT id FROM pokemon WHERE pokedex_number = $1",
-            &[&pokedex_number],
-        )?;
-        let pokemon_id: i32 = if let Some(row) = pokemon_rows.first() {
-            row.get(0)
-        } else {
-            return Err("Pokemon not found".into());
+            .and_then(resource_id);
+
+        let mut names = Vec::new();
+        for (index, name_data) in species_data["names"]
+            .as_array()
+            .ok_or_else(|| Error::missing_field("names"))?
+            .iter()
+            .enumerate()
+        {
+            let language = name_data["language"]["name"]
+                .as_str()
+                .ok_or_else(|| Error::missing_field(format!("names[{index}].language.name")))?;
+            let name = name_data["name"]
+                .as_str()
+                .ok_or_else(|| Error::missing_field(format!("names[{index}].name")))?;
+            names.push(LocalizedName {
+                language: language.to_string(),
+                name: name.to_string(),
+            });
+        }
+
+        Ok(SpeciesRecord {
+            pokedex_number,
+            generation,
+            names,
+        })
+    }
+
+    /* The URLs of the pokemon of a species that aren't the default one, e.g. ninetales-alola. They
+    are its forms with their own abilities and moves*/
+    pub fn variety_urls(species_data: &Value) -> Vec<String> {
+        species_data["varieties"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter(|variety| variety["is_default"].as_bool() == Some(false))
+            .filter_map(|variety| variety["pokemon"]["url"].as_str())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+// The types a pokemon had until a generation, e.g. clefairy was normal until generation 5
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PastTypes {
+    pub generation: i32,
+    pub types: Vec<String>,
+}
+
+/* The base stats a pokemon had until a generation, only the ones that changed after it. The
+names are the ones of the columns, e.g. "special_defense"*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PastStats {
+    pub generation: i32,
+    pub stats: BTreeMap<String, i32>,
+}
+
+// What changed in a pokemon between the games, from 'past_types' and 'past_stats' of the API
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PokemonHistory {
+    pub pokedex_number: i32,
+    // Sorted by generation, the oldest first
+    pub past_types: Vec<PastTypes>,
+    pub past_stats: Vec<PastStats>,
+}
+
+impl PokemonHistory {
+    pub fn from_api(pokemon_data: &Value) -> Result<Self, Error> {
+        let pokedex_number = pokemon_data["id"]
+            .as_i64()
+            .ok_or_else(|| Error::missing_field("id"))? as i32;
+        // The path is the one of the past values, e.g. "past_types[0]"
+        let generation_of = |past: &Value, path: &str| {
+            past["generation"]["url"]
+                .as_str()
+                .and_then(resource_id)
+                .ok_or_else(|| Error::missing_field(format!("{path}.generation.url")))
         };
+        // Both lists are missing in the documents of the older versions of the API
+        let empty = Vec::new();
+
+        let mut past_types = Vec::new();
+        for (index, past) in pokemon_data["past_types"]
+            .as_array()
+            .unwrap_or(&empty)
+            .iter()
+            .enumerate()
+        {
+            let path = format!("past_types[{index}]");
+            let types = past["types"]
+                .as_array()
+                .ok_or_else(|| Error::missing_field(format!("{path}.types")))?
+                .iter()
+                .enumerate()
+                .map(|(type_index, type_pokemon)| {
+                    type_pokemon["type"]["name"]
+                        .as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| {
+                            Error::missing_field(format!("{path}.types[{type_index}].type.name"))
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            past_types.push(PastTypes {
+                generation: generation_of(past, &path)?,
+                types,
+            });
+        }
+        past_types.sort_by_key(|past| past.generation);
 
-        // Once fetched the data, the pokedex insert the id type and pokemon id in the
-        // pokemon_types table
-        client.execute(
-            "INSERT INTO pokemon_types (pokemon_id, type_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
-            &[&pokemon_id, &type_id],
-        )?;
+        let mut past_stats = Vec::new();
+        for (index, past) in pokemon_data["past_stats"]
+            .as_array()
+            .unwrap_or(&empty)
+            .iter()
+            .enumerate()
+        {
+            let path = format!("past_stats[{index}]");
+            let mut stats = BTreeMap::new();
+            for (stat_index, stat) in past["stats"]
+                .as_array()
+                .ok_or_else(|| Error::missing_field(format!("{path}.stats")))?
+                .iter()
+                .enumerate()
+            {
+                let name = stat["stat"]["name"].as_str().ok_or_else(|| {
+                    Error::missing_field(format!("{path}.stats[{stat_index}].stat.name"))
+                })?;
+                let base_stat = stat["base_stat"].as_i64().ok_or_else(|| {
+                    Error::missing_field(format!("{path}.stats[{stat_index}].base_stat"))
+                })?;
+                stats.insert(name.replace('-', "_"), base_stat as i32);
+            }
+            past_stats.push(PastStats {
+                generation: generation_of(past, &path)?,
+                stats,
+            });
+        }
+        past_stats.sort_by_key(|past| past.generation);
+
+        Ok(PokemonHistory {
+            pokedex_number,
+            past_types,
+            past_stats,
+        })
     }
 
-    let abilities = pokemon_data["abilities"]
-        .as_array()
-        .ok_or("Missing abilities array")?;
+    /* Grouping the rows of the tables of the SQL backends: (pokedex number, generation, type) and
+    (pokedex number, generation, stat, base stat), sorted by pokedex number and generation*/
+    pub fn from_rows(
+        type_rows: Vec<(i32, i32, String)>,
+        stat_rows: Vec<(i32, i32, String, i32)>,
+    ) -> Vec<PokemonHistory> {
+        fn history(
+            histories: &mut BTreeMap<i32, PokemonHistory>,
+            pokedex_number: i32,
+        ) -> &mut PokemonHistory {
+            histories
+                .entry(pokedex_number)
+                .or_insert_with(|| PokemonHistory {
+                    pokedex_number,
+                    ..PokemonHistory::default()
+                })
+        }
+
+        let mut histories: BTreeMap<i32, PokemonHistory> = BTreeMap::new();
+        for (pokedex_number, generation, type_name) in type_rows {
+            let past_types = &mut history(&mut histories, pokedex_number).past_types;
+            match past_types.last_mut() {
+                Some(past) if past.generation == generation => past.types.push(type_name),
+                _ => past_types.push(PastTypes {
+                    generation,
+                    types: vec![type_name],
+                }),
+            }
+        }
+        for (pokedex_number, generation, stat, base_stat) in stat_rows {
+            let past_stats = &mut history(&mut histories, pokedex_number).past_stats;
+            match past_stats.last_mut() {
+                Some(past) if past.generation == generation => {
+                    past.stats.insert(stat, base_stat);
+                }
+                _ => past_stats.push(PastStats {
+                    generation,
+                    stats: BTreeMap::from([(stat, base_stat)]),
+                }),
+            }
+        }
+
+        histories.into_values().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.past_types.is_empty() && self.past_stats.is_empty()
+    }
 
-    // Loop for insert the abilities for each pokemon in the db
-    for ability_data in abilities {
-        /* Fetching the pokemon abilities by 'abilities' and the name for the abilities like "Flash
-        fire" or "Cloud nine" */
-        let ability_name = ability_data["ability"]["name"]
+    /* The types in a generation, None when they are the current ones. The values of a past entry
+    last until its generation, so it's the first entry of that generation or a later one*/
+    pub fn types_in(&self, generation: i32) -> Option<&Vec<String>> {
+        self.past_types
+            .iter()
+            .find(|past| past.generation >= generation)
+            .map(|past| &past.types)
+    }
+
+    // The base stat in a generation, None when it's the current one
+    pub fn stat_in(&self, stat: &str, generation: i32) -> Option<i32> {
+        self.past_stats
+            .iter()
+            .filter(|past| past.generation >= generation)
+            .find_map(|past| past.stats.get(stat).copied())
+    }
+}
+
+/* A way a pokemon learns a move in a version group, e.g. thunderbolt by "machine" in
+"sword-shield". The level is 0 when it isn't learned by level up*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LearnedMove {
+    pub move_name: String,
+    pub version_group: String,
+    pub method: String,
+    pub level: i32,
+}
+
+// The moves a pokemon can learn in every game, from 'moves' of the pokemon endpoint
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Learnset {
+    pub pokedex_number: i32,
+    // Sorted by move, version group and method
+    pub moves: Vec<LearnedMove>,
+}
+
+impl Learnset {
+    pub fn from_api(pokemon_data: &Value) -> Result<Self, Error> {
+        let pokedex_number = pokemon_data["id"]
+            .as_i64()
+            .ok_or_else(|| Error::missing_field("id"))? as i32;
+        let empty = Vec::new();
+
+        let mut moves = Vec::new();
+        for (index, move_data) in pokemon_data["moves"]
+            .as_array()
+            .unwrap_or(&empty)
+            .iter()
+            .enumerate()
+        {
+            let path = format!("moves[{index}]");
+            let move_name = move_data["move"]["name"]
+                .as_str()
+                .ok_or_else(|| Error::missing_field(format!("{path}.move.name")))?;
+            for (details_index, details) in move_data["version_group_details"]
+                .as_array()
+                .ok_or_else(|| Error::missing_field(format!("{path}.version_group_details")))?
+                .iter()
+                .enumerate()
+            {
+                let details_path = format!("{path}.version_group_details[{details_index}]");
+                moves.push(LearnedMove {
+                    move_name: move_name.to_string(),
+                    version_group: details["version_group"]["name"]
+                        .as_str()
+                        .ok_or_else(|| {
+                            Error::missing_field(format!("{details_path}.version_group.name"))
+                        })?
+                        .to_string(),
+                    method: details["move_learn_method"]["name"]
+                        .as_str()
+                        .ok_or_else(|| {
+                            Error::missing_field(format!("{details_path}.move_learn_method.name"))
+                        })?
+                        .to_string(),
+                    level: details["level_learned_at"].as_i64().unwrap_or(0) as i32,
+                });
+            }
+        }
+        moves.sort_by(|a, b| {
+            (&a.move_name, &a.version_group, &a.method).cmp(&(
+                &b.move_name,
+                &b.version_group,
+                &b.method,
+            ))
+        });
+        // The API repeats a method when it's learned at two levels, the first one is kept
+        moves.dedup_by(|a, b| {
+            a.move_name == b.move_name && a.version_group == b.version_group && a.method == b.method
+        });
+
+        Ok(Learnset {
+            pokedex_number,
+            moves,
+        })
+    }
+
+    /* If the pokemon can learn the move, in the version group when it's given or in any game
+    without it*/
+    pub fn can_learn(&self, move_name: &str, version_group: Option<&str>) -> bool {
+        self.moves.iter().any(|learned| {
+            learned.move_name == move_name
+                && version_group.is_none_or(|group| learned.version_group == group)
+        })
+    }
+}
+
+/* A form of a species that isn't its default one, e.g. "alola" of Ninetales, "therian" of Landorus
+or "east" of Gastrodon. The varieties of the API are other pokemon, with their own abilities and
+moves. The forms that only change the look don't have them, and neither do the megas the API has
+without moves: theirs are the ones of the species*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PokemonForm {
+    pub pokedex_number: i32,
+    // The slug without the name of the species, e.g. "alola" of "ninetales-alola"
+    pub form: String,
+    // Sorted by name
+    pub abilities: Vec<AbilityRecord>,
+    // Sorted by move, version group and method, like a learnset
+    pub moves: Vec<LearnedMove>,
+}
+
+impl PokemonForm {
+    /* The forms of a pokemon document. A variety, a pokemon that isn't the default one of its
+    species, is a form with its abilities and moves. The default one has the forms that only
+    change its look, e.g. gastrodon-east*/
+    pub fn from_api(pokemon_data: &Value) -> Result<Vec<Self>, Error> {
+        let pokedex_number = pokemon_data["species"]["url"]
+            .as_str()
+            .and_then(resource_id)
+            .ok_or_else(|| Error::missing_field("species.url"))?;
+        let species = pokemon_data["species"]["name"]
             .as_str()
-            .ok_or("Missing abilities name")?;
+            .ok_or_else(|| Error::missing_field("species.name"))?;
+        let name = pokemon_data["name"]
+            .as_str()
+            .ok_or_else(|| Error::missing_field("name"))?;
+        let form_of = |slug: &str| {
+            slug.strip_prefix(species)
+                .and_then(|rest| rest.strip_prefix('-'))
+                .filter(|form| !form.is_empty())
+                .map(str::to_string)
+        };
 
-        let is_hidden = ability_data["is_hidden"]
-            .as_bool()
-            .ok_or("Missing is_hidden values")?;
-
-        client.execute(
-            "INSERT INTO abilities (name) VALUES ($1) ON CONFLICT (name) DO NOTHING",
-            &[&ability_name],
-        )?;
-
-        // Inserting the pokemon abilities on the 'abilities? table
-        let ability_id: i32 = client
-            .query_one(
-                "WITH inserted AS (
-                INSERT INTO abilities (name) VALUES ($1) ON CONFLICT (name) DO NOTHING
-                RETURNING id
-            )
-            SELECT id FROM inserted UNION ALL SELECT id FROM abilities WHERE name = $1 LIMIT 1",
-                &[&ability_name],
-            )?
-            .get(0);
-
-        let pokemon_rows = client.query(
-            "SELECT id FROM pokemon WHERE pokedex_number = $1",
-            &[&pokedex_number],
-        )?;
-        let pokemon_id: i32 = if let Some(row) = pokemon_rows.first() {
-            row.get(0)
-        } else {
-            return Err("Pokemon not found".into());
+        if pokemon_data["is_default"].as_bool() == Some(false) {
+            // A variety that isn't named after its species can't be written as a form of it
+            let Some(form) = form_of(name) else {
+                return Ok(Vec::new());
+            };
+            let mut abilities = abilities_from_api(pokemon_data)?;
+            abilities.sort_by(|a, b| a.name.cmp(&b.name));
+            return Ok(vec![PokemonForm {
+                pokedex_number,
+                form,
+                abilities,
+                moves: Learnset::from_api(pokemon_data)?.moves,
+            }]);
+        }
+
+        Ok(pokemon_data["forms"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|form_data| form_data["name"].as_str())
+            .filter(|slug| *slug != name)
+            .filter_map(form_of)
+            .map(|form| PokemonForm {
+                pokedex_number,
+                form,
+                abilities: Vec::new(),
+                moves: Vec::new(),
+            })
+            .collect())
+    }
+}
+
+// An entry of a regional pokedex, e.g. the number 1 of "galar" is the national 810 (grookey)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegionalDexEntry {
+    pub entry_number: i32,
+    pub pokedex_number: i32,
+}
+
+/* A pokedex of the API, e.g. "kanto", "original-johto" or "galar". "national" has every species,
+the others the ones of a region or a game, with their own numbers*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegionalDex {
+    pub name: String,
+    // The national pokedex doesn't have a region
+    pub region: Option<String>,
+    pub is_main_series: bool,
+    pub entries: Vec<RegionalDexEntry>,
+}
+
+impl RegionalDex {
+    pub fn from_api(pokedex_data: &Value) -> Result<Self, Error> {
+        let name = pokedex_data["name"]
+            .as_str()
+            .ok_or_else(|| Error::missing_field("name"))?;
+
+        let mut entries = Vec::new();
+        for (index, entry_data) in pokedex_data["pokemon_entries"]
+            .as_array()
+            .ok_or_else(|| Error::missing_field("pokemon_entries"))?
+            .iter()
+            .enumerate()
+        {
+            let entry_number = entry_data["entry_number"].as_i64().ok_or_else(|| {
+                Error::missing_field(format!("pokemon_entries[{index}].entry_number"))
+            })? as i32;
+            // The species id is the national pokedex number, it's only in the URL
+            let pokedex_number = entry_data["pokemon_species"]["url"]
+                .as_str()
+                .and_then(resource_id)
+                .ok_or_else(|| {
+                    Error::missing_field(format!("pokemon_entries[{index}].pokemon_species.url"))
+                })?;
+            entries.push(RegionalDexEntry {
+                entry_number,
+                pokedex_number,
+            });
+        }
+        entries.sort_by_key(|entry| entry.entry_number);
+
+        Ok(RegionalDex {
+            name: name.to_string(),
+            region: pokedex_data["region"]["name"].as_str().map(str::to_string),
+            is_main_series: pokedex_data["is_main_series"].as_bool().unwrap_or(true),
+            entries,
+        })
+    }
+}
+
+/* A group of games that share their data, e.g. "red-blue" or "sword-shield". The learnsets, the
+encounters and the pokedexes of the API are by version group*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionGroup {
+    pub name: String,
+    // The order of the releases, from the API
+    pub order: i32,
+    pub generation: Option<i32>,
+    pub regions: Vec<String>,
+    // The pokedexes of the games, the main one first
+    pub pokedexes: Vec<String>,
+}
+
+impl VersionGroup {
+    pub fn from_api(group_data: &Value) -> Result<Self, Error> {
+        let name = group_data["name"]
+            .as_str()
+            .ok_or_else(|| Error::missing_field("name"))?;
+        // Only the names of the resources in a list, e.g. [{"name": "kanto", "url": ...}]
+        let names = |field: &str| -> Vec<String> {
+            group_data[field]
+                .as_array()
+                .map(|resources| {
+                    resources
+                        .iter()
+                        .filter_map(|resource| resource["name"].as_str())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
         };
 
-        client.execute(
-            "INSERT INTO pokemon_abilities (pokemon_id, ability_id, is_hidden) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
-            &[&pokemon_id, &ability_id, &is_hidden],
-        )?;
+        Ok(VersionGroup {
+            name: name.to_string(),
+            order: group_data["order"].as_i64().unwrap_or_default() as i32,
+            generation: group_data["generation"]["url"]
+                .as_str()
+                .and_then(resource_id),
+            regions: names("regions"),
+            pokedexes: names("pokedexes"),
+        })
+    }
+}
+
+// A game of the API, e.g. "sword", with the group it belongs to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameVersion {
+    pub name: String,
+    pub version_group: String,
+    // The English name, e.g. "Sword"
+    pub display_name: Option<String>,
+}
+
+impl GameVersion {
+    pub fn from_api(version_data: &Value) -> Result<Self, Error> {
+        let name = version_data["name"]
+            .as_str()
+            .ok_or_else(|| Error::missing_field("name"))?;
+        let version_group = version_data["version_group"]["name"]
+            .as_str()
+            .ok_or_else(|| Error::missing_field("version_group.name"))?;
+        let display_name = version_data["names"].as_array().and_then(|names| {
+            names
+                .iter()
+                .find(|name| name["language"]["name"] == "en")
+                .and_then(|name| name["name"].as_str())
+                .map(str::to_string)
+        });
+
+        Ok(GameVersion {
+            name: name.to_string(),
+            version_group: version_group.to_string(),
+            display_name,
+        })
+    }
+}
+
+// A name a pokemon can be searched by, the slug of the API doesn't have a language
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PokemonName {
+    pub pokedex_number: i32,
+    pub language: Option<String>,
+    pub name: String,
+}
+
+// One row of the pokemon table with its types joined, used for the analysis
+#[derive(Debug, Clone, PartialEq)]
+pub struct PokemonRow {
+    pub id: i32,
+    pub random_id: i32,
+    pub pokedex_number: i32,
+    pub name: String,
+    pub height: String,
+    pub weight: String,
+    pub hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub special_attack: i32,
+    pub special_defense: i32,
+    pub speed: i32,
+    // Types joined with ", " and sorted by name, e.g. "grass, poison"
+    pub types: String,
+    pub generation: Option<i32>,
+}
+
+impl PokemonRow {
+    // The row with the types and base stats the pokemon had in a generation
+    pub fn in_generation(mut self, history: &PokemonHistory, generation: i32) -> PokemonRow {
+        if let Some(types) = history.types_in(generation) {
+            let mut types = types.clone();
+            types.sort();
+            self.types = types.join(", ");
+        }
+        for (stat, value) in [
+            ("hp", &mut self.hp),
+            ("attack", &mut self.attack),
+            ("defense", &mut self.defense),
+            ("special_attack", &mut self.special_attack),
+            ("special_defense", &mut self.special_defense),
+            ("speed", &mut self.speed),
+        ] {
+            if let Some(past) = history.stat_in(stat, generation) {
+                *value = past;
+            }
+        }
+
+        self
     }
+}
 
-    Ok(())
+// A document of the API saved as it was received, with the hash of the response body
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawDocument {
+    pub url: String,
+    pub fetched_at: DateTime<Utc>,
+    // SHA-256 of the bytes of the response, in hexadecimal
+    pub content_hash: String,
+    pub body: Value,
 }
 
-/* Adding a 'index' function for consistency for keeping the order the 'types' table
-This function is just for avoiding an insert issue in the 'types' table. Before this function,
-the types at insertion in the db, are in disorder and having a random index*/
-pub fn reset_types_table(client: &mut Client) -> Result<(), Box<dyn std::error::Error>> {
-    client.execute("TRUNCATE TABLE types RESTART IDENTITY CASCADE", &[])?;
+impl RawDocument {
+    pub fn new(url: &str, bytes: &[u8]) -> Result<Self, Error> {
+        let body = serde_json::from_slice(bytes).map_err(|e| Error::Decode {
+            path: url.to_string(),
+            message: format!("Invalid JSON: {e}"),
+        })?;
+        let content_hash = Sha256::digest(bytes)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
 
-    let types = [
-        "normal", "fighting", "flying", "poison", "ground", "rock", "bug", "ghost", "steel",
-        "fire", "water", "grass", "electric", "psychic", "ice", "dragon", "dark", "fairy",
-    ];
+        Ok(RawDocument {
+            url: url.to_string(),
+            fetched_at: Utc::now(),
+            content_hash,
+            body,
+        })
+    }
+}
+
+// A row of 'ingestion_runs', one for every time the pokemon are fetched from the API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IngestionRun {
+    pub id: i32,
+    // The API URL the pokemon were fetched from
+    pub source: String,
+    pub started_at: DateTime<Utc>,
+    // None while the run hasn't finished (or if it was interrupted)
+    pub finished_at: Option<DateTime<Utc>>,
+    pub inserted: i32,
+    pub updated: i32,
+    pub unchanged: i32,
+    // Pokemon the API didn't have
+    pub skipped: i32,
+    pub failed: i32,
+    pub duration_ms: Option<i64>,
+}
 
-    for name in types.iter() {
-        client.execute(
-            "INSERT INTO types (name) VALUES ($1) ON CONFLICT (name) DO NOTHING",
-            &[name],
-        )?;
+// A row of 'pokemon_changes', the value of a field before and after a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PokemonChange {
+    pub run_id: i32,
+    pub pokedex_number: i32,
+    pub pokemon_name: String,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_variety_is_a_form_with_its_abilities_and_moves() {
+        let ninetales_alola = serde_json::json!({
+            "id": 10104,
+            "name": "ninetales-alola",
+            "is_default": false,
+            "species": {"name": "ninetales", "url": "https://pokeapi.co/api/v2/pokemon-species/38/"},
+            "abilities": [
+                {"ability": {"name": "snow-warning"}, "is_hidden": true},
+                {"ability": {"name": "snow-cloak"}, "is_hidden": false},
+            ],
+            "moves": [{
+                "move": {"name": "moonblast"},
+                "version_group_details": [{
+                    "version_group": {"name": "sun-moon"},
+                    "move_learn_method": {"name": "level-up"},
+                    "level_learned_at": 0,
+                }],
+            }],
+            "forms": [{"name": "ninetales-alola"}],
+        });
+
+        let forms = PokemonForm::from_api(&ninetales_alola).unwrap();
+        assert_eq!(forms.len(), 1);
+        assert_eq!(
+            (forms[0].pokedex_number, forms[0].form.as_str()),
+            (38, "alola")
+        );
+        assert_eq!(
+            forms[0].abilities,
+            [
+                AbilityRecord {
+                    name: "snow-cloak".to_string(),
+                    is_hidden: false,
+                },
+                AbilityRecord {
+                    name: "snow-warning".to_string(),
+                    is_hidden: true,
+                },
+            ]
+        );
+        assert_eq!(forms[0].moves[0].move_name, "moonblast");
     }
 
-    Ok(())
+    #[test]
+    fn the_default_pokemon_has_the_forms_that_only_change_its_look() {
+        let gastrodon = serde_json::json!({
+            "id": 423,
+            "name": "gastrodon",
+            "is_default": true,
+            "species": {"name": "gastrodon", "url": "https://pokeapi.co/api/v2/pokemon-species/423/"},
+            "abilities": [],
+            "moves": [],
+            "forms": [{"name": "gastrodon"}, {"name": "gastrodon-east"}],
+        });
+
+        let forms = PokemonForm::from_api(&gastrodon).unwrap();
+        assert_eq!(
+            forms,
+            [PokemonForm {
+                pokedex_number: 423,
+                form: "east".to_string(),
+                abilities: Vec::new(),
+                moves: Vec::new(),
+            }]
+        );
+    }
 }
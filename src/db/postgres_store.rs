@@ -0,0 +1,2060 @@
+use crate::config::SslConfig;
+use crate::db::annotations::{group_annotations, Annotation, Note};
+use crate::db::collection::{CaughtPokemon, User};
+use crate::db::pg_credentials::complete_config;
+use crate::db::pokemon_tables::{
+    types_in_order, AbilityRecord, FieldChange, GameVersion, IngestionRun, LearnedMove, Learnset,
+    LocalizedName, PokemonChange, PokemonForm, PokemonHistory, PokemonName, PokemonRecord,
+    PokemonRow, RawDocument, RegionalDex, RegionalDexEntry, SpeciesRecord, UpsertOutcome,
+    VersionGroup, POKEMON_TYPES,
+};
+use crate::db::query::{
+    generation_sql, Pokemon, PokemonSearch, SearchResults, SqlDialect, SqlParam, Stat,
+};
+use crate::db::store::{has_data_sql, PokedexStore, TABLES};
+use crate::db::summary::{
+    postgres_views_sql, stat_columns, StatSummary, StatValues, SummaryView, SUMMARY_VIEWS_VERSION,
+};
+use crate::db::teams::{StatSpread, Team, TeamMember};
+use crate::db::tls::{is_tls_error, TlsSettings};
+use crate::error::{DatabaseError, Error};
+use async_trait::async_trait;
+use chrono::Utc;
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Config, GenericClient, Row};
+
+// Max connections opened at the same time with the database
+const POOL_SIZE: usize = 8;
+
+// The PostgreSQL backend, the connections are taken from a pool
+pub struct PostgresStore {
+    pool: Pool,
+}
+
+impl PostgresStore {
+    /* Establishing connection with the database URL, encrypted as the TLS settings say. What the URL
+    doesn't have comes from the PG* variables and the password files*/
+    pub async fn connect(database_url: &str, ssl: &SslConfig) -> Result<Self, Error> {
+        let (tls, database_url) =
+            TlsSettings::from_url_and_env(database_url, ssl).map_err(Error::Config)?;
+        let mut config: Config = database_url
+            .parse()
+            .map_err(|e| Error::Config(format!("Invalid database URL: {e}")))?;
+        complete_config(&mut config)?;
+        config.ssl_mode(tls.pg_ssl_mode());
+
+        let manager = Manager::from_config(
+            config,
+            tls.connector()?,
+            ManagerConfig {
+                recycling_method: RecyclingMethod::Fast,
+            },
+        );
+        let pool = Pool::builder(manager)
+            .max_size(POOL_SIZE)
+            .build()
+            .map_err(|e| Error::Database(DatabaseError::PoolBuild(e)))?;
+
+        // The pool connects lazily, taking a connection here reports a wrong URL at the start
+        if let Err(e) = pool.get().await {
+            if is_tls_error(&e) {
+                return Err(Error::Database(DatabaseError::Tls(format!(
+                    "TLS negotiation with the database failed (sslmode={}): {}",
+                    tls.mode.as_str(),
+                    tls_error_detail(&e)
+                ))));
+            }
+            return Err(e.into());
+        }
+
+        Ok(PostgresStore { pool })
+    }
+}
+
+// Why a connection to PostgreSQL failed, so the setup can say what to fix
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionProblem {
+    // Wrong password or a user that doesn't exist
+    Authentication(String),
+    // The server answered but it doesn't have the database
+    UnknownDatabase(String),
+    // Wrong host or port, or PostgreSQL isn't running
+    Unreachable(String),
+    Other(String),
+}
+
+impl fmt::Display for ConnectionProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionProblem::Authentication(detail) => {
+                write!(f, "The user or the password are wrong ({detail})")
+            }
+            ConnectionProblem::UnknownDatabase(name) => {
+                write!(f, "The database '{name}' doesn't exist")
+            }
+            ConnectionProblem::Unreachable(detail) => write!(
+                f,
+                "The server can't be reached, check the host and that PostgreSQL is running ({detail})"
+            ),
+            ConnectionProblem::Other(detail) => write!(f, "The connection failed: {detail}"),
+        }
+    }
+}
+
+impl std::error::Error for ConnectionProblem {}
+
+/* Opening one connection with the URL to see if it works. Unlike 'connect' the error says which
+part of the URL is wrong*/
+pub async fn diagnose_connection(
+    database_url: &str,
+    ssl: &SslConfig,
+) -> Result<(), ConnectionProblem> {
+    let (config, tls) = connection_config(database_url, ssl)?;
+    let connector = tls
+        .connector()
+        .map_err(|e| ConnectionProblem::Other(e.to_string()))?;
+    match config.connect(connector).await {
+        Ok((client, connection)) => {
+            drop(client);
+            let _ = connection.await;
+            Ok(())
+        }
+        Err(e) => Err(connection_problem(&config, &e)),
+    }
+}
+
+/* Creating the database of the URL, connected to the "postgres" database of the same server with
+the same user. The user needs the CREATEDB privilege*/
+pub async fn create_database(database_url: &str, ssl: &SslConfig) -> Result<(), Error> {
+    let (mut config, tls) = connection_config(database_url, ssl)?;
+    let name = config
+        .get_dbname()
+        .ok_or_else(|| Error::Config("The URL doesn't have a database name".to_string()))?
+        .to_string();
+    config.dbname("postgres");
+    let (client, connection) = config.connect(tls.connector()?).await?;
+    tokio::spawn(connection);
+    client
+        .batch_execute(&format!(
+            "CREATE DATABASE \"{}\"",
+            name.replace('"', "\"\"")
+        ))
+        .await?;
+
+    Ok(())
+}
+
+fn connection_config(
+    database_url: &str,
+    ssl: &SslConfig,
+) -> Result<(Config, TlsSettings), ConnectionProblem> {
+    let (tls, database_url) =
+        TlsSettings::from_url_and_env(database_url, ssl).map_err(ConnectionProblem::Other)?;
+    let mut config: Config = database_url
+        .parse()
+        .map_err(|e: tokio_postgres::Error| ConnectionProblem::Other(e.to_string()))?;
+    complete_config(&mut config).map_err(|e| ConnectionProblem::Other(e.to_string()))?;
+    config.ssl_mode(tls.pg_ssl_mode());
+
+    Ok((config, tls))
+}
+
+// The SQLSTATE of the server when it answered, the IO error when it couldn't be reached
+fn connection_problem(config: &Config, error: &tokio_postgres::Error) -> ConnectionProblem {
+    if let Some(db_error) = error.as_db_error() {
+        let code = db_error.code();
+        if *code == SqlState::INVALID_PASSWORD
+            || *code == SqlState::INVALID_AUTHORIZATION_SPECIFICATION
+        {
+            return ConnectionProblem::Authentication(db_error.message().to_string());
+        }
+        if *code == SqlState::INVALID_CATALOG_NAME {
+            let name = config.get_dbname().unwrap_or_default().to_string();
+            return ConnectionProblem::UnknownDatabase(name);
+        }
+        return ConnectionProblem::Other(db_error.message().to_string());
+    }
+    if is_tls_error(error) {
+        return ConnectionProblem::Other(format!(
+            "TLS negotiation failed: {}",
+            tls_error_detail(error)
+        ));
+    }
+
+    let mut current: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(e) = current {
+        if e.is::<std::io::Error>() {
+            return ConnectionProblem::Unreachable(e.to_string());
+        }
+        current = e.source();
+    }
+
+    ConnectionProblem::Other(error.to_string())
+}
+
+// The last error of the chain is the one that says why the handshake failed
+fn tls_error_detail(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut detail = error.to_string();
+    let mut current = error.source();
+    while let Some(error) = current {
+        detail = error.to_string();
+        current = error.source();
+    }
+
+    detail
+}
+
+// The pokemon saved with a pokedex number, with its types and abilities
+async fn load_record(
+    client: &impl GenericClient,
+    pokedex_number: i32,
+) -> Result<Option<PokemonRecord>, tokio_postgres::Error> {
+    let Some(row) = client
+        .query_opt(
+            "SELECT * FROM pokemon WHERE pokedex_number = $1",
+            &[&pokedex_number],
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+    let pokemon_id: i32 = row.get("id");
+
+    let types = client
+        .query(
+            "SELECT t.name FROM pokemon_types pt
+            JOIN types t ON pt.type_id = t.id
+            WHERE pt.pokemon_id = $1",
+            &[&pokemon_id],
+        )
+        .await?
+        .iter()
+        .map(|row| row.get("name"))
+        .collect();
+    let abilities = client
+        .query(
+            "SELECT a.name, pa.is_hidden FROM pokemon_abilities pa
+            JOIN abilities a ON pa.ability_id = a.id
+            WHERE pa.pokemon_id = $1",
+            &[&pokemon_id],
+        )
+        .await?
+        .iter()
+        .map(|row| AbilityRecord {
+            name: row.get("name"),
+            is_hidden: row.get("is_hidden"),
+        })
+        .collect();
+
+    Ok(Some(PokemonRecord {
+        pokedex_number,
+        name: row.get("name"),
+        height: row.get("height"),
+        weight: row.get("weight"),
+        hp: row.get("hp"),
+        attack: row.get("attack"),
+        defense: row.get("defense"),
+        special_attack: row.get("special_attack"),
+        special_defense: row.get("special_defense"),
+        speed: row.get("speed"),
+        types,
+        abilities,
+    }))
+}
+
+fn sql_param(param: &SqlParam) -> &(dyn ToSql + Sync) {
+    match param {
+        SqlParam::Int(value) => value,
+        SqlParam::Text(value) => value,
+    }
+}
+
+fn ingestion_run_from_row(row: &Row) -> IngestionRun {
+    IngestionRun {
+        id: row.get("id"),
+        source: row.get("source"),
+        started_at: row.get("started_at"),
+        finished_at: row.get("finished_at"),
+        inserted: row.get("inserted"),
+        updated: row.get("updated"),
+        unchanged: row.get("unchanged"),
+        skipped: row.get("skipped"),
+        failed: row.get("failed"),
+        duration_ms: row.get("duration_ms"),
+    }
+}
+
+// The forms with their abilities and moves, of a species or of every one without a number
+async fn load_forms(
+    client: &impl GenericClient,
+    pokedex_number: Option<i32>,
+) -> Result<Vec<PokemonForm>, tokio_postgres::Error> {
+    let filter = "WHERE $1::INTEGER IS NULL OR pokedex_number = $1";
+    let mut forms: BTreeMap<(i32, String), PokemonForm> = BTreeMap::new();
+    for row in client
+        .query(
+            &format!("SELECT pokedex_number, form FROM pokemon_forms {filter}"),
+            &[&pokedex_number],
+        )
+        .await?
+    {
+        let number: i32 = row.get("pokedex_number");
+        let form: String = row.get("form");
+        forms.insert(
+            (number, form.clone()),
+            PokemonForm {
+                pokedex_number: number,
+                form,
+                abilities: Vec::new(),
+                moves: Vec::new(),
+            },
+        );
+    }
+
+    for row in client
+        .query(
+            &format!(
+                "SELECT pokedex_number, form, ability, is_hidden FROM pokemon_form_abilities
+                {filter} ORDER BY ability"
+            ),
+            &[&pokedex_number],
+        )
+        .await?
+    {
+        if let Some(form) = forms.get_mut(&(row.get("pokedex_number"), row.get("form"))) {
+            form.abilities.push(AbilityRecord {
+                name: row.get("ability"),
+                is_hidden: row.get("is_hidden"),
+            });
+        }
+    }
+    for row in client
+        .query(
+            &format!(
+                "SELECT pokedex_number, form, move_name, version_group, method, level
+                FROM pokemon_form_moves {filter} ORDER BY move_name, version_group, method"
+            ),
+            &[&pokedex_number],
+        )
+        .await?
+    {
+        if let Some(form) = forms.get_mut(&(row.get("pokedex_number"), row.get("form"))) {
+            form.moves.push(LearnedMove {
+                move_name: row.get("move_name"),
+                version_group: row.get("version_group"),
+                method: row.get("method"),
+                level: row.get("level"),
+            });
+        }
+    }
+
+    Ok(forms.into_values().collect())
+}
+
+// The rows of 'pokemon_moves' grouped by pokemon, with the moves sorted like in the API
+fn learnsets_of_rows(rows: &[Row]) -> Vec<Learnset> {
+    let mut learnsets: BTreeMap<i32, Vec<LearnedMove>> = BTreeMap::new();
+    for row in rows {
+        learnsets
+            .entry(row.get("pokedex_number"))
+            .or_default()
+            .push(LearnedMove {
+                move_name: row.get("move_name"),
+                version_group: row.get("version_group"),
+                method: row.get("method"),
+                level: row.get("level"),
+            });
+    }
+
+    learnsets
+        .into_iter()
+        .map(|(pokedex_number, mut moves)| {
+            moves.sort_by(|a, b| {
+                (&a.move_name, &a.version_group, &a.method).cmp(&(
+                    &b.move_name,
+                    &b.version_group,
+                    &b.method,
+                ))
+            });
+            Learnset {
+                pokedex_number,
+                moves,
+            }
+        })
+        .collect()
+}
+
+// The EVs or the IVs of a row of 'team_members', the columns start with "ev" or "iv"
+fn stat_spread(row: &Row, prefix: &str) -> StatSpread {
+    let stat = |name: &str| row.get::<_, i32>(format!("{prefix}_{name}").as_str());
+    StatSpread {
+        hp: stat("hp"),
+        attack: stat("attack"),
+        defense: stat("defense"),
+        special_attack: stat("special_attack"),
+        special_defense: stat("special_defense"),
+        speed: stat("speed"),
+    }
+}
+
+#[async_trait]
+impl PokedexStore for PostgresStore {
+    async fn create_tables(&self) -> Result<(), Error> {
+        let client = self.pool.get().await?;
+        // Query to create tables into the db
+        client
+            .batch_execute(
+                "
+            -- Pokemon principle table
+            CREATE TABLE IF NOT EXISTS pokemon (
+                id   SERIAL PRIMARY KEY,
+                random_id   SERIAL UNIQUE NOT NULL,
+                pokedex_number INTEGER UNIQUE NOT NULL,
+                name    VARCHAR(100) NOT NULL,
+                height  VARCHAR(10),
+                weight  VARCHAR(10),
+                hp INTEGER,
+                attack INTEGER,
+                defense INTEGER,
+                special_attack INTEGER,
+                special_defense INTEGER,
+                speed INTEGER
+        );
+            -- Types of pokemon table
+            CREATE TABLE IF NOT EXISTS types (
+                id SERIAL PRIMARY KEY,
+                name VARCHAR(20) UNIQUE NOT NULL
+        );
+            -- Pokemon types table
+            CREATE TABLE IF NOT EXISTS pokemon_types (
+                pokemon_id SERIAL REFERENCES pokemon(id),
+                type_id INTEGER REFERENCES types(id),
+                PRIMARY KEY (pokemon_id, type_id)
+        );
+            -- Abilities of pokemon table
+            CREATE TABLE IF NOT EXISTS abilities (
+                id SERIAL PRIMARY KEY,
+                name VARCHAR(50) UNIQUE NOT NULL
+        );
+            -- Abilities from each pokemon table
+            CREATE TABLE IF NOT EXISTS pokemon_abilities (
+                pokemon_id INTEGER REFERENCES pokemon(id),
+                ability_id INTEGER REFERENCES abilities(id),
+                is_hidden BOOLEAN NOT NULL,
+                PRIMARY KEY (pokemon_id, ability_id)
+        );
+            -- The species of the API, with the generation it was introduced in
+            CREATE TABLE IF NOT EXISTS species (
+                pokedex_number INTEGER PRIMARY KEY,
+                generation INTEGER
+        );
+            -- The types a pokemon had until a generation, when they changed after it
+            CREATE TABLE IF NOT EXISTS pokemon_past_types (
+                pokedex_number INTEGER NOT NULL,
+                generation INTEGER NOT NULL,
+                slot INTEGER NOT NULL,
+                type_name VARCHAR(20) NOT NULL,
+                PRIMARY KEY (pokedex_number, generation, slot)
+        );
+            -- The base stats a pokemon had until a generation, when they changed after it
+            CREATE TABLE IF NOT EXISTS pokemon_past_stats (
+                pokedex_number INTEGER NOT NULL,
+                generation INTEGER NOT NULL,
+                stat VARCHAR(20) NOT NULL,
+                base_stat INTEGER NOT NULL,
+                PRIMARY KEY (pokedex_number, generation, stat)
+        );
+            -- The pokedexes of the API, the national one and those of the regions and games
+            CREATE TABLE IF NOT EXISTS regional_dexes (
+                name VARCHAR(50) PRIMARY KEY,
+                region VARCHAR(50),
+                is_main_series BOOLEAN NOT NULL
+        );
+            -- The species of each pokedex with their number in it
+            CREATE TABLE IF NOT EXISTS regional_dex_entries (
+                pokedex_name VARCHAR(50) NOT NULL REFERENCES regional_dexes(name),
+                entry_number INTEGER NOT NULL,
+                pokedex_number INTEGER NOT NULL,
+                PRIMARY KEY (pokedex_name, entry_number)
+        );
+            CREATE INDEX IF NOT EXISTS regional_dex_entries_species_idx
+                ON regional_dex_entries (pokedex_number);
+            -- The groups of games that share their data, e.g. sword-shield
+            CREATE TABLE IF NOT EXISTS version_groups (
+                name VARCHAR(50) PRIMARY KEY,
+                sort_order INTEGER NOT NULL,
+                generation INTEGER
+        );
+            CREATE TABLE IF NOT EXISTS version_group_regions (
+                version_group VARCHAR(50) NOT NULL REFERENCES version_groups(name),
+                region VARCHAR(50) NOT NULL,
+                PRIMARY KEY (version_group, region)
+        );
+            -- The pokedexes of each group, the main one has position 0
+            CREATE TABLE IF NOT EXISTS version_group_pokedexes (
+                version_group VARCHAR(50) NOT NULL REFERENCES version_groups(name),
+                pokedex_name VARCHAR(50) NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY (version_group, pokedex_name)
+        );
+            -- The games, e.g. sword
+            CREATE TABLE IF NOT EXISTS versions (
+                name VARCHAR(50) PRIMARY KEY,
+                version_group VARCHAR(50) NOT NULL,
+                display_name VARCHAR(100)
+        );
+            -- The names of each species in every language of the API
+            CREATE TABLE IF NOT EXISTS pokemon_names (
+                id SERIAL PRIMARY KEY,
+                pokedex_number INTEGER NOT NULL,
+                language VARCHAR(20) NOT NULL,
+                name VARCHAR(100) NOT NULL,
+                UNIQUE (pokedex_number, language)
+        );
+            -- Every document fetched from the API, as it was received
+            CREATE TABLE IF NOT EXISTS raw_documents (
+                id SERIAL PRIMARY KEY,
+                url TEXT NOT NULL,
+                fetched_at TIMESTAMPTZ NOT NULL,
+                content_hash CHAR(64) NOT NULL,
+                body JSONB NOT NULL,
+                UNIQUE (url, content_hash)
+        );
+            CREATE INDEX IF NOT EXISTS raw_documents_url_idx
+                ON raw_documents (url, fetched_at DESC);
+            -- Every time the pokemon are fetched from the API
+            CREATE TABLE IF NOT EXISTS ingestion_runs (
+                id SERIAL PRIMARY KEY,
+                source TEXT NOT NULL,
+                started_at TIMESTAMPTZ NOT NULL,
+                finished_at TIMESTAMPTZ,
+                inserted INTEGER NOT NULL DEFAULT 0,
+                updated INTEGER NOT NULL DEFAULT 0,
+                unchanged INTEGER NOT NULL DEFAULT 0,
+                skipped INTEGER NOT NULL DEFAULT 0,
+                failed INTEGER NOT NULL DEFAULT 0,
+                duration_ms BIGINT
+        );
+            -- The values of the pokemon before and after each run that changed them
+            CREATE TABLE IF NOT EXISTS pokemon_changes (
+                id SERIAL PRIMARY KEY,
+                run_id INTEGER NOT NULL REFERENCES ingestion_runs(id),
+                pokedex_number INTEGER NOT NULL,
+                pokemon_name VARCHAR(100) NOT NULL,
+                field VARCHAR(30) NOT NULL,
+                old_value TEXT,
+                new_value TEXT,
+                changed_at TIMESTAMPTZ NOT NULL
+        );
+            CREATE INDEX IF NOT EXISTS pokemon_changes_run_idx ON pokemon_changes (run_id);
+            -- The users of the pokedex and the pokemon each one has caught
+            CREATE TABLE IF NOT EXISTS users (
+                id SERIAL PRIMARY KEY,
+                name VARCHAR(50) UNIQUE NOT NULL
+        );
+            CREATE TABLE IF NOT EXISTS user_pokemon (
+                user_id INTEGER NOT NULL REFERENCES users(id),
+                pokedex_number INTEGER NOT NULL,
+                form VARCHAR(50) NOT NULL DEFAULT '',
+                shiny BOOLEAN NOT NULL DEFAULT FALSE,
+                game VARCHAR(50),
+                caught_at TIMESTAMPTZ NOT NULL,
+                PRIMARY KEY (user_id, pokedex_number, form, shiny)
+        );
+            -- The moves each pokemon can learn, by version group and how they are learned
+            CREATE TABLE IF NOT EXISTS pokemon_moves (
+                pokedex_number INTEGER NOT NULL,
+                move_name VARCHAR(100) NOT NULL,
+                version_group VARCHAR(50) NOT NULL,
+                method VARCHAR(50) NOT NULL,
+                level INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (pokedex_number, move_name, version_group, method)
+        );
+            -- The forms of the species that aren't the default one, the varieties with their
+            -- abilities and moves
+            CREATE TABLE IF NOT EXISTS pokemon_forms (
+                pokedex_number INTEGER NOT NULL,
+                form VARCHAR(100) NOT NULL,
+                PRIMARY KEY (pokedex_number, form)
+        );
+            CREATE TABLE IF NOT EXISTS pokemon_form_abilities (
+                pokedex_number INTEGER NOT NULL,
+                form VARCHAR(100) NOT NULL,
+                ability VARCHAR(100) NOT NULL,
+                is_hidden BOOLEAN NOT NULL,
+                PRIMARY KEY (pokedex_number, form, ability),
+                FOREIGN KEY (pokedex_number, form)
+                    REFERENCES pokemon_forms(pokedex_number, form) ON DELETE CASCADE
+        );
+            CREATE TABLE IF NOT EXISTS pokemon_form_moves (
+                pokedex_number INTEGER NOT NULL,
+                form VARCHAR(100) NOT NULL,
+                move_name VARCHAR(100) NOT NULL,
+                version_group VARCHAR(50) NOT NULL,
+                method VARCHAR(50) NOT NULL,
+                level INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (pokedex_number, form, move_name, version_group, method),
+                FOREIGN KEY (pokedex_number, form)
+                    REFERENCES pokemon_forms(pokedex_number, form) ON DELETE CASCADE
+        );
+            -- The teams of the users, with the pokemon in their slots and the moves of each one
+            CREATE TABLE IF NOT EXISTS teams (
+                id SERIAL PRIMARY KEY,
+                user_id INTEGER NOT NULL REFERENCES users(id),
+                name VARCHAR(100) NOT NULL,
+                game VARCHAR(50),
+                UNIQUE (user_id, name)
+        );
+            CREATE TABLE IF NOT EXISTS team_members (
+                team_id INTEGER NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+                slot INTEGER NOT NULL,
+                pokedex_number INTEGER NOT NULL,
+                species VARCHAR(100) NOT NULL,
+                form VARCHAR(50) NOT NULL DEFAULT '',
+                nickname VARCHAR(100),
+                gender VARCHAR(1),
+                item VARCHAR(100),
+                ability VARCHAR(100),
+                nature VARCHAR(20),
+                level INTEGER NOT NULL DEFAULT 100,
+                shiny BOOLEAN NOT NULL DEFAULT FALSE,
+                ev_hp INTEGER NOT NULL DEFAULT 0,
+                ev_attack INTEGER NOT NULL DEFAULT 0,
+                ev_defense INTEGER NOT NULL DEFAULT 0,
+                ev_special_attack INTEGER NOT NULL DEFAULT 0,
+                ev_special_defense INTEGER NOT NULL DEFAULT 0,
+                ev_speed INTEGER NOT NULL DEFAULT 0,
+                iv_hp INTEGER NOT NULL DEFAULT 31,
+                iv_attack INTEGER NOT NULL DEFAULT 31,
+                iv_defense INTEGER NOT NULL DEFAULT 31,
+                iv_special_attack INTEGER NOT NULL DEFAULT 31,
+                iv_special_defense INTEGER NOT NULL DEFAULT 31,
+                iv_speed INTEGER NOT NULL DEFAULT 31,
+                PRIMARY KEY (team_id, slot)
+        );
+            CREATE TABLE IF NOT EXISTS team_member_moves (
+                team_id INTEGER NOT NULL,
+                slot INTEGER NOT NULL,
+                position INTEGER NOT NULL,
+                move_name VARCHAR(100) NOT NULL,
+                PRIMARY KEY (team_id, slot, position),
+                FOREIGN KEY (team_id, slot) REFERENCES team_members(team_id, slot) ON DELETE CASCADE
+        );
+            -- What the users write about the pokemon: favorites, tags and notes in markdown
+            CREATE TABLE IF NOT EXISTS user_favorites (
+                user_id INTEGER NOT NULL REFERENCES users(id),
+                pokedex_number INTEGER NOT NULL,
+                PRIMARY KEY (user_id, pokedex_number)
+        );
+            CREATE TABLE IF NOT EXISTS user_tags (
+                user_id INTEGER NOT NULL REFERENCES users(id),
+                pokedex_number INTEGER NOT NULL,
+                tag VARCHAR(50) NOT NULL,
+                PRIMARY KEY (user_id, pokedex_number, tag)
+        );
+            CREATE INDEX IF NOT EXISTS user_tags_tag_idx ON user_tags (user_id, tag);
+            CREATE TABLE IF NOT EXISTS user_notes (
+                user_id INTEGER NOT NULL REFERENCES users(id),
+                pokedex_number INTEGER NOT NULL,
+                note TEXT NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL,
+                PRIMARY KEY (user_id, pokedex_number)
+        );
+
+        ",
+            )
+            .await?;
+        /* The summaries are views of the tables, so they are created after them. The views of an
+        older version of the pokedex are dropped, CREATE doesn't replace them*/
+        let version: Option<String> = client
+            .query_one(
+                "SELECT obj_description(to_regclass('generation_stats'), 'pg_class')",
+                &[],
+            )
+            .await?
+            .get(0);
+        if version.as_deref() != Some(SUMMARY_VIEWS_VERSION) {
+            for view in SummaryView::ALL {
+                client
+                    .batch_execute(&format!(
+                        "DROP MATERIALIZED VIEW IF EXISTS {}",
+                        view.view_name()
+                    ))
+                    .await?;
+            }
+        }
+        client.batch_execute(&postgres_views_sql()).await?;
+
+        println!("Tables created or uploaded.");
+
+        Ok(())
+    }
+
+    /* Adding a 'index' function for consistency for keeping the order the 'types' table
+    This function is just for avoiding an insert issue in the 'types' table. Before this function,
+    the types at insertion in the db, are in disorder and having a random index*/
+    async fn reset_types_table(&self) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+
+        // When the types already have the right index the table isn't touched
+        let saved: Vec<(i32, String)> = client
+            .query("SELECT id, name FROM types ORDER BY id", &[])
+            .await?
+            .iter()
+            .map(|row| (row.get("id"), row.get("name")))
+            .collect();
+        if types_in_order(&saved) {
+            return Ok(());
+        }
+
+        let tx = client.transaction().await?;
+        tx.execute("TRUNCATE TABLE types RESTART IDENTITY CASCADE", &[])
+            .await?;
+
+        for name in POKEMON_TYPES.iter() {
+            tx.execute(
+                "INSERT INTO types (name) VALUES ($1) ON CONFLICT (name) DO NOTHING",
+                &[name],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Query for inserting the fetching pokemons
+    async fn upsert_pokemon(&self, pokemon: &PokemonRecord) -> Result<UpsertOutcome, Error> {
+        let mut client = self.pool.get().await?;
+        // Everything of the pokemon is saved or nothing, even when several are inserted at once
+        let tx = client.transaction().await?;
+        // The saved pokemon is compared with the new one to know what changed
+        let old = load_record(&*tx, pokemon.pokedex_number).await?;
+
+        let pokemon_id: i32 = tx
+            .query_one(
+                "INSERT INTO pokemon (pokedex_number, name, height, weight, hp, attack, defense, special_attack, special_defense, speed)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ON CONFLICT(pokedex_number) DO UPDATE SET
+                    name = EXCLUDED.name,
+                    height = EXCLUDED.height,
+                    weight = EXCLUDED.weight,
+                    hp = EXCLUDED.hp,
+                    attack = EXCLUDED.attack,
+                    defense = EXCLUDED.defense,
+                    special_attack = EXCLUDED.special_attack,
+                    special_defense = EXCLUDED.special_defense,
+                    speed = EXCLUDED.speed
+                RETURNING id",
+                &[
+                    &pokemon.pokedex_number,
+                    &pokemon.name,
+                    &pokemon.height,
+                    &pokemon.weight,
+                    &pokemon.hp,
+                    &pokemon.attack,
+                    &pokemon.defense,
+                    &pokemon.special_attack,
+                    &pokemon.special_defense,
+                    &pokemon.speed,
+                ],
+            )
+            .await?
+            .get(0);
+
+        // The types and abilities the pokemon doesn't have anymore are removed
+        let ability_names: Vec<&str> = pokemon.abilities.iter().map(|a| a.name.as_str()).collect();
+        tx.execute(
+            "DELETE FROM pokemon_types WHERE pokemon_id = $1
+            AND type_id NOT IN (SELECT id FROM types WHERE name = ANY($2))",
+            &[&pokemon_id, &pokemon.types],
+        )
+        .await?;
+        tx.execute(
+            "DELETE FROM pokemon_abilities WHERE pokemon_id = $1
+            AND ability_id NOT IN (SELECT id FROM abilities WHERE name = ANY($2))",
+            &[&pokemon_id, &ability_names],
+        )
+        .await?;
+
+        // Loop for insert the type for each pokemon in the db
+        for type_name in &pokemon.types {
+            // Inserting the pokemon type on the 'types' table
+            let type_id: i32 = tx
+                .query_one("SELECT id FROM types WHERE name = $1", &[type_name])
+                .await?
+                .get(0);
+
+            // Once fetched the data, the pokedex insert the id type and pokemon id in the
+            // pokemon_types table
+            tx.execute(
+                "INSERT INTO pokemon_types (pokemon_id, type_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                &[&pokemon_id, &type_id],
+            )
+            .await?;
+        }
+
+        // Loop for insert the abilities for each pokemon in the db
+        for ability in &pokemon.abilities {
+            /* Inserting the pokemon abilities on the 'abilities' table. The DO UPDATE always
+            returns the id, even when another upsert running at the same time inserted it*/
+            let ability_id: i32 = tx
+                .query_one(
+                    "INSERT INTO abilities (name) VALUES ($1)
+                    ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+                    RETURNING id",
+                    &[&ability.name],
+                )
+                .await?
+                .get(0);
+
+            tx.execute(
+                "INSERT INTO pokemon_abilities (pokemon_id, ability_id, is_hidden) VALUES ($1, $2, $3)
+                ON CONFLICT (pokemon_id, ability_id) DO UPDATE SET is_hidden = EXCLUDED.is_hidden",
+                &[&pokemon_id, &ability_id, &ability.is_hidden],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(UpsertOutcome::between(old.as_ref(), pokemon))
+    }
+
+    async fn search(&self, search: &PokemonSearch) -> Result<SearchResults, Error> {
+        if let Some(generation) = search.as_of_generation {
+            return self.search_as_of(search, generation).await;
+        }
+        let sql = search.to_sql(SqlDialect::Postgres);
+        let client = self.pool.get().await?;
+        let filter_params: Vec<&(dyn ToSql + Sync)> = sql.params.iter().map(sql_param).collect();
+
+        let total: i64 = client
+            .query_one(
+                &format!("SELECT COUNT(*) FROM pokemon p {}", sql.where_clause),
+                &filter_params,
+            )
+            .await?
+            .get(0);
+
+        let mut page_params = filter_params.clone();
+        page_params.extend(sql.page.iter().map(|value| value as &(dyn ToSql + Sync)));
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT p.*, {} AS generation, {} AS regional_number FROM pokemon p {} {} {}",
+                    generation_sql(),
+                    sql.regional_number,
+                    sql.where_clause,
+                    sql.order_by,
+                    sql.limit
+                ),
+                &page_params,
+            )
+            .await?;
+        let ids: Vec<i32> = rows.iter().map(|row| row.get("id")).collect();
+
+        // The types and abilities of the whole page are loaded with one query each
+        let mut types: HashMap<i32, Vec<String>> = HashMap::new();
+        for row in client
+            .query(
+                "SELECT pt.pokemon_id, t.name FROM pokemon_types pt
+                JOIN types t ON pt.type_id = t.id
+                WHERE pt.pokemon_id = ANY($1)",
+                &[&ids],
+            )
+            .await?
+        {
+            types
+                .entry(row.get("pokemon_id"))
+                .or_default()
+                .push(row.get("name"));
+        }
+        let mut abilities: HashMap<i32, Vec<AbilityRecord>> = HashMap::new();
+        for row in client
+            .query(
+                "SELECT pa.pokemon_id, a.name, pa.is_hidden FROM pokemon_abilities pa
+                JOIN abilities a ON pa.ability_id = a.id
+                WHERE pa.pokemon_id = ANY($1)",
+                &[&ids],
+            )
+            .await?
+        {
+            abilities
+                .entry(row.get("pokemon_id"))
+                .or_default()
+                .push(AbilityRecord {
+                    name: row.get("name"),
+                    is_hidden: row.get("is_hidden"),
+                });
+        }
+
+        let pokemon = rows
+            .iter()
+            .map(|row| {
+                let id: i32 = row.get("id");
+                let record = PokemonRecord {
+                    pokedex_number: row.get("pokedex_number"),
+                    name: row.get("name"),
+                    height: row.get("height"),
+                    weight: row.get("weight"),
+                    hp: row.get("hp"),
+                    attack: row.get("attack"),
+                    defense: row.get("defense"),
+                    special_attack: row.get("special_attack"),
+                    special_defense: row.get("special_defense"),
+                    speed: row.get("speed"),
+                    types: types.remove(&id).unwrap_or_default(),
+                    abilities: abilities.remove(&id).unwrap_or_default(),
+                };
+                let mut pokemon = Pokemon::from_record(id, &record, row.get("generation"));
+                pokemon.regional_number = row.get("regional_number");
+                pokemon
+            })
+            .collect();
+
+        Ok(SearchResults { total, pokemon })
+    }
+
+    async fn upsert_species(&self, species: &SpeciesRecord) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        tx.execute(
+            "INSERT INTO species (pokedex_number, generation) VALUES ($1, $2)
+            ON CONFLICT (pokedex_number) DO UPDATE SET generation = EXCLUDED.generation",
+            &[&species.pokedex_number, &species.generation],
+        )
+        .await?;
+        tx.execute(
+            "DELETE FROM pokemon_names WHERE pokedex_number = $1",
+            &[&species.pokedex_number],
+        )
+        .await?;
+        for name in &species.names {
+            tx.execute(
+                "INSERT INTO pokemon_names (pokedex_number, language, name) VALUES ($1, $2, $3)
+                ON CONFLICT (pokedex_number, language) DO UPDATE SET name = EXCLUDED.name",
+                &[&species.pokedex_number, &name.language, &name.name],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn list_species(&self) -> Result<Vec<SpeciesRecord>, Error> {
+        let client = self.pool.get().await?;
+        let mut species: Vec<SpeciesRecord> = client
+            .query(
+                "SELECT pokedex_number, generation FROM species ORDER BY pokedex_number",
+                &[],
+            )
+            .await?
+            .iter()
+            .map(|row| SpeciesRecord {
+                pokedex_number: row.get("pokedex_number"),
+                generation: row.get("generation"),
+                names: Vec::new(),
+            })
+            .collect();
+        let mut names: HashMap<i32, Vec<LocalizedName>> = HashMap::new();
+        for row in client
+            .query(
+                "SELECT pokedex_number, language, name FROM pokemon_names ORDER BY id",
+                &[],
+            )
+            .await?
+        {
+            names
+                .entry(row.get("pokedex_number"))
+                .or_default()
+                .push(LocalizedName {
+                    language: row.get("language"),
+                    name: row.get("name"),
+                });
+        }
+        for record in &mut species {
+            record.names = names.remove(&record.pokedex_number).unwrap_or_default();
+        }
+        // The names saved before the species table existed don't have a generation
+        species.extend(
+            names
+                .into_iter()
+                .map(|(pokedex_number, names)| SpeciesRecord {
+                    pokedex_number,
+                    generation: None,
+                    names,
+                }),
+        );
+        species.sort_by_key(|record| record.pokedex_number);
+
+        Ok(species)
+    }
+
+    async fn upsert_pokemon_history(&self, history: &PokemonHistory) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        tx.execute(
+            "DELETE FROM pokemon_past_types WHERE pokedex_number = $1",
+            &[&history.pokedex_number],
+        )
+        .await?;
+        for past in &history.past_types {
+            for (slot, type_name) in past.types.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO pokemon_past_types (pokedex_number, generation, slot, type_name)
+                    VALUES ($1, $2, $3, $4)",
+                    &[
+                        &history.pokedex_number,
+                        &past.generation,
+                        &(slot as i32 + 1),
+                        type_name,
+                    ],
+                )
+                .await?;
+            }
+        }
+        tx.execute(
+            "DELETE FROM pokemon_past_stats WHERE pokedex_number = $1",
+            &[&history.pokedex_number],
+        )
+        .await?;
+        for past in &history.past_stats {
+            for (stat, base_stat) in &past.stats {
+                tx.execute(
+                    "INSERT INTO pokemon_past_stats (pokedex_number, generation, stat, base_stat)
+                    VALUES ($1, $2, $3, $4)",
+                    &[&history.pokedex_number, &past.generation, stat, base_stat],
+                )
+                .await?;
+            }
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn list_pokemon_history(&self) -> Result<Vec<PokemonHistory>, Error> {
+        let client = self.pool.get().await?;
+        let type_rows = client
+            .query(
+                "SELECT pokedex_number, generation, type_name FROM pokemon_past_types
+                ORDER BY pokedex_number, generation, slot",
+                &[],
+            )
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    row.get("pokedex_number"),
+                    row.get("generation"),
+                    row.get("type_name"),
+                )
+            })
+            .collect();
+        let stat_rows = client
+            .query(
+                "SELECT pokedex_number, generation, stat, base_stat FROM pokemon_past_stats
+                ORDER BY pokedex_number, generation, stat",
+                &[],
+            )
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    row.get("pokedex_number"),
+                    row.get("generation"),
+                    row.get("stat"),
+                    row.get("base_stat"),
+                )
+            })
+            .collect();
+
+        Ok(PokemonHistory::from_rows(type_rows, stat_rows))
+    }
+
+    async fn upsert_regional_dex(&self, dex: &RegionalDex) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        tx.execute(
+            "INSERT INTO regional_dexes (name, region, is_main_series) VALUES ($1, $2, $3)
+            ON CONFLICT (name) DO UPDATE
+            SET region = EXCLUDED.region, is_main_series = EXCLUDED.is_main_series",
+            &[&dex.name, &dex.region, &dex.is_main_series],
+        )
+        .await?;
+        tx.execute(
+            "DELETE FROM regional_dex_entries WHERE pokedex_name = $1",
+            &[&dex.name],
+        )
+        .await?;
+        for entry in &dex.entries {
+            tx.execute(
+                "INSERT INTO regional_dex_entries (pokedex_name, entry_number, pokedex_number)
+                VALUES ($1, $2, $3)",
+                &[&dex.name, &entry.entry_number, &entry.pokedex_number],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn list_regional_dexes(&self) -> Result<Vec<RegionalDex>, Error> {
+        let client = self.pool.get().await?;
+        let mut entries: HashMap<String, Vec<RegionalDexEntry>> = HashMap::new();
+        for row in client
+            .query(
+                "SELECT pokedex_name, entry_number, pokedex_number FROM regional_dex_entries
+                ORDER BY pokedex_name, entry_number",
+                &[],
+            )
+            .await?
+        {
+            entries
+                .entry(row.get("pokedex_name"))
+                .or_default()
+                .push(RegionalDexEntry {
+                    entry_number: row.get("entry_number"),
+                    pokedex_number: row.get("pokedex_number"),
+                });
+        }
+        let rows = client
+            .query(
+                "SELECT name, region, is_main_series FROM regional_dexes ORDER BY name",
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let name: String = row.get("name");
+                RegionalDex {
+                    entries: entries.remove(&name).unwrap_or_default(),
+                    name,
+                    region: row.get("region"),
+                    is_main_series: row.get("is_main_series"),
+                }
+            })
+            .collect())
+    }
+
+    async fn upsert_version_group(&self, group: &VersionGroup) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        tx.execute(
+            "INSERT INTO version_groups (name, sort_order, generation) VALUES ($1, $2, $3)
+            ON CONFLICT (name) DO UPDATE
+            SET sort_order = EXCLUDED.sort_order, generation = EXCLUDED.generation",
+            &[&group.name, &group.order, &group.generation],
+        )
+        .await?;
+        tx.execute(
+            "DELETE FROM version_group_regions WHERE version_group = $1",
+            &[&group.name],
+        )
+        .await?;
+        for region in &group.regions {
+            tx.execute(
+                "INSERT INTO version_group_regions (version_group, region) VALUES ($1, $2)
+                ON CONFLICT DO NOTHING",
+                &[&group.name, region],
+            )
+            .await?;
+        }
+        tx.execute(
+            "DELETE FROM version_group_pokedexes WHERE version_group = $1",
+            &[&group.name],
+        )
+        .await?;
+        for (position, pokedex) in group.pokedexes.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO version_group_pokedexes (version_group, pokedex_name, position)
+                VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                &[&group.name, pokedex, &(position as i32)],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn upsert_version(&self, version: &GameVersion) -> Result<(), Error> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "INSERT INTO versions (name, version_group, display_name) VALUES ($1, $2, $3)
+                ON CONFLICT (name) DO UPDATE
+                SET version_group = EXCLUDED.version_group, display_name = EXCLUDED.display_name",
+                &[&version.name, &version.version_group, &version.display_name],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_version_groups(&self) -> Result<Vec<VersionGroup>, Error> {
+        let client = self.pool.get().await?;
+        let mut regions: HashMap<String, Vec<String>> = HashMap::new();
+        for row in client
+            .query(
+                "SELECT version_group, region FROM version_group_regions
+                ORDER BY version_group, region",
+                &[],
+            )
+            .await?
+        {
+            regions
+                .entry(row.get("version_group"))
+                .or_default()
+                .push(row.get("region"));
+        }
+        let mut pokedexes: HashMap<String, Vec<String>> = HashMap::new();
+        for row in client
+            .query(
+                "SELECT version_group, pokedex_name FROM version_group_pokedexes
+                ORDER BY version_group, position",
+                &[],
+            )
+            .await?
+        {
+            pokedexes
+                .entry(row.get("version_group"))
+                .or_default()
+                .push(row.get("pokedex_name"));
+        }
+        let rows = client
+            .query(
+                "SELECT name, sort_order, generation FROM version_groups ORDER BY sort_order, name",
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let name: String = row.get("name");
+                VersionGroup {
+                    regions: regions.remove(&name).unwrap_or_default(),
+                    pokedexes: pokedexes.remove(&name).unwrap_or_default(),
+                    name,
+                    order: row.get("sort_order"),
+                    generation: row.get("generation"),
+                }
+            })
+            .collect())
+    }
+
+    async fn list_versions(&self) -> Result<Vec<GameVersion>, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT name, version_group, display_name FROM versions ORDER BY name",
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| GameVersion {
+                name: row.get("name"),
+                version_group: row.get("version_group"),
+                display_name: row.get("display_name"),
+            })
+            .collect())
+    }
+
+    async fn list_names(&self) -> Result<Vec<PokemonName>, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT pokedex_number, NULL AS language, name FROM pokemon
+                UNION ALL
+                SELECT pokedex_number, language, name FROM pokemon_names",
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| PokemonName {
+                pokedex_number: row.get("pokedex_number"),
+                language: row.get("language"),
+                name: row.get("name"),
+            })
+            .collect())
+    }
+
+    async fn load_pokemon_rows(&self) -> Result<Vec<PokemonRow>, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                &format!(
+                    "
+            SELECT p.*,
+                string_agg(DISTINCT t.name, ', 'ORDER BY t.name) as types,
+                {} AS generation
+            FROM pokemon p
+            LEFT JOIN pokemon_types pt ON p.id = pt.pokemon_id
+            LEFT JOIN types t ON pt.type_id = t.id
+            GROUP BY p.id
+            ",
+                    generation_sql()
+                ),
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| PokemonRow {
+                id: row.get("id"),
+                random_id: row.get("random_id"),
+                pokedex_number: row.get("pokedex_number"),
+                name: row.get("name"),
+                height: row.get("height"),
+                weight: row.get("weight"),
+                hp: row.get("hp"),
+                attack: row.get("attack"),
+                defense: row.get("defense"),
+                special_attack: row.get("special_attack"),
+                special_defense: row.get("special_defense"),
+                speed: row.get("speed"),
+                types: row.get::<_, Option<String>>("types").unwrap_or_default(),
+                generation: row.get("generation"),
+            })
+            .collect())
+    }
+
+    async fn archive_document(&self, document: &RawDocument) -> Result<(), Error> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "INSERT INTO raw_documents (url, fetched_at, content_hash, body)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (url, content_hash) DO UPDATE SET fetched_at = EXCLUDED.fetched_at",
+                &[
+                    &document.url,
+                    &document.fetched_at,
+                    &document.content_hash,
+                    &document.body,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load_raw_documents(&self, url_prefix: &str) -> Result<Vec<RawDocument>, Error> {
+        let client = self.pool.get().await?;
+        // The prefix is compared with starts_with so '%' and '_' in the URL don't match anything
+        let rows = client
+            .query(
+                "SELECT DISTINCT ON (url) url, fetched_at, content_hash, body
+                FROM raw_documents
+                WHERE starts_with(url, $1)
+                ORDER BY url, fetched_at DESC",
+                &[&url_prefix],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| RawDocument {
+                url: row.get("url"),
+                fetched_at: row.get("fetched_at"),
+                content_hash: row.get("content_hash"),
+                body: row.get("body"),
+            })
+            .collect())
+    }
+
+    async fn refresh_summaries(&self) -> Result<(), Error> {
+        let client = self.pool.get().await?;
+        // CONCURRENTLY lets the views be read while they are refreshed, it needs the unique indexes
+        for view in SummaryView::ALL {
+            client
+                .batch_execute(&format!(
+                    "REFRESH MATERIALIZED VIEW CONCURRENTLY {}",
+                    view.view_name()
+                ))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn load_summary(&self, view: SummaryView) -> Result<Vec<StatSummary>, Error> {
+        let client = self.pool.get().await?;
+        // The "C" collation sorts the names like Rust does, so every backend has the same order
+        let order_by = match view {
+            SummaryView::Generations => view.group_column().to_string(),
+            _ => format!("{} COLLATE \"C\"", view.group_column()),
+        };
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT CAST({} AS TEXT) AS group_name, {} FROM {} ORDER BY {order_by}",
+                    view.group_column(),
+                    stat_columns().join(", "),
+                    view.view_name()
+                ),
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let mut mean = StatValues::default();
+                let mut median = StatValues::default();
+                for stat in Stat::ALL {
+                    mean.set(stat, row.get(format!("mean_{}", stat.name()).as_str()));
+                    median.set(stat, row.get(format!("median_{}", stat.name()).as_str()));
+                }
+                StatSummary {
+                    group: row.get("group_name"),
+                    pokemon_count: row.get("pokemon_count"),
+                    mean,
+                    median,
+                }
+            })
+            .collect())
+    }
+
+    async fn start_ingestion_run(&self, source: &str) -> Result<IngestionRun, Error> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_one(
+                "INSERT INTO ingestion_runs (source, started_at) VALUES ($1, $2) RETURNING *",
+                &[&source, &Utc::now()],
+            )
+            .await?;
+
+        Ok(ingestion_run_from_row(&row))
+    }
+
+    async fn finish_ingestion_run(&self, run: &IngestionRun) -> Result<(), Error> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "UPDATE ingestion_runs SET finished_at = $2, inserted = $3, updated = $4,
+                    unchanged = $5, skipped = $6, failed = $7, duration_ms = $8
+                WHERE id = $1",
+                &[
+                    &run.id,
+                    &run.finished_at,
+                    &run.inserted,
+                    &run.updated,
+                    &run.unchanged,
+                    &run.skipped,
+                    &run.failed,
+                    &run.duration_ms,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn record_changes(
+        &self,
+        run_id: i32,
+        pokemon: &PokemonRecord,
+        changes: &[FieldChange],
+    ) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        let changed_at = Utc::now();
+
+        for change in changes {
+            tx.execute(
+                "INSERT INTO pokemon_changes (run_id, pokedex_number, pokemon_name, field, old_value, new_value, changed_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &run_id,
+                    &pokemon.pokedex_number,
+                    &pokemon.name,
+                    &change.field,
+                    &change.old_value,
+                    &change.new_value,
+                    &changed_at,
+                ],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn restore_history(
+        &self,
+        runs: &[IngestionRun],
+        changes: &[PokemonChange],
+    ) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+
+        for run in runs {
+            tx.execute(
+                "INSERT INTO ingestion_runs (id, source, started_at, finished_at, inserted, updated, unchanged, skipped, failed, duration_ms)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                &[
+                    &run.id,
+                    &run.source,
+                    &run.started_at,
+                    &run.finished_at,
+                    &run.inserted,
+                    &run.updated,
+                    &run.unchanged,
+                    &run.skipped,
+                    &run.failed,
+                    &run.duration_ms,
+                ],
+            )
+            .await?;
+        }
+        // The ids were given, so the sequence must continue after the last one
+        tx.execute(
+            "SELECT setval(pg_get_serial_sequence('ingestion_runs', 'id'), MAX(id))
+            FROM ingestion_runs HAVING MAX(id) IS NOT NULL",
+            &[],
+        )
+        .await?;
+
+        for change in changes {
+            tx.execute(
+                "INSERT INTO pokemon_changes (run_id, pokedex_number, pokemon_name, field, old_value, new_value, changed_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &change.run_id,
+                    &change.pokedex_number,
+                    &change.pokemon_name,
+                    &change.field,
+                    &change.old_value,
+                    &change.new_value,
+                    &change.changed_at,
+                ],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn list_ingestion_runs(&self) -> Result<Vec<IngestionRun>, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query("SELECT * FROM ingestion_runs ORDER BY id DESC", &[])
+            .await?;
+
+        Ok(rows.iter().map(ingestion_run_from_row).collect())
+    }
+
+    async fn changes_between_runs(
+        &self,
+        from_run: i32,
+        to_run: i32,
+    ) -> Result<Vec<PokemonChange>, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT * FROM pokemon_changes
+                WHERE run_id > $1 AND run_id <= $2
+                ORDER BY run_id, pokedex_number, id",
+                &[&from_run, &to_run],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| PokemonChange {
+                run_id: row.get("run_id"),
+                pokedex_number: row.get("pokedex_number"),
+                pokemon_name: row.get("pokemon_name"),
+                field: row.get("field"),
+                old_value: row.get("old_value"),
+                new_value: row.get("new_value"),
+                changed_at: row.get("changed_at"),
+            })
+            .collect())
+    }
+
+    async fn create_user(&self, name: &str) -> Result<User, Error> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_opt(
+                "INSERT INTO users (name) VALUES ($1) ON CONFLICT (name) DO NOTHING RETURNING id",
+                &[&name],
+            )
+            .await?
+            .ok_or_else(|| Error::Input(format!("The user '{name}' already exists")))?;
+
+        Ok(User {
+            id: row.get("id"),
+            name: name.to_string(),
+        })
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query("SELECT id, name FROM users ORDER BY name", &[])
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| User {
+                id: row.get("id"),
+                name: row.get("name"),
+            })
+            .collect())
+    }
+
+    async fn mark_caught(&self, user_id: i32, pokemon: &CaughtPokemon) -> Result<(), Error> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "INSERT INTO user_pokemon (user_id, pokedex_number, form, shiny, game, caught_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (user_id, pokedex_number, form, shiny) DO UPDATE
+                SET game = EXCLUDED.game, caught_at = EXCLUDED.caught_at",
+                &[
+                    &user_id,
+                    &pokemon.pokedex_number,
+                    &pokemon.form,
+                    &pokemon.shiny,
+                    &pokemon.game,
+                    &pokemon.caught_at,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn unmark_caught(
+        &self,
+        user_id: i32,
+        pokedex_number: i32,
+        form: &str,
+        shiny: bool,
+    ) -> Result<bool, Error> {
+        let client = self.pool.get().await?;
+        let deleted = client
+            .execute(
+                "DELETE FROM user_pokemon
+                WHERE user_id = $1 AND pokedex_number = $2 AND form = $3 AND shiny = $4",
+                &[&user_id, &pokedex_number, &form, &shiny],
+            )
+            .await?;
+
+        Ok(deleted > 0)
+    }
+
+    async fn list_caught(&self, user_id: i32) -> Result<Vec<CaughtPokemon>, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT pokedex_number, form, shiny, game, caught_at FROM user_pokemon
+                WHERE user_id = $1 ORDER BY pokedex_number, form, shiny",
+                &[&user_id],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| CaughtPokemon {
+                pokedex_number: row.get("pokedex_number"),
+                form: row.get("form"),
+                shiny: row.get("shiny"),
+                game: row.get("game"),
+                caught_at: row.get("caught_at"),
+            })
+            .collect())
+    }
+
+    async fn upsert_learnset(&self, learnset: &Learnset) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        tx.execute(
+            "DELETE FROM pokemon_moves WHERE pokedex_number = $1",
+            &[&learnset.pokedex_number],
+        )
+        .await?;
+        for learned in &learnset.moves {
+            tx.execute(
+                "INSERT INTO pokemon_moves (pokedex_number, move_name, version_group, method, level)
+                VALUES ($1, $2, $3, $4, $5)",
+                &[
+                    &learnset.pokedex_number,
+                    &learned.move_name,
+                    &learned.version_group,
+                    &learned.method,
+                    &learned.level,
+                ],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_learnset(&self, pokedex_number: i32) -> Result<Learnset, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT pokedex_number, move_name, version_group, method, level FROM pokemon_moves
+                WHERE pokedex_number = $1",
+                &[&pokedex_number],
+            )
+            .await?;
+
+        Ok(learnsets_of_rows(&rows).pop().unwrap_or_else(|| Learnset {
+            pokedex_number,
+            moves: Vec::new(),
+        }))
+    }
+
+    async fn list_learnsets(&self) -> Result<Vec<Learnset>, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT pokedex_number, move_name, version_group, method, level FROM pokemon_moves",
+                &[],
+            )
+            .await?;
+
+        Ok(learnsets_of_rows(&rows))
+    }
+
+    async fn save_team(&self, user_id: i32, team: &Team) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        // The members and their moves are deleted with the old team
+        tx.execute(
+            "DELETE FROM teams WHERE user_id = $1 AND name = $2",
+            &[&user_id, &team.name],
+        )
+        .await?;
+        let team_id: i32 = tx
+            .query_one(
+                "INSERT INTO teams (user_id, name, game) VALUES ($1, $2, $3) RETURNING id",
+                &[&user_id, &team.name, &team.game],
+            )
+            .await?
+            .get("id");
+
+        for (slot, member) in team.members.iter().enumerate() {
+            let slot = slot as i32 + 1;
+            tx.execute(
+                "INSERT INTO team_members (team_id, slot, pokedex_number, species, form, nickname, gender, item, ability, nature, level, shiny,
+                    ev_hp, ev_attack, ev_defense, ev_special_attack, ev_special_defense, ev_speed,
+                    iv_hp, iv_attack, iv_defense, iv_special_attack, iv_special_defense, iv_speed)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24)",
+                &[
+                    &team_id,
+                    &slot,
+                    &member.pokedex_number,
+                    &member.species,
+                    &member.form,
+                    &member.nickname,
+                    &member.gender,
+                    &member.item,
+                    &member.ability,
+                    &member.nature,
+                    &member.level,
+                    &member.shiny,
+                    &member.evs.hp,
+                    &member.evs.attack,
+                    &member.evs.defense,
+                    &member.evs.special_attack,
+                    &member.evs.special_defense,
+                    &member.evs.speed,
+                    &member.ivs.hp,
+                    &member.ivs.attack,
+                    &member.ivs.defense,
+                    &member.ivs.special_attack,
+                    &member.ivs.special_defense,
+                    &member.ivs.speed,
+                ],
+            )
+            .await?;
+            for (position, move_name) in member.moves.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO team_member_moves (team_id, slot, position, move_name)
+                    VALUES ($1, $2, $3, $4)",
+                    &[&team_id, &slot, &(position as i32 + 1), move_name],
+                )
+                .await?;
+            }
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn list_teams(&self, user_id: i32) -> Result<Vec<Team>, Error> {
+        let client = self.pool.get().await?;
+        let team_rows = client
+            .query(
+                "SELECT id, name, game FROM teams WHERE user_id = $1 ORDER BY name",
+                &[&user_id],
+            )
+            .await?;
+        let member_rows = client
+            .query(
+                "SELECT team_members.* FROM team_members
+                JOIN teams ON teams.id = team_members.team_id
+                WHERE teams.user_id = $1 ORDER BY team_id, slot",
+                &[&user_id],
+            )
+            .await?;
+        let move_rows = client
+            .query(
+                "SELECT team_id, slot, move_name FROM team_member_moves
+                JOIN teams ON teams.id = team_member_moves.team_id
+                WHERE teams.user_id = $1 ORDER BY team_id, slot, position",
+                &[&user_id],
+            )
+            .await?;
+
+        let mut moves: HashMap<(i32, i32), Vec<String>> = HashMap::new();
+        for row in &move_rows {
+            moves
+                .entry((row.get("team_id"), row.get("slot")))
+                .or_default()
+                .push(row.get("move_name"));
+        }
+        let mut members: HashMap<i32, Vec<TeamMember>> = HashMap::new();
+        for row in &member_rows {
+            let key: (i32, i32) = (row.get("team_id"), row.get("slot"));
+            members.entry(key.0).or_default().push(TeamMember {
+                pokedex_number: row.get("pokedex_number"),
+                species: row.get("species"),
+                form: row.get("form"),
+                nickname: row.get("nickname"),
+                gender: row.get("gender"),
+                item: row.get("item"),
+                ability: row.get("ability"),
+                nature: row.get("nature"),
+                level: row.get("level"),
+                shiny: row.get("shiny"),
+                evs: stat_spread(row, "ev"),
+                ivs: stat_spread(row, "iv"),
+                moves: moves.remove(&key).unwrap_or_default(),
+            });
+        }
+
+        Ok(team_rows
+            .iter()
+            .map(|row| Team {
+                name: row.get("name"),
+                game: row.get("game"),
+                members: members.remove(&row.get("id")).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    async fn delete_team(&self, user_id: i32, name: &str) -> Result<bool, Error> {
+        let client = self.pool.get().await?;
+        let deleted = client
+            .execute(
+                "DELETE FROM teams WHERE user_id = $1 AND name = $2",
+                &[&user_id, &name],
+            )
+            .await?;
+
+        Ok(deleted > 0)
+    }
+
+    async fn save_annotation(&self, user_id: i32, annotation: &Annotation) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        let number = annotation.pokedex_number;
+        for table in ["user_favorites", "user_tags", "user_notes"] {
+            tx.execute(
+                &format!("DELETE FROM {table} WHERE user_id = $1 AND pokedex_number = $2"),
+                &[&user_id, &number],
+            )
+            .await?;
+        }
+
+        if annotation.favorite {
+            tx.execute(
+                "INSERT INTO user_favorites (user_id, pokedex_number) VALUES ($1, $2)",
+                &[&user_id, &number],
+            )
+            .await?;
+        }
+        for tag in &annotation.tags {
+            tx.execute(
+                "INSERT INTO user_tags (user_id, pokedex_number, tag) VALUES ($1, $2, $3)
+                ON CONFLICT DO NOTHING",
+                &[&user_id, &number, tag],
+            )
+            .await?;
+        }
+        if let Some(note) = &annotation.note {
+            tx.execute(
+                "INSERT INTO user_notes (user_id, pokedex_number, note, updated_at)
+                VALUES ($1, $2, $3, $4)",
+                &[&user_id, &number, &note.text, &note.updated_at],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn list_annotations(&self, user_id: i32) -> Result<Vec<Annotation>, Error> {
+        let client = self.pool.get().await?;
+        let favorites = client
+            .query(
+                "SELECT pokedex_number FROM user_favorites WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?
+            .iter()
+            .map(|row| row.get("pokedex_number"))
+            .collect();
+        let tags = client
+            .query(
+                "SELECT pokedex_number, tag FROM user_tags WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?
+            .iter()
+            .map(|row| (row.get("pokedex_number"), row.get("tag")))
+            .collect();
+        let notes = client
+            .query(
+                "SELECT pokedex_number, note, updated_at FROM user_notes WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?
+            .iter()
+            .map(|row| {
+                let note = Note {
+                    text: row.get("note"),
+                    updated_at: row.get("updated_at"),
+                };
+                (row.get("pokedex_number"), note)
+            })
+            .collect();
+
+        Ok(group_annotations(favorites, tags, notes))
+    }
+
+    async fn list_raw_documents(&self) -> Result<Vec<RawDocument>, Error> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT url, fetched_at, content_hash, body FROM raw_documents
+                ORDER BY fetched_at, id",
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| RawDocument {
+                url: row.get("url"),
+                fetched_at: row.get("fetched_at"),
+                content_hash: row.get("content_hash"),
+                body: row.get("body"),
+            })
+            .collect())
+    }
+
+    async fn clear_tables(&self) -> Result<(), Error> {
+        let client = self.pool.get().await?;
+        // RESTART IDENTITY so the ids of the import start again from 1
+        client
+            .batch_execute(&format!(
+                "TRUNCATE {} RESTART IDENTITY CASCADE",
+                TABLES.join(", ")
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn has_data(&self) -> Result<bool, Error> {
+        let client = self.pool.get().await?;
+        let row = client.query_one(&has_data_sql(), &[]).await?;
+
+        Ok(row.get(0))
+    }
+
+    async fn upsert_form(&self, form: &PokemonForm) -> Result<(), Error> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+        // The abilities and moves are deleted with the old form
+        tx.execute(
+            "DELETE FROM pokemon_forms WHERE pokedex_number = $1 AND form = $2",
+            &[&form.pokedex_number, &form.form],
+        )
+        .await?;
+        tx.execute(
+            "INSERT INTO pokemon_forms (pokedex_number, form) VALUES ($1, $2)",
+            &[&form.pokedex_number, &form.form],
+        )
+        .await?;
+        for ability in &form.abilities {
+            tx.execute(
+                "INSERT INTO pokemon_form_abilities (pokedex_number, form, ability, is_hidden)
+                VALUES ($1, $2, $3, $4)",
+                &[
+                    &form.pokedex_number,
+                    &form.form,
+                    &ability.name,
+                    &ability.is_hidden,
+                ],
+            )
+            .await?;
+        }
+        for learned in &form.moves {
+            tx.execute(
+                "INSERT INTO pokemon_form_moves
+                (pokedex_number, form, move_name, version_group, method, level)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+                &[
+                    &form.pokedex_number,
+                    &form.form,
+                    &learned.move_name,
+                    &learned.version_group,
+                    &learned.method,
+                    &learned.level,
+                ],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_forms(&self, pokedex_number: i32) -> Result<Vec<PokemonForm>, Error> {
+        let client = self.pool.get().await?;
+
+        Ok(load_forms(&**client, Some(pokedex_number)).await?)
+    }
+
+    async fn list_forms(&self) -> Result<Vec<PokemonForm>, Error> {
+        let client = self.pool.get().await?;
+
+        Ok(load_forms(&**client, None).await?)
+    }
+}
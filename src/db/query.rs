@@ -0,0 +1,742 @@
+use crate::db::annotations::AnnotationFilter;
+use crate::db::games::Game;
+use crate::db::pokemon_tables::{AbilityRecord, PokemonHistory, PokemonRecord};
+use std::str::FromStr;
+
+// First and last national pokedex number of each generation
+pub const GENERATION_RANGES: [(i32, i32); 9] = [
+    (1, 151),
+    (152, 251),
+    (252, 386),
+    (387, 493),
+    (494, 649),
+    (650, 721),
+    (722, 809),
+    (810, 905),
+    (906, 1025),
+];
+
+// The generation a pokemon was introduced in, by its national pokedex number
+pub fn generation_of(pokedex_number: i32) -> Option<i32> {
+    GENERATION_RANGES
+        .iter()
+        .position(|&(first, last)| (first..=last).contains(&pokedex_number))
+        .map(|index| index as i32 + 1)
+}
+
+/* The generation of a row of 'pokemon p' in SQL. The one of its species is the authoritative one,
+the ranges of pokedex numbers are for the pokemon whose species wasn't fetched yet*/
+pub fn generation_sql() -> String {
+    let cases: Vec<String> = GENERATION_RANGES
+        .iter()
+        .enumerate()
+        .map(|(index, (first, last))| {
+            format!(
+                "WHEN p.pokedex_number BETWEEN {first} AND {last} THEN {}",
+                index + 1
+            )
+        })
+        .collect();
+
+    format!(
+        "COALESCE((SELECT s.generation FROM species s WHERE s.pokedex_number = p.pokedex_number), \
+        CASE {} END)",
+        cases.join(" ")
+    )
+}
+
+// The base stats of a pokemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub special_attack: i32,
+    pub special_defense: i32,
+    pub speed: i32,
+}
+
+impl Stats {
+    // Base stat total
+    pub fn total(&self) -> i32 {
+        self.hp
+            + self.attack
+            + self.defense
+            + self.special_attack
+            + self.special_defense
+            + self.speed
+    }
+
+    pub fn get(&self, stat: Stat) -> i32 {
+        match stat {
+            Stat::Hp => self.hp,
+            Stat::Attack => self.attack,
+            Stat::Defense => self.defense,
+            Stat::SpecialAttack => self.special_attack,
+            Stat::SpecialDefense => self.special_defense,
+            Stat::Speed => self.speed,
+            Stat::Total => self.total(),
+        }
+    }
+
+    // The stats in an older generation, the ones that didn't change are the current ones
+    pub fn in_generation(&self, history: &PokemonHistory, generation: i32) -> Stats {
+        let stat =
+            |stat: Stat, current: i32| history.stat_in(stat.name(), generation).unwrap_or(current);
+
+        Stats {
+            hp: stat(Stat::Hp, self.hp),
+            attack: stat(Stat::Attack, self.attack),
+            defense: stat(Stat::Defense, self.defense),
+            special_attack: stat(Stat::SpecialAttack, self.special_attack),
+            special_defense: stat(Stat::SpecialDefense, self.special_defense),
+            speed: stat(Stat::Speed, self.speed),
+        }
+    }
+}
+
+// A pokemon read from the database, with its types and abilities
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pokemon {
+    pub id: i32,
+    pub pokedex_number: i32,
+    // The slug of the API, e.g. "mr-mime"
+    pub name: String,
+    pub generation: Option<i32>,
+    // The number in the regional pokedex of the search, when it has one
+    pub regional_number: Option<i32>,
+    // Meters
+    pub height: f64,
+    // Kilograms
+    pub weight: f64,
+    pub stats: Stats,
+    // Types and abilities are sorted by name
+    pub types: Vec<String>,
+    pub abilities: Vec<AbilityRecord>,
+}
+
+impl Pokemon {
+    /* The pokemon of a record, with the types and abilities sorted by name. The generation is the
+    one of its species when the store has it*/
+    pub fn from_record(id: i32, record: &PokemonRecord, generation: Option<i32>) -> Self {
+        let mut types = record.types.clone();
+        types.sort_unstable();
+        let mut abilities = record.abilities.clone();
+        abilities.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Pokemon {
+            id,
+            pokedex_number: record.pokedex_number,
+            name: record.name.clone(),
+            generation: generation.or_else(|| generation_of(record.pokedex_number)),
+            regional_number: None,
+            height: record.height.parse().unwrap_or_default(),
+            weight: record.weight.parse().unwrap_or_default(),
+            stats: Stats {
+                hp: record.hp,
+                attack: record.attack,
+                defense: record.defense,
+                special_attack: record.special_attack,
+                special_defense: record.special_defense,
+                speed: record.speed,
+            },
+            types,
+            abilities,
+        }
+    }
+
+    // The pokemon like it was in an older generation, with the types and stats it had then
+    pub fn in_generation(&self, history: &PokemonHistory, generation: i32) -> Pokemon {
+        let mut pokemon = self.clone();
+        if let Some(types) = history.types_in(generation) {
+            pokemon.types = types.clone();
+            pokemon.types.sort_unstable();
+        }
+        pokemon.stats = self.stats.in_generation(history, generation);
+
+        pokemon
+    }
+
+    // Back to the record the stores save, height and weight have the format of the API parsing
+    pub fn to_record(&self) -> PokemonRecord {
+        PokemonRecord {
+            pokedex_number: self.pokedex_number,
+            name: self.name.clone(),
+            height: format!("{:.2}", self.height),
+            weight: format!("{:.2}", self.weight),
+            hp: self.stats.hp,
+            attack: self.stats.attack,
+            defense: self.stats.defense,
+            special_attack: self.stats.special_attack,
+            special_defense: self.stats.special_defense,
+            speed: self.stats.speed,
+            types: self.types.clone(),
+            abilities: self.abilities.clone(),
+        }
+    }
+}
+
+// The stats that can be filtered and sorted, Total is the base stat total
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stat {
+    Hp,
+    Attack,
+    Defense,
+    SpecialAttack,
+    SpecialDefense,
+    Speed,
+    Total,
+}
+
+impl Stat {
+    pub const ALL: [Stat; 7] = [
+        Stat::Hp,
+        Stat::Attack,
+        Stat::Defense,
+        Stat::SpecialAttack,
+        Stat::SpecialDefense,
+        Stat::Speed,
+        Stat::Total,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stat::Hp => "hp",
+            Stat::Attack => "attack",
+            Stat::Defense => "defense",
+            Stat::SpecialAttack => "special_attack",
+            Stat::SpecialDefense => "special_defense",
+            Stat::Speed => "speed",
+            Stat::Total => "total",
+        }
+    }
+
+    // The column in the SQL tables, the total is calculated from the six columns
+    pub fn sql_expression(&self) -> &'static str {
+        match self {
+            Stat::Hp => "p.hp",
+            Stat::Attack => "p.attack",
+            Stat::Defense => "p.defense",
+            Stat::SpecialAttack => "p.special_attack",
+            Stat::SpecialDefense => "p.special_defense",
+            Stat::Speed => "p.speed",
+            Stat::Total => {
+                "(p.hp + p.attack + p.defense + p.special_attack + p.special_defense + p.speed)"
+            }
+        }
+    }
+}
+
+impl FromStr for Stat {
+    type Err = String;
+
+    fn from_str(stat: &str) -> Result<Self, Self::Err> {
+        match stat.replace('-', "_").as_str() {
+            "hp" => Ok(Stat::Hp),
+            "attack" => Ok(Stat::Attack),
+            "defense" => Ok(Stat::Defense),
+            "special_attack" => Ok(Stat::SpecialAttack),
+            "special_defense" => Ok(Stat::SpecialDefense),
+            "speed" => Ok(Stat::Speed),
+            "total" => Ok(Stat::Total),
+            _ => Err(format!("Unknown stat '{stat}'")),
+        }
+    }
+}
+
+// Minimum and maximum (both included) of a stat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatRange {
+    pub stat: Stat,
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+}
+
+// Generations from the first to the last one, both included. "1-3" or "2" from the CLI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerationRange {
+    pub from: i32,
+    pub to: i32,
+}
+
+impl GenerationRange {
+    pub fn contains(&self, generation: i32) -> bool {
+        (self.from..=self.to).contains(&generation)
+    }
+}
+
+impl std::fmt::Display for GenerationRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.from == self.to {
+            write!(f, "{}", self.from)
+        } else {
+            write!(f, "{}-{}", self.from, self.to)
+        }
+    }
+}
+
+impl FromStr for GenerationRange {
+    type Err = String;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let parse = |generation: &str| {
+            generation
+                .trim()
+                .parse::<i32>()
+                .ok()
+                .filter(|g| (1..=GENERATION_RANGES.len() as i32).contains(g))
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid generation '{generation}', it must be from 1 to {}",
+                        GENERATION_RANGES.len()
+                    )
+                })
+        };
+        let (from, to) = match range.split_once('-') {
+            Some((from, to)) => (parse(from)?, parse(to)?),
+            None => (parse(range)?, parse(range)?),
+        };
+        if from > to {
+            return Err(format!("Invalid generation range '{range}'"));
+        }
+
+        Ok(GenerationRange { from, to })
+    }
+}
+
+// How the results of a search are sorted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortField {
+    #[default]
+    PokedexNumber,
+    Name,
+    Height,
+    Weight,
+    Stat(Stat),
+    // The number in the regional pokedex of the search
+    RegionalNumber,
+}
+
+impl SortField {
+    fn sql_expression(&self) -> &'static str {
+        match self {
+            SortField::PokedexNumber => "p.pokedex_number",
+            SortField::Name => "p.name",
+            // Height and weight are saved as text
+            SortField::Height => "CAST(p.height AS NUMERIC)",
+            SortField::Weight => "CAST(p.weight AS NUMERIC)",
+            SortField::Stat(stat) => stat.sql_expression(),
+            // Without a regional pokedex it's the national number
+            SortField::RegionalNumber => "p.pokedex_number",
+        }
+    }
+}
+
+impl FromStr for SortField {
+    type Err = String;
+
+    fn from_str(field: &str) -> Result<Self, Self::Err> {
+        match field {
+            "number" | "pokedex_number" => Ok(SortField::PokedexNumber),
+            "name" => Ok(SortField::Name),
+            "height" => Ok(SortField::Height),
+            "weight" => Ok(SortField::Weight),
+            "regional" | "regional_number" => Ok(SortField::RegionalNumber),
+            _ => field.parse().map(SortField::Stat),
+        }
+    }
+}
+
+/* The filters of a search. Every filter that is set must match, e.g. two types means pokemon with
+both types. The empty search returns every pokemon by pokedex number*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PokemonSearch {
+    // Exact slug of the API
+    pub name: Option<String>,
+    pub pokedex_number: Option<i32>,
+    pub types: Vec<String>,
+    pub ability: Option<String>,
+    pub generation: Option<GenerationRange>,
+    // Name of a pokedex of the API, e.g. "galar". Only its pokemon, with their regional number
+    pub regional_dex: Option<String>,
+    /* The pokemon like they were in a generation: only the ones that existed, with the types and
+    stats they had then*/
+    pub as_of_generation: Option<i32>,
+    pub stats: Vec<StatRange>,
+    // Only the pokemon a user annotated, e.g. their favorites or the ones with a tag
+    pub annotations: Option<AnnotationFilter>,
+    pub sort_by: SortField,
+    pub descending: bool,
+    pub limit: Option<i64>,
+    pub offset: i64,
+}
+
+// A page of the results and how many pokemon match the search in total
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchResults {
+    pub total: i64,
+    pub pokemon: Vec<Pokemon>,
+}
+
+impl PokemonSearch {
+    /* The search in the target game: without a generation, only the pokemon that exist in it with
+    the types and stats they have in it, and sorting by regional number uses its main pokedex when the search doesn't have one*/
+    pub fn for_game(mut self, game: Option<&Game>) -> Self {
+        let Some(game) = game else {
+            return self;
+        };
+        if self.generation.is_none() {
+            self.generation = game.generations();
+        }
+        if self.as_of_generation.is_none() {
+            self.as_of_generation = game.generation();
+        }
+        if self.sort_by == SortField::RegionalNumber && self.regional_dex.is_none() {
+            self.regional_dex = game.pokedex().map(str::to_string);
+        }
+
+        self
+    }
+
+    /* Used by the backends without SQL. With a regional pokedex, the backend sets the regional
+    numbers before, and with annotations it leaves only the annotated pokemon*/
+    pub fn matches(&self, pokemon: &Pokemon) -> bool {
+        self.name.as_ref().is_none_or(|name| &pokemon.name == name)
+            && self
+                .pokedex_number
+                .is_none_or(|number| pokemon.pokedex_number == number)
+            && self.types.iter().all(|t| pokemon.types.contains(t))
+            && self
+                .ability
+                .as_ref()
+                .is_none_or(|name| pokemon.abilities.iter().any(|a| &a.name == name))
+            && self.generation.is_none_or(|range| {
+                pokemon
+                    .generation
+                    .is_some_and(|generation| range.contains(generation))
+            })
+            && (self.regional_dex.is_none() || pokemon.regional_number.is_some())
+            && self.stats.iter().all(|range| {
+                let value = pokemon.stats.get(range.stat);
+                range.min.is_none_or(|min| value >= min) && range.max.is_none_or(|max| value <= max)
+            })
+    }
+
+    // Filtering, sorting and paginating the pokemon like the SQL of the search does
+    pub fn apply(&self, mut pokemon: Vec<Pokemon>) -> SearchResults {
+        pokemon.retain(|p| self.matches(p));
+        pokemon.sort_by(|a, b| {
+            let order = match self.sort_by {
+                SortField::PokedexNumber => a.pokedex_number.cmp(&b.pokedex_number),
+                SortField::Name => a.name.cmp(&b.name),
+                SortField::Height => a.height.total_cmp(&b.height),
+                SortField::Weight => a.weight.total_cmp(&b.weight),
+                SortField::Stat(stat) => a.stats.get(stat).cmp(&b.stats.get(stat)),
+                SortField::RegionalNumber => a
+                    .regional_number
+                    .unwrap_or(a.pokedex_number)
+                    .cmp(&b.regional_number.unwrap_or(b.pokedex_number)),
+            };
+            let order = if self.descending {
+                order.reverse()
+            } else {
+                order
+            };
+            // Ties are always by pokedex number, so the pages don't overlap
+            order.then(a.pokedex_number.cmp(&b.pokedex_number))
+        });
+
+        let total = pokemon.len() as i64;
+        let pokemon = pokemon
+            .into_iter()
+            .skip(self.offset.max(0) as usize)
+            .take(self.limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+            .collect();
+
+        SearchResults { total, pokemon }
+    }
+
+    /* The WHERE, ORDER BY and LIMIT of the search for the SQL backends, the values go in the
+    parameters so nothing of the search is written in the SQL*/
+    pub fn to_sql(&self, dialect: SqlDialect) -> SearchSql {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        let mut bind = |param: SqlParam| {
+            params.push(param);
+            dialect.placeholder(params.len())
+        };
+
+        if let Some(name) = &self.name {
+            conditions.push(format!("p.name = {}", bind(SqlParam::Text(name.clone()))));
+        }
+        if let Some(number) = self.pokedex_number {
+            conditions.push(format!(
+                "p.pokedex_number = {}",
+                bind(SqlParam::Int(number))
+            ));
+        }
+        for type_name in &self.types {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM pokemon_types pt JOIN types t ON pt.type_id = t.id
+                WHERE pt.pokemon_id = p.id AND t.name = {})",
+                bind(SqlParam::Text(type_name.clone()))
+            ));
+        }
+        if let Some(ability) = &self.ability {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM pokemon_abilities pa JOIN abilities a ON pa.ability_id = a.id
+                WHERE pa.pokemon_id = p.id AND a.name = {})",
+                bind(SqlParam::Text(ability.clone()))
+            ));
+        }
+        if let Some(range) = self.generation {
+            conditions.push(format!(
+                "{} BETWEEN {} AND {}",
+                generation_sql(),
+                bind(SqlParam::Int(range.from)),
+                bind(SqlParam::Int(range.to))
+            ));
+        }
+        // The placeholder of the pokedex is used again in the columns and the order
+        let regional_number = match &self.regional_dex {
+            Some(dex) => {
+                let number = format!(
+                    "(SELECT MIN(e.entry_number) FROM regional_dex_entries e
+                    WHERE e.pokedex_name = {} AND e.pokedex_number = p.pokedex_number)",
+                    bind(SqlParam::Text(dex.clone()))
+                );
+                conditions.push(format!("{number} IS NOT NULL"));
+                number
+            }
+            None => "CAST(NULL AS INTEGER)".to_string(),
+        };
+        if let Some(filter) = &self.annotations {
+            conditions.push(annotations_sql(filter, &mut bind));
+        }
+        for range in &self.stats {
+            if let Some(min) = range.min {
+                conditions.push(format!(
+                    "{} >= {}",
+                    range.stat.sql_expression(),
+                    bind(SqlParam::Int(min))
+                ));
+            }
+            if let Some(max) = range.max {
+                conditions.push(format!(
+                    "{} <= {}",
+                    range.stat.sql_expression(),
+                    bind(SqlParam::Int(max))
+                ));
+            }
+        }
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let sort_expression = match self.sort_by {
+            SortField::RegionalNumber if self.regional_dex.is_some() => regional_number.clone(),
+            sort_by => sort_by.sql_expression().to_string(),
+        };
+        let order_by = format!(
+            "ORDER BY {sort_expression} {}, p.pokedex_number",
+            if self.descending { "DESC" } else { "ASC" }
+        );
+        // The page goes after the filters, so the count of the results can use only the filters
+        let limit = format!(
+            "LIMIT {} OFFSET {}",
+            dialect.placeholder(params.len() + 1),
+            dialect.placeholder(params.len() + 2)
+        );
+
+        SearchSql {
+            where_clause,
+            regional_number,
+            order_by,
+            limit,
+            params,
+            // SQLite doesn't accept OFFSET without LIMIT, so "no limit" is the biggest one
+            page: [self.limit.unwrap_or(i64::MAX).max(0), self.offset.max(0)],
+        }
+    }
+}
+
+/* The condition of the annotations of a user, the tables have the pokedex number so they don't
+change when the pokemon are fetched again*/
+fn annotations_sql(filter: &AnnotationFilter, bind: &mut impl FnMut(SqlParam) -> String) -> String {
+    let annotated = |table: &str, bind: &mut dyn FnMut(SqlParam) -> String| {
+        format!(
+            "EXISTS (SELECT 1 FROM {table} a WHERE a.user_id = {} AND a.pokedex_number = p.pokedex_number",
+            bind(SqlParam::Int(filter.user_id))
+        )
+    };
+
+    let mut conditions = Vec::new();
+    if filter.favorites {
+        conditions.push(format!("{})", annotated("user_favorites", bind)));
+    }
+    for tag in &filter.tags {
+        conditions.push(format!(
+            "{} AND a.tag = {})",
+            annotated("user_tags", bind),
+            bind(SqlParam::Text(tag.clone()))
+        ));
+    }
+    if let Some(pattern) = filter.note_pattern() {
+        conditions.push(format!(
+            "{} AND LOWER(a.note) LIKE {} ESCAPE '\\')",
+            annotated("user_notes", bind),
+            bind(SqlParam::Text(pattern))
+        ));
+    }
+    if conditions.is_empty() {
+        let any: Vec<String> = ["user_favorites", "user_tags", "user_notes"]
+            .iter()
+            .map(|table| format!("{})", annotated(table, bind)))
+            .collect();
+        return format!("({})", any.join(" OR "));
+    }
+
+    conditions.join(" AND ")
+}
+
+// The SQL of the backends only changes in how the parameters are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlDialect {
+    Postgres,
+    Sqlite,
+}
+
+impl SqlDialect {
+    pub fn placeholder(&self, index: usize) -> String {
+        match self {
+            SqlDialect::Postgres => format!("${index}"),
+            SqlDialect::Sqlite => format!("?{index}"),
+        }
+    }
+}
+
+// A value for a placeholder of the filters
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlParam {
+    Int(i32),
+    Text(String),
+}
+
+/* The pieces of the search query. The parameters of the filters are in the order of their
+placeholders, and the LIMIT and OFFSET of the page go after them*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchSql {
+    pub where_clause: String,
+    // The column of the regional number, NULL without a regional pokedex
+    pub regional_number: String,
+    pub order_by: String,
+    pub limit: String,
+    pub params: Vec<SqlParam>,
+    pub page: [i64; 2],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_empty_search_has_every_pokemon() {
+        let sql = PokemonSearch::default().to_sql(SqlDialect::Postgres);
+
+        assert_eq!(sql.where_clause, "");
+        assert_eq!(
+            sql.order_by,
+            "ORDER BY p.pokedex_number ASC, p.pokedex_number"
+        );
+        assert_eq!(sql.limit, "LIMIT $1 OFFSET $2");
+        assert!(sql.params.is_empty());
+        assert_eq!(sql.page, [i64::MAX, 0]);
+    }
+
+    #[test]
+    fn the_parameters_are_in_the_order_of_their_placeholders() {
+        let search = PokemonSearch {
+            name: Some("pikachu".to_string()),
+            types: vec!["electric".to_string()],
+            generation: Some(GenerationRange { from: 1, to: 3 }),
+            stats: vec![StatRange {
+                stat: Stat::Speed,
+                min: Some(90),
+                max: None,
+            }],
+            sort_by: SortField::Stat(Stat::Speed),
+            descending: true,
+            limit: Some(20),
+            offset: 40,
+            ..PokemonSearch::default()
+        };
+        let sql = search.to_sql(SqlDialect::Sqlite);
+
+        assert!(sql.where_clause.starts_with("WHERE p.name = ?1 AND EXISTS"));
+        assert!(sql.where_clause.contains("t.name = ?2"));
+        assert!(sql.where_clause.contains("BETWEEN ?3 AND ?4"));
+        assert!(sql.where_clause.ends_with(">= ?5"));
+        assert_eq!(
+            sql.params,
+            [
+                SqlParam::Text("pikachu".to_string()),
+                SqlParam::Text("electric".to_string()),
+                SqlParam::Int(1),
+                SqlParam::Int(3),
+                SqlParam::Int(90),
+            ]
+        );
+        assert!(sql.order_by.ends_with(" DESC, p.pokedex_number"));
+        assert_eq!(sql.limit, "LIMIT ?6 OFFSET ?7");
+        assert_eq!(sql.page, [20, 40]);
+    }
+
+    #[test]
+    fn parses_the_generations_and_the_sort_fields() {
+        assert_eq!("2".parse(), Ok(GenerationRange { from: 2, to: 2 }));
+        assert_eq!("1-3".parse(), Ok(GenerationRange { from: 1, to: 3 }));
+        assert!("3-1".parse::<GenerationRange>().is_err());
+        assert!("10".parse::<GenerationRange>().is_err());
+        assert_eq!("speed".parse(), Ok(SortField::Stat(Stat::Speed)));
+        assert!("color".parse::<SortField>().is_err());
+        assert_eq!(generation_of(151), Some(1));
+        assert_eq!(generation_of(1025), Some(9));
+        assert_eq!(generation_of(10001), None);
+    }
+
+    #[test]
+    fn sorts_by_the_number_of_the_regional_dex() {
+        let search = PokemonSearch {
+            regional_dex: Some("galar".to_string()),
+            sort_by: SortField::RegionalNumber,
+            ..PokemonSearch::default()
+        };
+        let sql = search.to_sql(SqlDialect::Postgres);
+
+        assert!(sql.regional_number.contains("e.pokedex_name = $1"));
+        assert_eq!(
+            sql.where_clause,
+            format!("WHERE {} IS NOT NULL", sql.regional_number)
+        );
+        assert_eq!(
+            sql.order_by,
+            format!("ORDER BY {} ASC, p.pokedex_number", sql.regional_number)
+        );
+        assert_eq!(sql.params, [SqlParam::Text("galar".to_string())]);
+        assert_eq!(sql.limit, "LIMIT $2 OFFSET $3");
+
+        // Without a regional pokedex it's the national number
+        let national = PokemonSearch {
+            sort_by: "regional".parse().unwrap(),
+            ..PokemonSearch::default()
+        }
+        .to_sql(SqlDialect::Postgres);
+        assert_eq!(national.regional_number, "CAST(NULL AS INTEGER)");
+        assert_eq!(
+            national.order_by,
+            "ORDER BY p.pokedex_number ASC, p.pokedex_number"
+        );
+    }
+}
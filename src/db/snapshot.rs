@@ -0,0 +1,719 @@
+use crate::db::collection::UserCollection;
+use crate::db::pokemon_tables::{
+    GameVersion, IngestionRun, Learnset, LocalizedName, PokemonChange, PokemonForm, PokemonHistory,
+    PokemonName, PokemonRecord, RawDocument, RegionalDex, SpeciesRecord, VersionGroup,
+};
+use crate::db::query::PokemonSearch;
+use crate::db::store::PokedexStore;
+use crate::error::Error;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// Identifies the archives of the pokedex
+pub const SNAPSHOT_FORMAT: &str = "pokedb-snapshot";
+/* The version of the files inside the archive. It changes when a file or a field is added, the
+import refuses archives of a newer version. Version 2 has the species with their generation instead
+of only the names, version 3 the regional pokedexes, version 4 the games, version 5 the past
+types and stats, version 6 the users with their collections, version 7 the learnsets and the teams
+of the users, version 8 their favorites, tags and notes and version 9 the forms of the species*/
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 9;
+
+// The files of the archive, one JSON object per line
+const POKEMON_FILE: &str = "pokemon.jsonl";
+const SPECIES_FILE: &str = "species.jsonl";
+const HISTORY_FILE: &str = "pokemon_history.jsonl";
+const LEARNSETS_FILE: &str = "learnsets.jsonl";
+const FORMS_FILE: &str = "pokemon_forms.jsonl";
+// Only in the archives of version 1
+const NAMES_FILE: &str = "pokemon_names.jsonl";
+const REGIONAL_DEXES_FILE: &str = "regional_dexes.jsonl";
+const VERSION_GROUPS_FILE: &str = "version_groups.jsonl";
+const VERSIONS_FILE: &str = "versions.jsonl";
+const RAW_DOCUMENTS_FILE: &str = "raw_documents.jsonl";
+const RUNS_FILE: &str = "ingestion_runs.jsonl";
+const CHANGES_FILE: &str = "pokemon_changes.jsonl";
+const USERS_FILE: &str = "users.jsonl";
+const MANIFEST_FILE: &str = "manifest.json";
+
+// 'manifest.json', the first file of the archive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub format: String,
+    pub schema_version: u32,
+    pub pokedb_version: String,
+    pub created_at: DateTime<Utc>,
+    // The rows of each file, to check nothing was lost
+    pub row_counts: BTreeMap<String, usize>,
+}
+
+fn to_json_lines<T: Serialize>(rows: &[T]) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut bytes, row)?;
+        bytes.push(b'\n');
+    }
+
+    Ok(bytes)
+}
+
+fn from_json_lines<T: DeserializeOwned>(file: &str, bytes: &[u8]) -> Result<Vec<T>, Error> {
+    let text = std::str::from_utf8(bytes).map_err(|e| Error::Decode {
+        path: file.to_string(),
+        message: format!("Not UTF-8: {e}"),
+    })?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| Error::Decode {
+                path: format!("{file}, line {}", index + 1),
+                message: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+/* Writing every table of the store in a .tar.gz: the pokemon with their types and abilities, the
+species with their generation, names and forms, the learnsets, the regional pokedexes, the
+games, every version of the archived documents, the history of the runs and the collections, teams
+and annotations of the users*/
+pub async fn export_snapshot(
+    store: &dyn PokedexStore,
+    path: &Path,
+) -> Result<SnapshotManifest, Error> {
+    let pokemon: Vec<PokemonRecord> = store
+        .search(&PokemonSearch::default())
+        .await?
+        .pokemon
+        .iter()
+        .map(|p| p.to_record())
+        .collect();
+    let species = store.list_species().await?;
+    let history = store.list_pokemon_history().await?;
+    let learnsets = store.list_learnsets().await?;
+    let forms = store.list_forms().await?;
+    let regional_dexes = store.list_regional_dexes().await?;
+    let version_groups = store.list_version_groups().await?;
+    let versions = store.list_versions().await?;
+    let raw_documents = store.list_raw_documents().await?;
+    // The first run first, like they were inserted
+    let mut runs = store.list_ingestion_runs().await?;
+    runs.reverse();
+    let changes = store.changes_between_runs(0, i32::MAX).await?;
+    let mut users = Vec::new();
+    for user in store.list_users().await? {
+        users.push(UserCollection {
+            pokemon: store.list_caught(user.id).await?,
+            teams: store.list_teams(user.id).await?,
+            annotations: store.list_annotations(user.id).await?,
+            name: user.name,
+        });
+    }
+
+    let files = [
+        (POKEMON_FILE, pokemon.len(), to_json_lines(&pokemon)?),
+        (SPECIES_FILE, species.len(), to_json_lines(&species)?),
+        (HISTORY_FILE, history.len(), to_json_lines(&history)?),
+        (LEARNSETS_FILE, learnsets.len(), to_json_lines(&learnsets)?),
+        (FORMS_FILE, forms.len(), to_json_lines(&forms)?),
+        (
+            REGIONAL_DEXES_FILE,
+            regional_dexes.len(),
+            to_json_lines(&regional_dexes)?,
+        ),
+        (
+            VERSION_GROUPS_FILE,
+            version_groups.len(),
+            to_json_lines(&version_groups)?,
+        ),
+        (VERSIONS_FILE, versions.len(), to_json_lines(&versions)?),
+        (
+            RAW_DOCUMENTS_FILE,
+            raw_documents.len(),
+            to_json_lines(&raw_documents)?,
+        ),
+        (RUNS_FILE, runs.len(), to_json_lines(&runs)?),
+        (CHANGES_FILE, changes.len(), to_json_lines(&changes)?),
+        (USERS_FILE, users.len(), to_json_lines(&users)?),
+    ];
+    let manifest = SnapshotManifest {
+        format: SNAPSHOT_FORMAT.to_string(),
+        schema_version: SNAPSHOT_SCHEMA_VERSION,
+        pokedb_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now(),
+        row_counts: files
+            .iter()
+            .map(|(file, rows, _)| (file.to_string(), *rows))
+            .collect(),
+    };
+
+    let archive = File::create(path).map_err(|e| Error::file(e, "create the snapshot", path))?;
+    let mut tar = tar::Builder::new(GzEncoder::new(archive, Compression::default()));
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
+    for (file, bytes) in std::iter::once((MANIFEST_FILE, &manifest_bytes))
+        .chain(files.iter().map(|(f, _, b)| (*f, b)))
+    {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(manifest.created_at.timestamp() as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, file, bytes.as_slice())?;
+    }
+    tar.into_inner()?.finish()?;
+
+    Ok(manifest)
+}
+
+/* Restoring a snapshot in an empty store, of any backend. The ids of the pokemon are given again
+by the store, the runs and changes keep theirs*/
+pub async fn import_snapshot(
+    store: &dyn PokedexStore,
+    path: &Path,
+) -> Result<SnapshotManifest, Error> {
+    let archive = File::open(path).map_err(|e| Error::file(e, "open the snapshot", path))?;
+    let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+    for entry in tar::Archive::new(GzDecoder::new(archive)).entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        files.insert(name, bytes);
+    }
+
+    let file = |name: &str| {
+        files
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| Error::Decode {
+                path: name.to_string(),
+                message: "The snapshot doesn't have it".to_string(),
+            })
+    };
+    let manifest: SnapshotManifest =
+        serde_json::from_slice(file(MANIFEST_FILE)?).map_err(|e| Error::Decode {
+            path: MANIFEST_FILE.to_string(),
+            message: e.to_string(),
+        })?;
+    if manifest.format != SNAPSHOT_FORMAT {
+        return Err(Error::Decode {
+            path: MANIFEST_FILE.to_string(),
+            message: format!("{} isn't a pokedb snapshot", path.display()),
+        });
+    }
+    if manifest.schema_version > SNAPSHOT_SCHEMA_VERSION {
+        return Err(Error::Decode {
+            path: MANIFEST_FILE.to_string(),
+            message: format!(
+                "The snapshot has schema version {}, this pokedb reads up to {}. Update pokedb",
+                manifest.schema_version, SNAPSHOT_SCHEMA_VERSION
+            ),
+        });
+    }
+
+    let pokemon: Vec<PokemonRecord> = from_json_lines(POKEMON_FILE, file(POKEMON_FILE)?)?;
+    let (species_file, species) = if manifest.schema_version >= 2 {
+        let species: Vec<SpeciesRecord> = from_json_lines(SPECIES_FILE, file(SPECIES_FILE)?)?;
+        (SPECIES_FILE, species)
+    } else {
+        let names: Vec<PokemonName> = from_json_lines(NAMES_FILE, file(NAMES_FILE)?)?;
+        (NAMES_FILE, species_of_names(names))
+    };
+    let history: Vec<PokemonHistory> = if manifest.schema_version >= 5 {
+        from_json_lines(HISTORY_FILE, file(HISTORY_FILE)?)?
+    } else {
+        Vec::new()
+    };
+    let learnsets: Vec<Learnset> = if manifest.schema_version >= 7 {
+        from_json_lines(LEARNSETS_FILE, file(LEARNSETS_FILE)?)?
+    } else {
+        Vec::new()
+    };
+    let forms: Vec<PokemonForm> = if manifest.schema_version >= 9 {
+        from_json_lines(FORMS_FILE, file(FORMS_FILE)?)?
+    } else {
+        Vec::new()
+    };
+    let regional_dexes: Vec<RegionalDex> = if manifest.schema_version >= 3 {
+        from_json_lines(REGIONAL_DEXES_FILE, file(REGIONAL_DEXES_FILE)?)?
+    } else {
+        Vec::new()
+    };
+    let (version_groups, versions): (Vec<VersionGroup>, Vec<GameVersion>) =
+        if manifest.schema_version >= 4 {
+            (
+                from_json_lines(VERSION_GROUPS_FILE, file(VERSION_GROUPS_FILE)?)?,
+                from_json_lines(VERSIONS_FILE, file(VERSIONS_FILE)?)?,
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+    let raw_documents: Vec<RawDocument> =
+        from_json_lines(RAW_DOCUMENTS_FILE, file(RAW_DOCUMENTS_FILE)?)?;
+    let runs: Vec<IngestionRun> = from_json_lines(RUNS_FILE, file(RUNS_FILE)?)?;
+    let changes: Vec<PokemonChange> = from_json_lines(CHANGES_FILE, file(CHANGES_FILE)?)?;
+    let users: Vec<UserCollection> = if manifest.schema_version >= 6 {
+        from_json_lines(USERS_FILE, file(USERS_FILE)?)?
+    } else {
+        Vec::new()
+    };
+
+    let species_rows = if species_file == SPECIES_FILE {
+        species.len()
+    } else {
+        species.iter().map(|record| record.names.len()).sum()
+    };
+    for (name, rows) in [
+        (POKEMON_FILE, pokemon.len()),
+        (species_file, species_rows),
+        (HISTORY_FILE, history.len()),
+        (LEARNSETS_FILE, learnsets.len()),
+        (FORMS_FILE, forms.len()),
+        (REGIONAL_DEXES_FILE, regional_dexes.len()),
+        (VERSION_GROUPS_FILE, version_groups.len()),
+        (VERSIONS_FILE, versions.len()),
+        (RAW_DOCUMENTS_FILE, raw_documents.len()),
+        (RUNS_FILE, runs.len()),
+        (CHANGES_FILE, changes.len()),
+        (USERS_FILE, users.len()),
+    ] {
+        let expected = manifest.row_counts.get(name).copied().unwrap_or_default();
+        if rows != expected {
+            return Err(Error::Decode {
+                path: name.to_string(),
+                message: format!("It has {rows} rows, the manifest says {expected}"),
+            });
+        }
+    }
+
+    /* Mixing the snapshot with other data would duplicate runs and hide what came from where, and
+    when the import fails the tables are emptied, so every table it writes must be empty*/
+    if store.has_data().await? {
+        return Err(Error::Input(
+            "The database isn't empty, the snapshot can only be imported in an empty one"
+                .to_string(),
+        ));
+    }
+
+    /* The backends don't share a transaction, so when a row fails the ones saved before it are
+    deleted. The database was empty, emptying it again lets the import be tried again*/
+    let restored: Result<(), Error> = async {
+        store.reset_types_table().await?;
+        for record in &pokemon {
+            store.upsert_pokemon(record).await?;
+        }
+        for record in &species {
+            store.upsert_species(record).await?;
+        }
+        for record in &history {
+            store.upsert_pokemon_history(record).await?;
+        }
+        for learnset in &learnsets {
+            store.upsert_learnset(learnset).await?;
+        }
+        for form in &forms {
+            store.upsert_form(form).await?;
+        }
+        for dex in &regional_dexes {
+            store.upsert_regional_dex(dex).await?;
+        }
+        for group in &version_groups {
+            store.upsert_version_group(group).await?;
+        }
+        for version in &versions {
+            store.upsert_version(version).await?;
+        }
+
+        for document in &raw_documents {
+            store.archive_document(document).await?;
+        }
+        store.restore_history(&runs, &changes).await?;
+        // The users get new ids, their collections, teams and annotations are saved with them
+        for collection in &users {
+            let user = store.create_user(&collection.name).await?;
+            for pokemon in &collection.pokemon {
+                store.mark_caught(user.id, pokemon).await?;
+            }
+            for team in &collection.teams {
+                store.save_team(user.id, team).await?;
+            }
+            for annotation in &collection.annotations {
+                store.save_annotation(user.id, annotation).await?;
+            }
+        }
+        store.refresh_summaries().await
+    }
+    .await;
+    if let Err(error) = restored {
+        if let Err(clear_error) = store.clear_tables().await {
+            eprintln!("Can't empty the tables after the failed import: {clear_error}");
+        } else {
+            store.refresh_summaries().await?;
+        }
+        return Err(error);
+    }
+
+    Ok(manifest)
+}
+
+/* The archives of version 1 only have the names, grouped here by species. The generation is
+left to the pokedex numbers until the next sync fetches the species*/
+fn species_of_names(names: Vec<PokemonName>) -> Vec<SpeciesRecord> {
+    let mut species: BTreeMap<i32, Vec<LocalizedName>> = BTreeMap::new();
+    for name in names {
+        if let Some(language) = name.language {
+            species
+                .entry(name.pokedex_number)
+                .or_default()
+                .push(LocalizedName {
+                    language,
+                    name: name.name,
+                });
+        }
+    }
+
+    species
+        .into_iter()
+        .map(|(pokedex_number, names)| SpeciesRecord {
+            pokedex_number,
+            generation: None,
+            names,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::annotations::{Annotation, Note};
+    use crate::db::collection::CaughtPokemon;
+    use crate::db::memory_store::MemoryStore;
+    use crate::db::pokemon_tables::AbilityRecord;
+    use crate::db::teams::{parse_showdown, Team};
+
+    // An archive in the temp dir, each test uses its own name
+    fn archive_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "pokedb-snapshot-{}-{name}.tar.gz",
+            std::process::id()
+        ))
+    }
+
+    // An archive with the manifest and the files as they are given, to test the old and broken ones
+    fn write_archive(path: &Path, manifest: &serde_json::Value, files: &[(&str, String)]) {
+        let manifest = serde_json::to_vec(manifest).unwrap();
+        let mut tar = tar::Builder::new(GzEncoder::new(
+            File::create(path).unwrap(),
+            Compression::default(),
+        ));
+        for (file, bytes) in std::iter::once((MANIFEST_FILE, manifest.as_slice()))
+            .chain(files.iter().map(|(file, text)| (*file, text.as_bytes())))
+        {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, file, bytes).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn manifest(schema_version: u32, row_counts: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "format": SNAPSHOT_FORMAT,
+            "schema_version": schema_version,
+            "pokedb_version": "0.1.0",
+            "created_at": "2024-01-01T00:00:00Z",
+            "row_counts": row_counts,
+        })
+    }
+
+    fn pikachu() -> PokemonRecord {
+        PokemonRecord {
+            pokedex_number: 25,
+            name: "pikachu".to_string(),
+            height: "0.40".to_string(),
+            weight: "6.00".to_string(),
+            hp: 35,
+            attack: 55,
+            defense: 40,
+            special_attack: 50,
+            special_defense: 50,
+            speed: 90,
+            types: vec!["electric".to_string()],
+            abilities: vec![AbilityRecord {
+                name: "static".to_string(),
+                is_hidden: false,
+            }],
+        }
+    }
+
+    async fn saved_store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store.reset_types_table().await.unwrap();
+        store.upsert_pokemon(&pikachu()).await.unwrap();
+        store
+            .upsert_species(&SpeciesRecord {
+                pokedex_number: 25,
+                generation: Some(1),
+                names: vec![LocalizedName {
+                    language: "en".to_string(),
+                    name: "Pikachu".to_string(),
+                }],
+            })
+            .await
+            .unwrap();
+        let user = store.create_user("ash").await.unwrap();
+        let caught = CaughtPokemon {
+            pokedex_number: 25,
+            form: String::new(),
+            shiny: true,
+            game: Some("red".to_string()),
+            caught_at: "2024-01-01T00:00:00Z".parse().unwrap(),
+        };
+        store.mark_caught(user.id, &caught).await.unwrap();
+        let mut members = parse_showdown("Pikachu @ Light Ball\n- Thunderbolt\n").unwrap();
+        members[0].pokedex_number = 25;
+        let team = Team {
+            name: "kanto".to_string(),
+            game: None,
+            members,
+        };
+        store.save_team(user.id, &team).await.unwrap();
+        let annotation = Annotation {
+            pokedex_number: 25,
+            favorite: true,
+            tags: vec!["starter".to_string()],
+            note: Some(Note {
+                text: "The first one".to_string(),
+                updated_at: "2024-01-02T00:00:00Z".parse().unwrap(),
+            }),
+        };
+        store.save_annotation(user.id, &annotation).await.unwrap();
+
+        store
+    }
+
+    // Everything of the store a snapshot has, to compare two stores
+    async fn contents(
+        store: &MemoryStore,
+    ) -> (Vec<PokemonRecord>, Vec<SpeciesRecord>, Vec<UserCollection>) {
+        let pokemon = store
+            .search(&PokemonSearch::default())
+            .await
+            .unwrap()
+            .pokemon
+            .iter()
+            .map(|p| p.to_record())
+            .collect();
+        let mut users = Vec::new();
+        for user in store.list_users().await.unwrap() {
+            users.push(UserCollection {
+                pokemon: store.list_caught(user.id).await.unwrap(),
+                teams: store.list_teams(user.id).await.unwrap(),
+                annotations: store.list_annotations(user.id).await.unwrap(),
+                name: user.name,
+            });
+        }
+
+        (pokemon, store.list_species().await.unwrap(), users)
+    }
+
+    #[tokio::test]
+    async fn the_export_can_be_imported_again() {
+        let path = archive_path("round-trip");
+        let store = saved_store().await;
+        let exported = export_snapshot(&store, &path).await.unwrap();
+        let restored = MemoryStore::new();
+        let imported = import_snapshot(&restored, &path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(imported.unwrap(), exported);
+        assert_eq!(exported.schema_version, SNAPSHOT_SCHEMA_VERSION);
+        assert_eq!(exported.row_counts[POKEMON_FILE], 1);
+        assert_eq!(exported.row_counts[USERS_FILE], 1);
+        let restored = contents(&restored).await;
+        assert_eq!(restored, contents(&store).await);
+        assert_eq!(restored.2[0].teams[0].members[0].moves, ["thunderbolt"]);
+    }
+
+    #[tokio::test]
+    async fn only_an_empty_store_can_import() {
+        let path = archive_path("not-empty");
+        let store = saved_store().await;
+        export_snapshot(&store, &path).await.unwrap();
+        let result = import_snapshot(&store, &path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(Error::Input(message)) if message.contains("isn't empty")));
+    }
+
+    // The data of a sync that didn't save pokemon, runs or users is still data the import would mix
+    #[tokio::test]
+    async fn the_other_tables_must_be_empty_too() {
+        let path = archive_path("only-species");
+        export_snapshot(&saved_store().await, &path).await.unwrap();
+
+        let only_species = MemoryStore::new();
+        let pichu = SpeciesRecord {
+            pokedex_number: 172,
+            generation: Some(2),
+            names: Vec::new(),
+        };
+        only_species.upsert_species(&pichu).await.unwrap();
+        let species_result = import_snapshot(&only_species, &path).await;
+
+        let only_documents = MemoryStore::new();
+        let document = RawDocument::new("https://pokeapi.co/api/v2/pokemon/172/", b"{}").unwrap();
+        only_documents.archive_document(&document).await.unwrap();
+        let documents_result = import_snapshot(&only_documents, &path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(
+            matches!(species_result, Err(Error::Input(message)) if message.contains("isn't empty"))
+        );
+        assert_eq!(only_species.list_species().await.unwrap(), [pichu]);
+        assert!(matches!(documents_result, Err(Error::Input(_))));
+        assert_eq!(
+            only_documents.list_raw_documents().await.unwrap(),
+            [document]
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_the_newer_versions_and_other_archives() {
+        let newer = archive_path("newer");
+        write_archive(
+            &newer,
+            &manifest(SNAPSHOT_SCHEMA_VERSION + 1, serde_json::json!({})),
+            &[],
+        );
+        let other = archive_path("other");
+        let mut other_manifest = manifest(1, serde_json::json!({}));
+        other_manifest["format"] = "something-else".into();
+        write_archive(&other, &other_manifest, &[]);
+
+        let store = MemoryStore::new();
+        let newer_result = import_snapshot(&store, &newer).await;
+        let other_result = import_snapshot(&store, &other).await;
+        std::fs::remove_file(&newer).unwrap();
+        std::fs::remove_file(&other).unwrap();
+
+        let message = newer_result.unwrap_err().to_string();
+        assert!(message.contains("schema version 10, this pokedb reads up to 9. Update pokedb"));
+        assert!(other_result
+            .unwrap_err()
+            .to_string()
+            .contains("isn't a pokedb snapshot"));
+    }
+
+    #[tokio::test]
+    async fn the_names_of_version_1_are_the_species() {
+        let path = archive_path("version-1");
+        let pokemon = serde_json::to_string(&pikachu()).unwrap();
+        let names = [("en", "Pikachu"), ("ja", "ピカチュウ")]
+            .iter()
+            .map(|(language, name)| {
+                serde_json::json!({"pokedex_number": 25, "language": language, "name": name})
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        write_archive(
+            &path,
+            &manifest(1, serde_json::json!({POKEMON_FILE: 1, NAMES_FILE: 2})),
+            &[
+                (POKEMON_FILE, pokemon),
+                (NAMES_FILE, names),
+                (RAW_DOCUMENTS_FILE, String::new()),
+                (RUNS_FILE, String::new()),
+                (CHANGES_FILE, String::new()),
+            ],
+        );
+        let store = MemoryStore::new();
+        let result = import_snapshot(&store, &path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap().schema_version, 1);
+        let species = store.list_species().await.unwrap();
+        assert_eq!(species.len(), 1);
+        assert_eq!(species[0].generation, None);
+        assert_eq!(species[0].names.len(), 2);
+        assert_eq!(
+            store.get_by_dex_number(25).await.unwrap().unwrap().name,
+            "pikachu"
+        );
+    }
+
+    #[tokio::test]
+    async fn the_rows_must_match_the_manifest() {
+        let path = archive_path("row-counts");
+        let pokemon = serde_json::to_string(&pikachu()).unwrap();
+        write_archive(
+            &path,
+            &manifest(1, serde_json::json!({POKEMON_FILE: 2})),
+            &[
+                (POKEMON_FILE, pokemon),
+                (NAMES_FILE, String::new()),
+                (RAW_DOCUMENTS_FILE, String::new()),
+                (RUNS_FILE, String::new()),
+                (CHANGES_FILE, String::new()),
+            ],
+        );
+        let result = import_snapshot(&MemoryStore::new(), &path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Can't decode 'pokemon.jsonl': It has 1 rows, the manifest says 2"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_failed_import_leaves_the_store_empty() {
+        let path = archive_path("failed");
+        let ash = serde_json::json!({"name": "ash", "pokemon": []}).to_string();
+        let mut files: Vec<(&str, String)> = [
+            POKEMON_FILE,
+            SPECIES_FILE,
+            HISTORY_FILE,
+            LEARNSETS_FILE,
+            FORMS_FILE,
+            REGIONAL_DEXES_FILE,
+            VERSION_GROUPS_FILE,
+            VERSIONS_FILE,
+            RAW_DOCUMENTS_FILE,
+            RUNS_FILE,
+            CHANGES_FILE,
+        ]
+        .iter()
+        .map(|file| (*file, String::new()))
+        .collect();
+        files[0].1 = serde_json::to_string(&pikachu()).unwrap();
+        // The same user twice can't be saved
+        files.push((USERS_FILE, format!("{ash}\n{ash}")));
+        write_archive(
+            &path,
+            &manifest(
+                SNAPSHOT_SCHEMA_VERSION,
+                serde_json::json!({POKEMON_FILE: 1, USERS_FILE: 2}),
+            ),
+            &files,
+        );
+        let store = MemoryStore::new();
+        let result = import_snapshot(&store, &path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(Error::Input(_))));
+        let (pokemon, species, users) = contents(&store).await;
+        assert!(pokemon.is_empty() && species.is_empty() && users.is_empty());
+    }
+}
@@ -0,0 +1,1842 @@
+use crate::db::annotations::{group_annotations, Annotation, Note};
+use crate::db::collection::{CaughtPokemon, User};
+use crate::db::pokemon_tables::{
+    types_in_order, AbilityRecord, FieldChange, GameVersion, IngestionRun, LearnedMove, Learnset,
+    LocalizedName, PokemonChange, PokemonForm, PokemonHistory, PokemonName, PokemonRecord,
+    PokemonRow, RawDocument, RegionalDex, RegionalDexEntry, SpeciesRecord, UpsertOutcome,
+    VersionGroup, POKEMON_TYPES,
+};
+use crate::db::query::{
+    generation_sql, Pokemon, PokemonSearch, SearchResults, SqlDialect, SqlParam, Stat,
+};
+use crate::db::store::{has_data_sql, PokedexStore, TABLES};
+use crate::db::summary::{
+    sqlite_tables_sql, stat_columns, summarize, StatSummary, StatValues, SummaryView,
+};
+use crate::db::teams::{StatSpread, Team, TeamMember};
+use crate::error::Error;
+use async_trait::async_trait;
+use chrono::Utc;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row, ToSql};
+use std::collections::{BTreeMap, HashMap};
+use std::panic;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::task;
+
+/* The embedded SQLite backend, everything is saved in a single file. SQLite only has one writer
+at a time, so the connection is behind a mutex instead of a pool*/
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    // Opening the database file, SQLite creates it if not exists
+    pub fn open(path: &Path) -> Result<Self, rusqlite::Error> {
+        let conn = Connection::open(path)?;
+        // SQLite doesn't check the foreign keys unless it's asked for
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+
+        Ok(SqliteStore {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /* The calls of rusqlite block, so they run in the blocking threads of tokio with the connection
+    locked there, and the other tasks (e.g. the requests of a sync) go on meanwhile*/
+    async fn run<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            // A panic while holding the lock doesn't corrupt the connection, so the poison is ignored
+            let mut conn = conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            f(&mut conn)
+        })
+        .await
+        .unwrap_or_else(|e| panic::resume_unwind(e.into_panic()))
+    }
+}
+
+// The pokemon saved with a pokedex number, with its types and abilities
+fn load_record(
+    conn: &Connection,
+    pokedex_number: i32,
+) -> Result<Option<PokemonRecord>, rusqlite::Error> {
+    let Some((pokemon_id, mut record)) = conn
+        .query_row(
+            "SELECT * FROM pokemon WHERE pokedex_number = ?1",
+            [pokedex_number],
+            |row| {
+                Ok((
+                    row.get::<_, i32>("id")?,
+                    PokemonRecord {
+                        pokedex_number,
+                        name: row.get("name")?,
+                        height: row.get("height")?,
+                        weight: row.get("weight")?,
+                        hp: row.get("hp")?,
+                        attack: row.get("attack")?,
+                        defense: row.get("defense")?,
+                        special_attack: row.get("special_attack")?,
+                        special_defense: row.get("special_defense")?,
+                        speed: row.get("speed")?,
+                        types: Vec::new(),
+                        abilities: Vec::new(),
+                    },
+                ))
+            },
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    record.types = conn
+        .prepare(
+            "SELECT t.name FROM pokemon_types pt
+            JOIN types t ON pt.type_id = t.id
+            WHERE pt.pokemon_id = ?1",
+        )?
+        .query_map([pokemon_id], |row| row.get("name"))?
+        .collect::<Result<Vec<_>, _>>()?;
+    record.abilities = conn
+        .prepare(
+            "SELECT a.name, pa.is_hidden FROM pokemon_abilities pa
+            JOIN abilities a ON pa.ability_id = a.id
+            WHERE pa.pokemon_id = ?1",
+        )?
+        .query_map([pokemon_id], |row| {
+            Ok(AbilityRecord {
+                name: row.get("name")?,
+                is_hidden: row.get("is_hidden")?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Some(record))
+}
+
+fn sql_param(param: &SqlParam) -> &dyn ToSql {
+    match param {
+        SqlParam::Int(value) => value,
+        SqlParam::Text(value) => value,
+    }
+}
+
+fn ingestion_run_from_row(row: &Row) -> Result<IngestionRun, rusqlite::Error> {
+    Ok(IngestionRun {
+        id: row.get("id")?,
+        source: row.get("source")?,
+        started_at: row.get("started_at")?,
+        finished_at: row.get("finished_at")?,
+        inserted: row.get("inserted")?,
+        updated: row.get("updated")?,
+        unchanged: row.get("unchanged")?,
+        skipped: row.get("skipped")?,
+        failed: row.get("failed")?,
+        duration_ms: row.get("duration_ms")?,
+    })
+}
+
+fn learned_move_from_row(row: &Row) -> Result<(i32, LearnedMove), rusqlite::Error> {
+    Ok((
+        row.get("pokedex_number")?,
+        LearnedMove {
+            move_name: row.get("move_name")?,
+            version_group: row.get("version_group")?,
+            method: row.get("method")?,
+            level: row.get("level")?,
+        },
+    ))
+}
+
+// The forms with their abilities and moves, of a species or of every one without a number
+fn load_forms(
+    conn: &Connection,
+    pokedex_number: Option<i32>,
+) -> Result<Vec<PokemonForm>, rusqlite::Error> {
+    let filter = "WHERE ?1 IS NULL OR pokedex_number = ?1";
+    let mut forms: BTreeMap<(i32, String), PokemonForm> = BTreeMap::new();
+    for row in conn
+        .prepare(&format!(
+            "SELECT pokedex_number, form FROM pokemon_forms {filter}"
+        ))?
+        .query_map([pokedex_number], |row| Ok((row.get(0)?, row.get(1)?)))?
+    {
+        let (number, form): (i32, String) = row?;
+        forms.insert(
+            (number, form.clone()),
+            PokemonForm {
+                pokedex_number: number,
+                form,
+                abilities: Vec::new(),
+                moves: Vec::new(),
+            },
+        );
+    }
+
+    for row in conn
+        .prepare(&format!(
+            "SELECT pokedex_number, form, ability, is_hidden FROM pokemon_form_abilities {filter}
+            ORDER BY ability"
+        ))?
+        .query_map([pokedex_number], |row| {
+            Ok((
+                (row.get(0)?, row.get(1)?),
+                AbilityRecord {
+                    name: row.get(2)?,
+                    is_hidden: row.get(3)?,
+                },
+            ))
+        })?
+    {
+        let (key, ability) = row?;
+        if let Some(form) = forms.get_mut(&key) {
+            form.abilities.push(ability);
+        }
+    }
+    for row in conn
+        .prepare(&format!(
+            "SELECT pokedex_number, form, move_name, version_group, method, level
+            FROM pokemon_form_moves {filter} ORDER BY move_name, version_group, method"
+        ))?
+        .query_map([pokedex_number], |row| {
+            let (number, learned) = learned_move_from_row(row)?;
+            Ok(((number, row.get("form")?), learned))
+        })?
+    {
+        let (key, learned) = row?;
+        if let Some(form) = forms.get_mut(&key) {
+            form.moves.push(learned);
+        }
+    }
+
+    Ok(forms.into_values().collect())
+}
+
+// The EVs or the IVs of a row of 'team_members', the columns start with "ev" or "iv"
+fn stat_spread(row: &Row, prefix: &str) -> Result<StatSpread, rusqlite::Error> {
+    let stat = |name: &str| row.get::<_, i32>(format!("{prefix}_{name}").as_str());
+    Ok(StatSpread {
+        hp: stat("hp")?,
+        attack: stat("attack")?,
+        defense: stat("defense")?,
+        special_attack: stat("special_attack")?,
+        special_defense: stat("special_defense")?,
+        speed: stat("speed")?,
+    })
+}
+
+#[async_trait]
+impl PokedexStore for SqliteStore {
+    async fn create_tables(&self) -> Result<(), Error> {
+        // Same tables than PostgreSQL, SERIAL columns are INTEGER PRIMARY KEY here
+        self.run(move |conn| {
+            conn.execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS pokemon (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    random_id INTEGER UNIQUE NOT NULL,
+                    pokedex_number INTEGER UNIQUE NOT NULL,
+                    name TEXT NOT NULL,
+                    height TEXT,
+                    weight TEXT,
+                    hp INTEGER,
+                    attack INTEGER,
+                    defense INTEGER,
+                    special_attack INTEGER,
+                    special_defense INTEGER,
+                    speed INTEGER
+                );
+                CREATE TABLE IF NOT EXISTS types (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT UNIQUE NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_types (
+                    pokemon_id INTEGER REFERENCES pokemon(id),
+                    type_id INTEGER REFERENCES types(id),
+                    PRIMARY KEY (pokemon_id, type_id)
+                );
+                CREATE TABLE IF NOT EXISTS abilities (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT UNIQUE NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_abilities (
+                    pokemon_id INTEGER REFERENCES pokemon(id),
+                    ability_id INTEGER REFERENCES abilities(id),
+                    is_hidden INTEGER NOT NULL,
+                    PRIMARY KEY (pokemon_id, ability_id)
+                );
+                CREATE TABLE IF NOT EXISTS species (
+                    pokedex_number INTEGER PRIMARY KEY,
+                    generation INTEGER
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_past_types (
+                    pokedex_number INTEGER NOT NULL,
+                    generation INTEGER NOT NULL,
+                    slot INTEGER NOT NULL,
+                    type_name TEXT NOT NULL,
+                    PRIMARY KEY (pokedex_number, generation, slot)
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_past_stats (
+                    pokedex_number INTEGER NOT NULL,
+                    generation INTEGER NOT NULL,
+                    stat TEXT NOT NULL,
+                    base_stat INTEGER NOT NULL,
+                    PRIMARY KEY (pokedex_number, generation, stat)
+                );
+                CREATE TABLE IF NOT EXISTS regional_dexes (
+                    name TEXT PRIMARY KEY,
+                    region TEXT,
+                    is_main_series INTEGER NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS regional_dex_entries (
+                    pokedex_name TEXT NOT NULL REFERENCES regional_dexes(name),
+                    entry_number INTEGER NOT NULL,
+                    pokedex_number INTEGER NOT NULL,
+                    PRIMARY KEY (pokedex_name, entry_number)
+                );
+                CREATE INDEX IF NOT EXISTS regional_dex_entries_species_idx
+                    ON regional_dex_entries (pokedex_number);
+                CREATE TABLE IF NOT EXISTS version_groups (
+                    name TEXT PRIMARY KEY,
+                    sort_order INTEGER NOT NULL,
+                    generation INTEGER
+                );
+                CREATE TABLE IF NOT EXISTS version_group_regions (
+                    version_group TEXT NOT NULL REFERENCES version_groups(name),
+                    region TEXT NOT NULL,
+                    PRIMARY KEY (version_group, region)
+                );
+                CREATE TABLE IF NOT EXISTS version_group_pokedexes (
+                    version_group TEXT NOT NULL REFERENCES version_groups(name),
+                    pokedex_name TEXT NOT NULL,
+                    position INTEGER NOT NULL,
+                    PRIMARY KEY (version_group, pokedex_name)
+                );
+                CREATE TABLE IF NOT EXISTS versions (
+                    name TEXT PRIMARY KEY,
+                    version_group TEXT NOT NULL,
+                    display_name TEXT
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_names (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    pokedex_number INTEGER NOT NULL,
+                    language TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    UNIQUE (pokedex_number, language)
+                );
+                -- The JSON is saved as text, SQLite's json functions can still query it
+                CREATE TABLE IF NOT EXISTS raw_documents (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    url TEXT NOT NULL,
+                    fetched_at TEXT NOT NULL,
+                    content_hash TEXT NOT NULL,
+                    body TEXT NOT NULL,
+                    UNIQUE (url, content_hash)
+                );
+                CREATE INDEX IF NOT EXISTS raw_documents_url_idx
+                    ON raw_documents (url, fetched_at DESC);
+                CREATE TABLE IF NOT EXISTS ingestion_runs (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    source TEXT NOT NULL,
+                    started_at TEXT NOT NULL,
+                    finished_at TEXT,
+                    inserted INTEGER NOT NULL DEFAULT 0,
+                    updated INTEGER NOT NULL DEFAULT 0,
+                    unchanged INTEGER NOT NULL DEFAULT 0,
+                    skipped INTEGER NOT NULL DEFAULT 0,
+                    failed INTEGER NOT NULL DEFAULT 0,
+                    duration_ms INTEGER
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_changes (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    run_id INTEGER NOT NULL REFERENCES ingestion_runs(id),
+                    pokedex_number INTEGER NOT NULL,
+                    pokemon_name TEXT NOT NULL,
+                    field TEXT NOT NULL,
+                    old_value TEXT,
+                    new_value TEXT,
+                    changed_at TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS pokemon_changes_run_idx ON pokemon_changes (run_id);
+                CREATE TABLE IF NOT EXISTS users (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT UNIQUE NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS user_pokemon (
+                    user_id INTEGER NOT NULL REFERENCES users(id),
+                    pokedex_number INTEGER NOT NULL,
+                    form TEXT NOT NULL DEFAULT '',
+                    shiny INTEGER NOT NULL DEFAULT 0,
+                    game TEXT,
+                    caught_at TEXT NOT NULL,
+                    PRIMARY KEY (user_id, pokedex_number, form, shiny)
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_moves (
+                    pokedex_number INTEGER NOT NULL,
+                    move_name TEXT NOT NULL,
+                    version_group TEXT NOT NULL,
+                    method TEXT NOT NULL,
+                    level INTEGER NOT NULL DEFAULT 0,
+                    PRIMARY KEY (pokedex_number, move_name, version_group, method)
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_forms (
+                    pokedex_number INTEGER NOT NULL,
+                    form TEXT NOT NULL,
+                    PRIMARY KEY (pokedex_number, form)
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_form_abilities (
+                    pokedex_number INTEGER NOT NULL,
+                    form TEXT NOT NULL,
+                    ability TEXT NOT NULL,
+                    is_hidden INTEGER NOT NULL,
+                    PRIMARY KEY (pokedex_number, form, ability),
+                    FOREIGN KEY (pokedex_number, form)
+                        REFERENCES pokemon_forms(pokedex_number, form) ON DELETE CASCADE
+                );
+                CREATE TABLE IF NOT EXISTS pokemon_form_moves (
+                    pokedex_number INTEGER NOT NULL,
+                    form TEXT NOT NULL,
+                    move_name TEXT NOT NULL,
+                    version_group TEXT NOT NULL,
+                    method TEXT NOT NULL,
+                    level INTEGER NOT NULL DEFAULT 0,
+                    PRIMARY KEY (pokedex_number, form, move_name, version_group, method),
+                    FOREIGN KEY (pokedex_number, form)
+                        REFERENCES pokemon_forms(pokedex_number, form) ON DELETE CASCADE
+                );
+                CREATE TABLE IF NOT EXISTS teams (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    user_id INTEGER NOT NULL REFERENCES users(id),
+                    name TEXT NOT NULL,
+                    game TEXT,
+                    UNIQUE (user_id, name)
+                );
+                CREATE TABLE IF NOT EXISTS team_members (
+                    team_id INTEGER NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+                    slot INTEGER NOT NULL,
+                    pokedex_number INTEGER NOT NULL,
+                    species TEXT NOT NULL,
+                    form TEXT NOT NULL DEFAULT '',
+                    nickname TEXT,
+                    gender TEXT,
+                    item TEXT,
+                    ability TEXT,
+                    nature TEXT,
+                    level INTEGER NOT NULL DEFAULT 100,
+                    shiny INTEGER NOT NULL DEFAULT 0,
+                    ev_hp INTEGER NOT NULL DEFAULT 0,
+                    ev_attack INTEGER NOT NULL DEFAULT 0,
+                    ev_defense INTEGER NOT NULL DEFAULT 0,
+                    ev_special_attack INTEGER NOT NULL DEFAULT 0,
+                    ev_special_defense INTEGER NOT NULL DEFAULT 0,
+                    ev_speed INTEGER NOT NULL DEFAULT 0,
+                    iv_hp INTEGER NOT NULL DEFAULT 31,
+                    iv_attack INTEGER NOT NULL DEFAULT 31,
+                    iv_defense INTEGER NOT NULL DEFAULT 31,
+                    iv_special_attack INTEGER NOT NULL DEFAULT 31,
+                    iv_special_defense INTEGER NOT NULL DEFAULT 31,
+                    iv_speed INTEGER NOT NULL DEFAULT 31,
+                    PRIMARY KEY (team_id, slot)
+                );
+                CREATE TABLE IF NOT EXISTS team_member_moves (
+                    team_id INTEGER NOT NULL,
+                    slot INTEGER NOT NULL,
+                    position INTEGER NOT NULL,
+                    move_name TEXT NOT NULL,
+                    PRIMARY KEY (team_id, slot, position),
+                    FOREIGN KEY (team_id, slot) REFERENCES team_members(team_id, slot) ON DELETE CASCADE
+                );
+                CREATE TABLE IF NOT EXISTS user_favorites (
+                    user_id INTEGER NOT NULL REFERENCES users(id),
+                    pokedex_number INTEGER NOT NULL,
+                    PRIMARY KEY (user_id, pokedex_number)
+                );
+                CREATE TABLE IF NOT EXISTS user_tags (
+                    user_id INTEGER NOT NULL REFERENCES users(id),
+                    pokedex_number INTEGER NOT NULL,
+                    tag TEXT NOT NULL,
+                    PRIMARY KEY (user_id, pokedex_number, tag)
+                );
+                CREATE INDEX IF NOT EXISTS user_tags_tag_idx ON user_tags (user_id, tag);
+                CREATE TABLE IF NOT EXISTS user_notes (
+                    user_id INTEGER NOT NULL REFERENCES users(id),
+                    pokedex_number INTEGER NOT NULL,
+                    note TEXT NOT NULL,
+                    updated_at TEXT NOT NULL,
+                    PRIMARY KEY (user_id, pokedex_number)
+                );
+                ",
+            )?;
+            conn.execute_batch(&sqlite_tables_sql())?;
+
+            println!("Tables created or uploaded.");
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn reset_types_table(&self) -> Result<(), Error> {
+        self.run(move |conn| {
+            // When the types already have the right index the table isn't touched
+            let saved = conn
+                .prepare("SELECT id, name FROM types ORDER BY id")?
+                .query_map([], |row| Ok((row.get("id")?, row.get("name")?)))?
+                .collect::<Result<Vec<(i32, String)>, _>>()?;
+            if types_in_order(&saved) {
+                return Ok(());
+            }
+
+            // SQLite doesn't have TRUNCATE, the sequence is restarted by hand
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM pokemon_types", [])?;
+            tx.execute("DELETE FROM types", [])?;
+            tx.execute("DELETE FROM sqlite_sequence WHERE name = 'types'", [])?;
+
+            for name in POKEMON_TYPES.iter() {
+                tx.execute(
+                    "INSERT INTO types (name) VALUES (?1) ON CONFLICT (name) DO NOTHING",
+                    [name],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn upsert_pokemon(&self, pokemon: &PokemonRecord) -> Result<UpsertOutcome, Error> {
+        let pokemon = pokemon.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            // The saved pokemon is compared with the new one to know what changed
+            let old = load_record(&tx, pokemon.pokedex_number)?;
+
+            // 'random_id' is SERIAL in PostgreSQL, here the next value is calculated in the insert
+            let pokemon_id: i32 = tx.query_row(
+                "INSERT INTO pokemon (random_id, pokedex_number, name, height, weight, hp, attack, defense, special_attack, special_defense, speed)
+                VALUES ((SELECT COALESCE(MAX(random_id), 0) + 1 FROM pokemon), ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                ON CONFLICT(pokedex_number) DO UPDATE SET
+                    name = excluded.name,
+                    height = excluded.height,
+                    weight = excluded.weight,
+                    hp = excluded.hp,
+                    attack = excluded.attack,
+                    defense = excluded.defense,
+                    special_attack = excluded.special_attack,
+                    special_defense = excluded.special_defense,
+                    speed = excluded.speed
+                RETURNING id",
+                params![
+                    pokemon.pokedex_number,
+                    pokemon.name,
+                    pokemon.height,
+                    pokemon.weight,
+                    pokemon.hp,
+                    pokemon.attack,
+                    pokemon.defense,
+                    pokemon.special_attack,
+                    pokemon.special_defense,
+                    pokemon.speed,
+                ],
+                |row| row.get(0),
+            )?;
+
+            // The types and abilities the pokemon doesn't have anymore are removed
+            let types_json = serde_json::to_string(&pokemon.types)?;
+            let abilities_json = serde_json::to_string(
+                &pokemon
+                    .abilities
+                    .iter()
+                    .map(|a| &a.name)
+                    .collect::<Vec<_>>(),
+            )?;
+            tx.execute(
+                "DELETE FROM pokemon_types WHERE pokemon_id = ?1
+                AND type_id NOT IN (SELECT id FROM types WHERE name IN (SELECT value FROM json_each(?2)))",
+                params![pokemon_id, types_json],
+            )?;
+            tx.execute(
+                "DELETE FROM pokemon_abilities WHERE pokemon_id = ?1
+                AND ability_id NOT IN (SELECT id FROM abilities WHERE name IN (SELECT value FROM json_each(?2)))",
+                params![pokemon_id, abilities_json],
+            )?;
+
+            for type_name in &pokemon.types {
+                let type_id: i32 =
+                    tx.query_row("SELECT id FROM types WHERE name = ?1", [type_name], |row| {
+                        row.get(0)
+                    })?;
+
+                tx.execute(
+                    "INSERT INTO pokemon_types (pokemon_id, type_id) VALUES (?1, ?2) ON CONFLICT DO NOTHING",
+                    params![pokemon_id, type_id],
+                )?;
+            }
+
+            for ability in &pokemon.abilities {
+                tx.execute(
+                    "INSERT INTO abilities (name) VALUES (?1) ON CONFLICT (name) DO NOTHING",
+                    [&ability.name],
+                )?;
+                let ability_id: i32 = tx.query_row(
+                    "SELECT id FROM abilities WHERE name = ?1",
+                    [&ability.name],
+                    |row| row.get(0),
+                )?;
+
+                tx.execute(
+                    "INSERT INTO pokemon_abilities (pokemon_id, ability_id, is_hidden) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (pokemon_id, ability_id) DO UPDATE SET is_hidden = excluded.is_hidden",
+                    params![pokemon_id, ability_id, ability.is_hidden],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(UpsertOutcome::between(old.as_ref(), &pokemon))
+        })
+        .await
+    }
+
+    async fn search(&self, search: &PokemonSearch) -> Result<SearchResults, Error> {
+        if let Some(generation) = search.as_of_generation {
+            return self.search_as_of(search, generation).await;
+        }
+        let sql = search.to_sql(SqlDialect::Sqlite);
+        self.run(move |conn| {
+            let total = conn.query_row(
+                &format!("SELECT COUNT(*) FROM pokemon p {}", sql.where_clause),
+                params_from_iter(sql.params.iter().map(sql_param)),
+                |row| row.get(0),
+            )?;
+
+            let page = conn
+                .prepare(&format!(
+                    "SELECT p.id, p.pokedex_number, {}, {} FROM pokemon p {} {} {}",
+                    generation_sql(),
+                    sql.regional_number,
+                    sql.where_clause,
+                    sql.order_by,
+                    sql.limit
+                ))?
+                .query_map(
+                    params_from_iter(
+                        sql.params
+                            .iter()
+                            .map(sql_param)
+                            .chain(sql.page.iter().map(|value| value as &dyn ToSql)),
+                    ),
+                    |row| {
+                        Ok((
+                            row.get::<_, i32>(0)?,
+                            row.get::<_, i32>(1)?,
+                            row.get::<_, Option<i32>>(2)?,
+                            row.get::<_, Option<i32>>(3)?,
+                        ))
+                    },
+                )?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // The file is local, so loading each pokemon of the page on its own is fast
+            let mut pokemon = Vec::with_capacity(page.len());
+            for (id, pokedex_number, generation, regional_number) in page {
+                if let Some(record) = load_record(conn, pokedex_number)? {
+                    let mut found = Pokemon::from_record(id, &record, generation);
+                    found.regional_number = regional_number;
+                    pokemon.push(found);
+                }
+            }
+
+            Ok(SearchResults { total, pokemon })
+        })
+        .await
+    }
+
+    async fn upsert_species(&self, species: &SpeciesRecord) -> Result<(), Error> {
+        let species = species.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO species (pokedex_number, generation) VALUES (?1, ?2)
+                ON CONFLICT (pokedex_number) DO UPDATE SET generation = excluded.generation",
+                params![species.pokedex_number, species.generation],
+            )?;
+            tx.execute(
+                "DELETE FROM pokemon_names WHERE pokedex_number = ?1",
+                [species.pokedex_number],
+            )?;
+            for name in &species.names {
+                tx.execute(
+                    "INSERT INTO pokemon_names (pokedex_number, language, name) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (pokedex_number, language) DO UPDATE SET name = excluded.name",
+                    params![species.pokedex_number, name.language, name.name],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_species(&self) -> Result<Vec<SpeciesRecord>, Error> {
+        self.run(move |conn| {
+            let mut species = conn
+                .prepare("SELECT pokedex_number, generation FROM species ORDER BY pokedex_number")?
+                .query_map([], |row| {
+                    Ok(SpeciesRecord {
+                        pokedex_number: row.get("pokedex_number")?,
+                        generation: row.get("generation")?,
+                        names: Vec::new(),
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut names: HashMap<i32, Vec<LocalizedName>> = HashMap::new();
+            let rows = conn
+                .prepare("SELECT pokedex_number, language, name FROM pokemon_names ORDER BY id")?
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i32>("pokedex_number")?,
+                        LocalizedName {
+                            language: row.get("language")?,
+                            name: row.get("name")?,
+                        },
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (pokedex_number, name) in rows {
+                names.entry(pokedex_number).or_default().push(name);
+            }
+            for record in &mut species {
+                record.names = names.remove(&record.pokedex_number).unwrap_or_default();
+            }
+            // The names saved before the species table existed don't have a generation
+            species.extend(
+                names
+                    .into_iter()
+                    .map(|(pokedex_number, names)| SpeciesRecord {
+                        pokedex_number,
+                        generation: None,
+                        names,
+                    }),
+            );
+            species.sort_by_key(|record| record.pokedex_number);
+
+            Ok(species)
+        })
+        .await
+    }
+
+    async fn upsert_pokemon_history(&self, history: &PokemonHistory) -> Result<(), Error> {
+        let history = history.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM pokemon_past_types WHERE pokedex_number = ?1",
+                [history.pokedex_number],
+            )?;
+            for past in &history.past_types {
+                for (slot, type_name) in past.types.iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO pokemon_past_types (pokedex_number, generation, slot, type_name)
+                        VALUES (?1, ?2, ?3, ?4)",
+                        params![history.pokedex_number, past.generation, slot + 1, type_name],
+                    )?;
+                }
+            }
+            tx.execute(
+                "DELETE FROM pokemon_past_stats WHERE pokedex_number = ?1",
+                [history.pokedex_number],
+            )?;
+            for past in &history.past_stats {
+                for (stat, base_stat) in &past.stats {
+                    tx.execute(
+                        "INSERT INTO pokemon_past_stats (pokedex_number, generation, stat, base_stat)
+                        VALUES (?1, ?2, ?3, ?4)",
+                        params![history.pokedex_number, past.generation, stat, base_stat],
+                    )?;
+                }
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_pokemon_history(&self) -> Result<Vec<PokemonHistory>, Error> {
+        self.run(move |conn| {
+            let type_rows = conn
+                .prepare(
+                    "SELECT pokedex_number, generation, type_name FROM pokemon_past_types
+                    ORDER BY pokedex_number, generation, slot",
+                )?
+                .query_map([], |row| {
+                    Ok((
+                        row.get("pokedex_number")?,
+                        row.get("generation")?,
+                        row.get("type_name")?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            let stat_rows = conn
+                .prepare(
+                    "SELECT pokedex_number, generation, stat, base_stat FROM pokemon_past_stats
+                    ORDER BY pokedex_number, generation, stat",
+                )?
+                .query_map([], |row| {
+                    Ok((
+                        row.get("pokedex_number")?,
+                        row.get("generation")?,
+                        row.get("stat")?,
+                        row.get("base_stat")?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(PokemonHistory::from_rows(type_rows, stat_rows))
+        })
+        .await
+    }
+
+    async fn upsert_regional_dex(&self, dex: &RegionalDex) -> Result<(), Error> {
+        let dex = dex.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO regional_dexes (name, region, is_main_series) VALUES (?1, ?2, ?3)
+                ON CONFLICT (name) DO UPDATE
+                SET region = excluded.region, is_main_series = excluded.is_main_series",
+                params![dex.name, dex.region, dex.is_main_series],
+            )?;
+            tx.execute(
+                "DELETE FROM regional_dex_entries WHERE pokedex_name = ?1",
+                [&dex.name],
+            )?;
+            for entry in &dex.entries {
+                tx.execute(
+                    "INSERT INTO regional_dex_entries (pokedex_name, entry_number, pokedex_number)
+                    VALUES (?1, ?2, ?3)",
+                    params![dex.name, entry.entry_number, entry.pokedex_number],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_regional_dexes(&self) -> Result<Vec<RegionalDex>, Error> {
+        self.run(move |conn| {
+            let mut entries: HashMap<String, Vec<RegionalDexEntry>> = HashMap::new();
+            let rows = conn
+                .prepare(
+                    "SELECT pokedex_name, entry_number, pokedex_number FROM regional_dex_entries
+                    ORDER BY pokedex_name, entry_number",
+                )?
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>("pokedex_name")?,
+                        RegionalDexEntry {
+                            entry_number: row.get("entry_number")?,
+                            pokedex_number: row.get("pokedex_number")?,
+                        },
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (name, entry) in rows {
+                entries.entry(name).or_default().push(entry);
+            }
+            let dexes = conn
+                .prepare("SELECT name, region, is_main_series FROM regional_dexes ORDER BY name")?
+                .query_map([], |row| {
+                    let name: String = row.get("name")?;
+                    Ok(RegionalDex {
+                        entries: entries.remove(&name).unwrap_or_default(),
+                        name,
+                        region: row.get("region")?,
+                        is_main_series: row.get("is_main_series")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(dexes)
+        })
+        .await
+    }
+
+    async fn upsert_version_group(&self, group: &VersionGroup) -> Result<(), Error> {
+        let group = group.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO version_groups (name, sort_order, generation) VALUES (?1, ?2, ?3)
+                ON CONFLICT (name) DO UPDATE
+                SET sort_order = excluded.sort_order, generation = excluded.generation",
+                params![group.name, group.order, group.generation],
+            )?;
+            tx.execute(
+                "DELETE FROM version_group_regions WHERE version_group = ?1",
+                [&group.name],
+            )?;
+            for region in &group.regions {
+                tx.execute(
+                    "INSERT OR IGNORE INTO version_group_regions (version_group, region) VALUES (?1, ?2)",
+                    params![group.name, region],
+                )?;
+            }
+            tx.execute(
+                "DELETE FROM version_group_pokedexes WHERE version_group = ?1",
+                [&group.name],
+            )?;
+            for (position, pokedex) in group.pokedexes.iter().enumerate() {
+                tx.execute(
+                    "INSERT OR IGNORE INTO version_group_pokedexes (version_group, pokedex_name, position)
+                    VALUES (?1, ?2, ?3)",
+                    params![group.name, pokedex, position as i32],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn upsert_version(&self, version: &GameVersion) -> Result<(), Error> {
+        let version = version.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO versions (name, version_group, display_name) VALUES (?1, ?2, ?3)
+                ON CONFLICT (name) DO UPDATE
+                SET version_group = excluded.version_group, display_name = excluded.display_name",
+                params![version.name, version.version_group, version.display_name],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_version_groups(&self) -> Result<Vec<VersionGroup>, Error> {
+        self.run(move |conn| {
+            // The regions and the pokedexes of every group, by the name of the group
+            let names_of = |sql: &str| -> rusqlite::Result<HashMap<String, Vec<String>>> {
+                let mut names: HashMap<String, Vec<String>> = HashMap::new();
+                let rows = conn
+                    .prepare(sql)?
+                    .query_map([], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (group, name) in rows {
+                    names.entry(group).or_default().push(name);
+                }
+                Ok(names)
+            };
+            let mut regions = names_of(
+                "SELECT version_group, region FROM version_group_regions
+                ORDER BY version_group, region",
+            )?;
+            let mut pokedexes = names_of(
+                "SELECT version_group, pokedex_name FROM version_group_pokedexes
+                ORDER BY version_group, position",
+            )?;
+            let groups = conn
+                .prepare(
+                    "SELECT name, sort_order, generation FROM version_groups ORDER BY sort_order, name",
+                )?
+                .query_map([], |row| {
+                    let name: String = row.get("name")?;
+                    Ok(VersionGroup {
+                        regions: regions.remove(&name).unwrap_or_default(),
+                        pokedexes: pokedexes.remove(&name).unwrap_or_default(),
+                        name,
+                        order: row.get("sort_order")?,
+                        generation: row.get("generation")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(groups)
+        })
+        .await
+    }
+
+    async fn list_versions(&self) -> Result<Vec<GameVersion>, Error> {
+        self.run(move |conn| {
+            let versions = conn
+                .prepare("SELECT name, version_group, display_name FROM versions ORDER BY name")?
+                .query_map([], |row| {
+                    Ok(GameVersion {
+                        name: row.get("name")?,
+                        version_group: row.get("version_group")?,
+                        display_name: row.get("display_name")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(versions)
+        })
+        .await
+    }
+
+    async fn list_names(&self) -> Result<Vec<PokemonName>, Error> {
+        self.run(move |conn| {
+            let names = conn
+                .prepare(
+                    "SELECT pokedex_number, NULL AS language, name FROM pokemon
+                    UNION ALL
+                    SELECT pokedex_number, language, name FROM pokemon_names",
+                )?
+                .query_map([], |row| {
+                    Ok(PokemonName {
+                        pokedex_number: row.get("pokedex_number")?,
+                        language: row.get("language")?,
+                        name: row.get("name")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(names)
+        })
+        .await
+    }
+
+    async fn load_pokemon_rows(&self) -> Result<Vec<PokemonRow>, Error> {
+        self.run(move |conn| {
+            // group_concat doesn't have ORDER BY, so the types are sorted in a subquery
+            let mut statement = conn.prepare(&format!(
+                "
+                SELECT p.*,
+                    (SELECT group_concat(name, ', ') FROM (
+                        SELECT t.name FROM pokemon_types pt
+                        JOIN types t ON pt.type_id = t.id
+                        WHERE pt.pokemon_id = p.id
+                        ORDER BY t.name
+                    )) AS types,
+                    {} AS generation
+                FROM pokemon p
+                ",
+                generation_sql()
+            ))?;
+
+            let rows = statement
+                .query_map([], |row| {
+                    Ok(PokemonRow {
+                        id: row.get("id")?,
+                        random_id: row.get("random_id")?,
+                        pokedex_number: row.get("pokedex_number")?,
+                        name: row.get("name")?,
+                        height: row.get("height")?,
+                        weight: row.get("weight")?,
+                        hp: row.get("hp")?,
+                        attack: row.get("attack")?,
+                        defense: row.get("defense")?,
+                        special_attack: row.get("special_attack")?,
+                        special_defense: row.get("special_defense")?,
+                        speed: row.get("speed")?,
+                        types: row.get::<_, Option<String>>("types")?.unwrap_or_default(),
+                        generation: row.get("generation")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(rows)
+        })
+        .await
+    }
+
+    async fn archive_document(&self, document: &RawDocument) -> Result<(), Error> {
+        let document = document.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO raw_documents (url, fetched_at, content_hash, body)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (url, content_hash) DO UPDATE SET fetched_at = excluded.fetched_at",
+                params![
+                    document.url,
+                    document.fetched_at,
+                    document.content_hash,
+                    document.body,
+                ],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_raw_documents(&self, url_prefix: &str) -> Result<Vec<RawDocument>, Error> {
+        let url_prefix = url_prefix.to_string();
+        self.run(move |conn| {
+            // substr instead of LIKE, so '%' and '_' in the URL don't match anything
+            let mut statement = conn.prepare(
+                "SELECT url, fetched_at, content_hash, body FROM (
+                    SELECT *, ROW_NUMBER() OVER (PARTITION BY url ORDER BY fetched_at DESC) AS position
+                    FROM raw_documents
+                    WHERE substr(url, 1, length(?1)) = ?1
+                )
+                WHERE position = 1
+                ORDER BY url",
+            )?;
+
+            let documents = statement
+                .query_map([url_prefix], |row| {
+                    Ok(RawDocument {
+                        url: row.get("url")?,
+                        fetched_at: row.get("fetched_at")?,
+                        content_hash: row.get("content_hash")?,
+                        body: row.get("body")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(documents)
+        })
+        .await
+    }
+
+    async fn refresh_summaries(&self) -> Result<(), Error> {
+        // The summaries are calculated in Rust and saved in their tables
+        let pokemon = self.search(&PokemonSearch::default()).await?.pokemon;
+        let columns = stat_columns();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            for view in SummaryView::ALL {
+                tx.execute(&format!("DELETE FROM {}", view.view_name()), [])?;
+                let placeholders: Vec<String> = (1..=columns.len() + 1)
+                    .map(|index| format!("?{index}"))
+                    .collect();
+                let mut insert = tx.prepare(&format!(
+                    "INSERT INTO {} ({}, {}) VALUES ({})",
+                    view.view_name(),
+                    view.group_column(),
+                    columns.join(", "),
+                    placeholders.join(", ")
+                ))?;
+
+                for summary in summarize(&pokemon, view) {
+                    let mut values = vec![
+                        SqlValue::Text(summary.group),
+                        SqlValue::Integer(summary.pokemon_count),
+                    ];
+                    values.extend(
+                        Stat::ALL
+                            .iter()
+                            .map(|s| SqlValue::Real(summary.mean.get(*s))),
+                    );
+                    values.extend(
+                        Stat::ALL
+                            .iter()
+                            .map(|s| SqlValue::Real(summary.median.get(*s))),
+                    );
+                    insert.execute(params_from_iter(values))?;
+                }
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_summary(&self, view: SummaryView) -> Result<Vec<StatSummary>, Error> {
+        self.run(move |conn| {
+            let summaries = conn
+                .prepare(&format!(
+                    "SELECT CAST({group} AS TEXT) AS group_name, {} FROM {} ORDER BY {group}",
+                    stat_columns().join(", "),
+                    view.view_name(),
+                    group = view.group_column()
+                ))?
+                .query_map([], |row| {
+                    let mut mean = StatValues::default();
+                    let mut median = StatValues::default();
+                    for stat in Stat::ALL {
+                        mean.set(stat, row.get(format!("mean_{}", stat.name()).as_str())?);
+                        median.set(stat, row.get(format!("median_{}", stat.name()).as_str())?);
+                    }
+                    Ok(StatSummary {
+                        group: row.get("group_name")?,
+                        pokemon_count: row.get("pokemon_count")?,
+                        mean,
+                        median,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(summaries)
+        })
+        .await
+    }
+
+    async fn start_ingestion_run(&self, source: &str) -> Result<IngestionRun, Error> {
+        let source = source.to_string();
+        self.run(move |conn| {
+            let run = conn.query_row(
+                "INSERT INTO ingestion_runs (source, started_at) VALUES (?1, ?2) RETURNING *",
+                params![source, Utc::now()],
+                ingestion_run_from_row,
+            )?;
+
+            Ok(run)
+        })
+        .await
+    }
+
+    async fn finish_ingestion_run(&self, run: &IngestionRun) -> Result<(), Error> {
+        let run = run.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE ingestion_runs SET finished_at = ?2, inserted = ?3, updated = ?4,
+                    unchanged = ?5, skipped = ?6, failed = ?7, duration_ms = ?8
+                WHERE id = ?1",
+                params![
+                    run.id,
+                    run.finished_at,
+                    run.inserted,
+                    run.updated,
+                    run.unchanged,
+                    run.skipped,
+                    run.failed,
+                    run.duration_ms,
+                ],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn record_changes(
+        &self,
+        run_id: i32,
+        pokemon: &PokemonRecord,
+        changes: &[FieldChange],
+    ) -> Result<(), Error> {
+        let pokemon = pokemon.clone();
+        let changes = changes.to_vec();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            let changed_at = Utc::now();
+
+            for change in changes {
+                tx.execute(
+                    "INSERT INTO pokemon_changes (run_id, pokedex_number, pokemon_name, field, old_value, new_value, changed_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        run_id,
+                        pokemon.pokedex_number,
+                        pokemon.name,
+                        change.field,
+                        change.old_value,
+                        change.new_value,
+                        changed_at,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn restore_history(
+        &self,
+        runs: &[IngestionRun],
+        changes: &[PokemonChange],
+    ) -> Result<(), Error> {
+        let runs = runs.to_vec();
+        let changes = changes.to_vec();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            // AUTOINCREMENT continues after the biggest id, so the given ids can be inserted
+            for run in runs {
+                tx.execute(
+                    "INSERT INTO ingestion_runs (id, source, started_at, finished_at, inserted, updated, unchanged, skipped, failed, duration_ms)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    params![
+                        run.id,
+                        run.source,
+                        run.started_at,
+                        run.finished_at,
+                        run.inserted,
+                        run.updated,
+                        run.unchanged,
+                        run.skipped,
+                        run.failed,
+                        run.duration_ms
+                    ],
+                )?;
+            }
+            for change in changes {
+                tx.execute(
+                    "INSERT INTO pokemon_changes (run_id, pokedex_number, pokemon_name, field, old_value, new_value, changed_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        change.run_id,
+                        change.pokedex_number,
+                        change.pokemon_name,
+                        change.field,
+                        change.old_value,
+                        change.new_value,
+                        change.changed_at
+                    ],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_ingestion_runs(&self) -> Result<Vec<IngestionRun>, Error> {
+        self.run(move |conn| {
+            let runs = conn
+                .prepare("SELECT * FROM ingestion_runs ORDER BY id DESC")?
+                .query_map([], ingestion_run_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(runs)
+        })
+        .await
+    }
+
+    async fn changes_between_runs(
+        &self,
+        from_run: i32,
+        to_run: i32,
+    ) -> Result<Vec<PokemonChange>, Error> {
+        self.run(move |conn| {
+            let changes = conn
+                .prepare(
+                    "SELECT * FROM pokemon_changes
+                    WHERE run_id > ?1 AND run_id <= ?2
+                    ORDER BY run_id, pokedex_number, id",
+                )?
+                .query_map([from_run, to_run], |row| {
+                    Ok(PokemonChange {
+                        run_id: row.get("run_id")?,
+                        pokedex_number: row.get("pokedex_number")?,
+                        pokemon_name: row.get("pokemon_name")?,
+                        field: row.get("field")?,
+                        old_value: row.get("old_value")?,
+                        new_value: row.get("new_value")?,
+                        changed_at: row.get("changed_at")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(changes)
+        })
+        .await
+    }
+
+    async fn create_user(&self, name: &str) -> Result<User, Error> {
+        let name = name.to_string();
+        self.run(move |conn| {
+            let id: i32 = conn
+                .query_row(
+                    "INSERT INTO users (name) VALUES (?1) ON CONFLICT (name) DO NOTHING RETURNING id",
+                    [&name],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or_else(|| Error::Input(format!("The user '{name}' already exists")))?;
+
+            Ok(User {
+                id,
+                name: name.to_string(),
+            })
+        })
+        .await
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, Error> {
+        self.run(move |conn| {
+            let users = conn
+                .prepare("SELECT id, name FROM users ORDER BY name")?
+                .query_map([], |row| {
+                    Ok(User {
+                        id: row.get("id")?,
+                        name: row.get("name")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(users)
+        })
+        .await
+    }
+
+    async fn mark_caught(&self, user_id: i32, pokemon: &CaughtPokemon) -> Result<(), Error> {
+        let pokemon = pokemon.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO user_pokemon (user_id, pokedex_number, form, shiny, game, caught_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ON CONFLICT (user_id, pokedex_number, form, shiny) DO UPDATE
+                SET game = excluded.game, caught_at = excluded.caught_at",
+                params![
+                    user_id,
+                    pokemon.pokedex_number,
+                    pokemon.form,
+                    pokemon.shiny,
+                    pokemon.game,
+                    pokemon.caught_at
+                ],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn unmark_caught(
+        &self,
+        user_id: i32,
+        pokedex_number: i32,
+        form: &str,
+        shiny: bool,
+    ) -> Result<bool, Error> {
+        let form = form.to_string();
+        self.run(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM user_pokemon
+                WHERE user_id = ?1 AND pokedex_number = ?2 AND form = ?3 AND shiny = ?4",
+                params![user_id, pokedex_number, form, shiny],
+            )?;
+
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
+    async fn list_caught(&self, user_id: i32) -> Result<Vec<CaughtPokemon>, Error> {
+        self.run(move |conn| {
+            let pokemon = conn
+                .prepare(
+                    "SELECT pokedex_number, form, shiny, game, caught_at FROM user_pokemon
+                    WHERE user_id = ?1 ORDER BY pokedex_number, form, shiny",
+                )?
+                .query_map([user_id], |row| {
+                    Ok(CaughtPokemon {
+                        pokedex_number: row.get("pokedex_number")?,
+                        form: row.get("form")?,
+                        shiny: row.get("shiny")?,
+                        game: row.get("game")?,
+                        caught_at: row.get("caught_at")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(pokemon)
+        })
+        .await
+    }
+
+    async fn upsert_learnset(&self, learnset: &Learnset) -> Result<(), Error> {
+        let learnset = learnset.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM pokemon_moves WHERE pokedex_number = ?1",
+                [learnset.pokedex_number],
+            )?;
+            for learned in &learnset.moves {
+                tx.execute(
+                    "INSERT INTO pokemon_moves (pokedex_number, move_name, version_group, method, level)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        learnset.pokedex_number,
+                        learned.move_name,
+                        learned.version_group,
+                        learned.method,
+                        learned.level
+                    ],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_learnset(&self, pokedex_number: i32) -> Result<Learnset, Error> {
+        self.run(move |conn| {
+            let moves = conn
+                .prepare(
+                    "SELECT pokedex_number, move_name, version_group, method, level FROM pokemon_moves
+                    WHERE pokedex_number = ?1 ORDER BY move_name, version_group, method",
+                )?
+                .query_map([pokedex_number], |row| {
+                    learned_move_from_row(row).map(|(_, learned)| learned)
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(Learnset {
+                pokedex_number,
+                moves,
+            })
+        })
+        .await
+    }
+
+    async fn list_learnsets(&self) -> Result<Vec<Learnset>, Error> {
+        self.run(move |conn| {
+            let rows = conn
+                .prepare(
+                    "SELECT pokedex_number, move_name, version_group, method, level FROM pokemon_moves
+                    ORDER BY pokedex_number, move_name, version_group, method",
+                )?
+                .query_map([], learned_move_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut learnsets: Vec<Learnset> = Vec::new();
+            for (pokedex_number, learned) in rows {
+                match learnsets.last_mut() {
+                    Some(learnset) if learnset.pokedex_number == pokedex_number => {
+                        learnset.moves.push(learned)
+                    }
+                    _ => learnsets.push(Learnset {
+                        pokedex_number,
+                        moves: vec![learned],
+                    }),
+                }
+            }
+
+            Ok(learnsets)
+        })
+        .await
+    }
+
+    async fn save_team(&self, user_id: i32, team: &Team) -> Result<(), Error> {
+        let team = team.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            // The members and their moves are deleted with the old team
+            tx.execute(
+                "DELETE FROM teams WHERE user_id = ?1 AND name = ?2",
+                params![user_id, team.name],
+            )?;
+            let team_id: i32 = tx.query_row(
+                "INSERT INTO teams (user_id, name, game) VALUES (?1, ?2, ?3) RETURNING id",
+                params![user_id, team.name, team.game],
+                |row| row.get(0),
+            )?;
+
+            for (slot, member) in team.members.iter().enumerate() {
+                let slot = slot as i32 + 1;
+                tx.execute(
+                    "INSERT INTO team_members (team_id, slot, pokedex_number, species, form, nickname, gender, item, ability, nature, level, shiny,
+                        ev_hp, ev_attack, ev_defense, ev_special_attack, ev_special_defense, ev_speed,
+                        iv_hp, iv_attack, iv_defense, iv_special_attack, iv_special_defense, iv_speed)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)",
+                    params![
+                        team_id,
+                        slot,
+                        member.pokedex_number,
+                        member.species,
+                        member.form,
+                        member.nickname,
+                        member.gender,
+                        member.item,
+                        member.ability,
+                        member.nature,
+                        member.level,
+                        member.shiny,
+                        member.evs.hp,
+                        member.evs.attack,
+                        member.evs.defense,
+                        member.evs.special_attack,
+                        member.evs.special_defense,
+                        member.evs.speed,
+                        member.ivs.hp,
+                        member.ivs.attack,
+                        member.ivs.defense,
+                        member.ivs.special_attack,
+                        member.ivs.special_defense,
+                        member.ivs.speed
+                    ],
+                )?;
+                for (position, move_name) in member.moves.iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO team_member_moves (team_id, slot, position, move_name)
+                        VALUES (?1, ?2, ?3, ?4)",
+                        params![team_id, slot, position as i32 + 1, move_name],
+                    )?;
+                }
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_teams(&self, user_id: i32) -> Result<Vec<Team>, Error> {
+        self.run(move |conn| {
+            let mut moves: HashMap<(i32, i32), Vec<String>> = HashMap::new();
+            let move_rows = conn
+                .prepare(
+                    "SELECT team_id, slot, move_name FROM team_member_moves
+                    JOIN teams ON teams.id = team_member_moves.team_id
+                    WHERE teams.user_id = ?1 ORDER BY team_id, slot, position",
+                )?
+                .query_map([user_id], |row| {
+                    Ok((
+                        (row.get("team_id")?, row.get("slot")?),
+                        row.get("move_name")?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (key, move_name) in move_rows {
+                moves.entry(key).or_default().push(move_name);
+            }
+
+            let mut members: HashMap<i32, Vec<TeamMember>> = HashMap::new();
+            let member_rows = conn
+                .prepare(
+                    "SELECT team_members.* FROM team_members
+                    JOIN teams ON teams.id = team_members.team_id
+                    WHERE teams.user_id = ?1 ORDER BY team_id, slot",
+                )?
+                .query_map([user_id], |row| {
+                    let key: (i32, i32) = (row.get("team_id")?, row.get("slot")?);
+                    Ok((
+                        key,
+                        TeamMember {
+                            pokedex_number: row.get("pokedex_number")?,
+                            species: row.get("species")?,
+                            form: row.get("form")?,
+                            nickname: row.get("nickname")?,
+                            gender: row.get("gender")?,
+                            item: row.get("item")?,
+                            ability: row.get("ability")?,
+                            nature: row.get("nature")?,
+                            level: row.get("level")?,
+                            shiny: row.get("shiny")?,
+                            evs: stat_spread(row, "ev")?,
+                            ivs: stat_spread(row, "iv")?,
+                            moves: Vec::new(),
+                        },
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (key, mut member) in member_rows {
+                member.moves = moves.remove(&key).unwrap_or_default();
+                members.entry(key.0).or_default().push(member);
+            }
+
+            let teams = conn
+                .prepare("SELECT id, name, game FROM teams WHERE user_id = ?1 ORDER BY name")?
+                .query_map([user_id], |row| {
+                    Ok(Team {
+                        name: row.get("name")?,
+                        game: row.get("game")?,
+                        members: members.remove(&row.get("id")?).unwrap_or_default(),
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(teams)
+        })
+        .await
+    }
+
+    async fn delete_team(&self, user_id: i32, name: &str) -> Result<bool, Error> {
+        let name = name.to_string();
+        self.run(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM teams WHERE user_id = ?1 AND name = ?2",
+                params![user_id, name],
+            )?;
+
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
+    async fn save_annotation(&self, user_id: i32, annotation: &Annotation) -> Result<(), Error> {
+        let annotation = annotation.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            let number = annotation.pokedex_number;
+            for table in ["user_favorites", "user_tags", "user_notes"] {
+                tx.execute(
+                    &format!("DELETE FROM {table} WHERE user_id = ?1 AND pokedex_number = ?2"),
+                    [user_id, number],
+                )?;
+            }
+
+            if annotation.favorite {
+                tx.execute(
+                    "INSERT INTO user_favorites (user_id, pokedex_number) VALUES (?1, ?2)",
+                    [user_id, number],
+                )?;
+            }
+            for tag in &annotation.tags {
+                tx.execute(
+                    "INSERT INTO user_tags (user_id, pokedex_number, tag) VALUES (?1, ?2, ?3)
+                    ON CONFLICT DO NOTHING",
+                    params![user_id, number, tag],
+                )?;
+            }
+            if let Some(note) = &annotation.note {
+                tx.execute(
+                    "INSERT INTO user_notes (user_id, pokedex_number, note, updated_at)
+                    VALUES (?1, ?2, ?3, ?4)",
+                    params![user_id, number, note.text, note.updated_at],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_annotations(&self, user_id: i32) -> Result<Vec<Annotation>, Error> {
+        self.run(move |conn| {
+            let favorites = conn
+                .prepare("SELECT pokedex_number FROM user_favorites WHERE user_id = ?1")?
+                .query_map([user_id], |row| row.get::<_, i32>("pokedex_number"))?
+                .collect::<Result<Vec<_>, _>>()?;
+            let tags = conn
+                .prepare("SELECT pokedex_number, tag FROM user_tags WHERE user_id = ?1")?
+                .query_map([user_id], |row| {
+                    Ok((row.get::<_, i32>("pokedex_number")?, row.get("tag")?))
+                })?
+                .collect::<Result<Vec<(i32, String)>, _>>()?;
+            let notes = conn
+                .prepare(
+                    "SELECT pokedex_number, note, updated_at FROM user_notes WHERE user_id = ?1",
+                )?
+                .query_map([user_id], |row| {
+                    Ok((
+                        row.get::<_, i32>("pokedex_number")?,
+                        Note {
+                            text: row.get("note")?,
+                            updated_at: row.get("updated_at")?,
+                        },
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(group_annotations(favorites, tags, notes))
+        })
+        .await
+    }
+
+    async fn list_raw_documents(&self) -> Result<Vec<RawDocument>, Error> {
+        self.run(|conn| {
+            let documents = conn
+                .prepare(
+                    "SELECT url, fetched_at, content_hash, body FROM raw_documents
+                    ORDER BY fetched_at, id",
+                )?
+                .query_map([], |row| {
+                    Ok(RawDocument {
+                        url: row.get("url")?,
+                        fetched_at: row.get("fetched_at")?,
+                        content_hash: row.get("content_hash")?,
+                        body: row.get("body")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(documents)
+        })
+        .await
+    }
+
+    async fn clear_tables(&self) -> Result<(), Error> {
+        self.run(|conn| {
+            let tx = conn.transaction()?;
+            for table in TABLES {
+                tx.execute(&format!("DELETE FROM {table}"), [])?;
+            }
+            // The AUTOINCREMENT counters, so the ids of the import start again from 1
+            tx.execute("DELETE FROM sqlite_sequence", [])?;
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn has_data(&self) -> Result<bool, Error> {
+        self.run(|conn| Ok(conn.query_row(&has_data_sql(), [], |row| row.get(0))?))
+            .await
+    }
+
+    async fn upsert_form(&self, form: &PokemonForm) -> Result<(), Error> {
+        let form = form.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            // The abilities and moves are deleted with the old form
+            tx.execute(
+                "DELETE FROM pokemon_forms WHERE pokedex_number = ?1 AND form = ?2",
+                params![form.pokedex_number, form.form],
+            )?;
+            tx.execute(
+                "INSERT INTO pokemon_forms (pokedex_number, form) VALUES (?1, ?2)",
+                params![form.pokedex_number, form.form],
+            )?;
+            for ability in &form.abilities {
+                tx.execute(
+                    "INSERT INTO pokemon_form_abilities (pokedex_number, form, ability, is_hidden)
+                    VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        form.pokedex_number,
+                        form.form,
+                        ability.name,
+                        ability.is_hidden
+                    ],
+                )?;
+            }
+            for learned in &form.moves {
+                tx.execute(
+                    "INSERT INTO pokemon_form_moves
+                    (pokedex_number, form, move_name, version_group, method, level)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        form.pokedex_number,
+                        form.form,
+                        learned.move_name,
+                        learned.version_group,
+                        learned.method,
+                        learned.level
+                    ],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_forms(&self, pokedex_number: i32) -> Result<Vec<PokemonForm>, Error> {
+        self.run(move |conn| Ok(load_forms(conn, Some(pokedex_number))?))
+            .await
+    }
+
+    async fn list_forms(&self) -> Result<Vec<PokemonForm>, Error> {
+        self.run(|conn| Ok(load_forms(conn, None)?)).await
+    }
+}
@@ -0,0 +1,410 @@
+use crate::db::query::{generation_sql, Pokemon, Stat};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+// The summaries kept in the database, materialized views in PostgreSQL and tables in SQLite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SummaryView {
+    // One row per type, a pokemon with two types counts in both
+    Types,
+    // One row per combination of types, e.g. "grass, poison" or "fire"
+    TypeCombinations,
+    // One row per generation
+    Generations,
+}
+
+impl SummaryView {
+    pub const ALL: [SummaryView; 3] = [
+        SummaryView::Types,
+        SummaryView::TypeCombinations,
+        SummaryView::Generations,
+    ];
+
+    pub fn view_name(&self) -> &'static str {
+        match self {
+            SummaryView::Types => "type_stats",
+            SummaryView::TypeCombinations => "type_combination_stats",
+            SummaryView::Generations => "generation_stats",
+        }
+    }
+
+    // The column the pokemon are grouped by
+    pub fn group_column(&self) -> &'static str {
+        match self {
+            SummaryView::Types => "type_name",
+            SummaryView::TypeCombinations => "types",
+            SummaryView::Generations => "generation",
+        }
+    }
+}
+
+impl FromStr for SummaryView {
+    type Err = String;
+
+    fn from_str(view: &str) -> Result<Self, Self::Err> {
+        match view {
+            "types" => Ok(SummaryView::Types),
+            "combinations" => Ok(SummaryView::TypeCombinations),
+            "generations" => Ok(SummaryView::Generations),
+            _ => Err(format!(
+                "Unknown summary '{view}'. Use types, combinations or generations"
+            )),
+        }
+    }
+}
+
+// A mean or a median of every stat of a group
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StatValues {
+    pub hp: f64,
+    pub attack: f64,
+    pub defense: f64,
+    pub special_attack: f64,
+    pub special_defense: f64,
+    pub speed: f64,
+    pub total: f64,
+}
+
+impl StatValues {
+    pub fn get(&self, stat: Stat) -> f64 {
+        match stat {
+            Stat::Hp => self.hp,
+            Stat::Attack => self.attack,
+            Stat::Defense => self.defense,
+            Stat::SpecialAttack => self.special_attack,
+            Stat::SpecialDefense => self.special_defense,
+            Stat::Speed => self.speed,
+            Stat::Total => self.total,
+        }
+    }
+
+    pub fn set(&mut self, stat: Stat, value: f64) {
+        match stat {
+            Stat::Hp => self.hp = value,
+            Stat::Attack => self.attack = value,
+            Stat::Defense => self.defense = value,
+            Stat::SpecialAttack => self.special_attack = value,
+            Stat::SpecialDefense => self.special_defense = value,
+            Stat::Speed => self.speed = value,
+            Stat::Total => self.total = value,
+        }
+    }
+}
+
+// A row of a summary: the group, how many pokemon it has and the mean and median of their stats
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatSummary {
+    // The type, the combination of types or the generation
+    pub group: String,
+    pub pokemon_count: i64,
+    pub mean: StatValues,
+    pub median: StatValues,
+}
+
+// The columns after the group, in the order of the views and tables
+pub fn stat_columns() -> Vec<String> {
+    let mut columns = vec!["pokemon_count".to_string()];
+    columns.extend(Stat::ALL.iter().map(|stat| format!("mean_{}", stat.name())));
+    columns.extend(
+        Stat::ALL
+            .iter()
+            .map(|stat| format!("median_{}", stat.name())),
+    );
+    columns
+}
+
+// Saved as the comment of the views, the ones of another version are created again
+pub const SUMMARY_VIEWS_VERSION: &str = "pokedb summaries 2";
+
+/* The materialized views of PostgreSQL. Each one has a unique index on the group, so they can be
+refreshed without locking the dashboards that read them*/
+pub fn postgres_views_sql() -> String {
+    let mut aggregates = vec!["COUNT(*) AS pokemon_count".to_string()];
+    aggregates.extend(Stat::ALL.iter().map(|stat| {
+        format!(
+            "AVG({})::DOUBLE PRECISION AS mean_{}",
+            stat.sql_expression(),
+            stat.name()
+        )
+    }));
+    aggregates.extend(Stat::ALL.iter().map(|stat| {
+        format!(
+            "percentile_cont(0.5) WITHIN GROUP (ORDER BY {}) AS median_{}",
+            stat.sql_expression(),
+            stat.name()
+        )
+    }));
+    let aggregates = aggregates.join(",\n                ");
+
+    format!(
+        "
+        CREATE MATERIALIZED VIEW IF NOT EXISTS type_stats AS
+            SELECT t.name AS type_name,
+                {aggregates}
+            FROM pokemon p
+            JOIN pokemon_types pt ON p.id = pt.pokemon_id
+            JOIN types t ON pt.type_id = t.id
+            GROUP BY t.name;
+        CREATE UNIQUE INDEX IF NOT EXISTS type_stats_idx ON type_stats (type_name);
+
+        CREATE MATERIALIZED VIEW IF NOT EXISTS type_combination_stats AS
+            SELECT c.types,
+                {aggregates}
+            FROM pokemon p
+            JOIN (
+                SELECT pt.pokemon_id, string_agg(t.name, ', ' ORDER BY t.name) AS types
+                FROM pokemon_types pt
+                JOIN types t ON pt.type_id = t.id
+                GROUP BY pt.pokemon_id
+            ) c ON p.id = c.pokemon_id
+            GROUP BY c.types;
+        CREATE UNIQUE INDEX IF NOT EXISTS type_combination_stats_idx
+            ON type_combination_stats (types);
+
+        CREATE MATERIALIZED VIEW IF NOT EXISTS generation_stats AS
+            SELECT {generation} AS generation,
+                {aggregates}
+            FROM pokemon p
+            WHERE {generation} IS NOT NULL
+            GROUP BY 1;
+        CREATE UNIQUE INDEX IF NOT EXISTS generation_stats_idx ON generation_stats (generation);
+        {comments}
+        ",
+        generation = generation_sql(),
+        comments = SummaryView::ALL
+            .iter()
+            .map(|view| format!(
+                "COMMENT ON MATERIALIZED VIEW {} IS '{SUMMARY_VIEWS_VERSION}';",
+                view.view_name()
+            ))
+            .collect::<Vec<_>>()
+            .join("\n        ")
+    )
+}
+
+// SQLite doesn't have materialized views, the summaries are tables filled by the refresh
+pub fn sqlite_tables_sql() -> String {
+    let columns: Vec<String> = stat_columns()
+        .iter()
+        .map(|column| {
+            if column == "pokemon_count" {
+                format!("{column} INTEGER NOT NULL")
+            } else {
+                format!("{column} REAL NOT NULL")
+            }
+        })
+        .collect();
+    let columns = columns.join(", ");
+
+    SummaryView::ALL
+        .iter()
+        .map(|view| {
+            let group_type = match view {
+                SummaryView::Generations => "INTEGER",
+                _ => "TEXT",
+            };
+            format!(
+                "CREATE TABLE IF NOT EXISTS {} ({} {group_type} PRIMARY KEY, {columns});",
+                view.view_name(),
+                view.group_column()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Like percentile_cont(0.5), the mean of the two values in the middle when there's an even count
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+/* Calculating a summary in Rust, for the backends without materialized views. The groups are
+sorted like the SQL ones*/
+pub fn summarize(pokemon: &[Pokemon], view: SummaryView) -> Vec<StatSummary> {
+    let mut groups: BTreeMap<String, Vec<&Pokemon>> = BTreeMap::new();
+    for p in pokemon {
+        match view {
+            SummaryView::Types => {
+                for type_name in &p.types {
+                    groups.entry(type_name.clone()).or_default().push(p);
+                }
+            }
+            SummaryView::TypeCombinations => {
+                if !p.types.is_empty() {
+                    groups.entry(p.types.join(", ")).or_default().push(p);
+                }
+            }
+            SummaryView::Generations => {
+                if let Some(generation) = p.generation {
+                    groups.entry(generation.to_string()).or_default().push(p);
+                }
+            }
+        }
+    }
+
+    let mut summaries: Vec<StatSummary> = groups
+        .into_iter()
+        .map(|(group, members)| {
+            let mut mean = StatValues::default();
+            let mut middle = StatValues::default();
+            for stat in Stat::ALL {
+                let mut values: Vec<f64> =
+                    members.iter().map(|p| p.stats.get(stat) as f64).collect();
+                mean.set(stat, values.iter().sum::<f64>() / values.len() as f64);
+                middle.set(stat, median(&mut values));
+            }
+            StatSummary {
+                group,
+                pokemon_count: members.len() as i64,
+                mean,
+                median: middle,
+            }
+        })
+        .collect();
+    if view == SummaryView::Generations {
+        summaries.sort_by_key(|summary| summary.group.parse::<i32>().unwrap_or_default());
+    }
+
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory_store::MemoryStore;
+    use crate::db::pokemon_tables::{AbilityRecord, PokemonRecord};
+    use crate::db::sqlite_store::SqliteStore;
+    use crate::db::store::PokedexStore;
+    use std::path::Path;
+
+    fn record(pokedex_number: i32, name: &str, types: &[&str], speed: i32) -> PokemonRecord {
+        PokemonRecord {
+            pokedex_number,
+            name: name.to_string(),
+            height: "1.00".to_string(),
+            weight: "10.00".to_string(),
+            hp: 50,
+            attack: 50,
+            defense: 50,
+            special_attack: 50,
+            special_defense: 50,
+            speed,
+            types: types.iter().map(|name| name.to_string()).collect(),
+            abilities: vec![AbilityRecord {
+                name: "overgrow".to_string(),
+                is_hidden: false,
+            }],
+        }
+    }
+
+    /* Three fire and three grass pokemon for the odd counts, two poison ones for the even count.
+    Chikorita is the only one of the generation 2*/
+    fn records() -> Vec<PokemonRecord> {
+        vec![
+            record(1, "bulbasaur", &["grass", "poison"], 45),
+            record(2, "ivysaur", &["grass", "poison"], 60),
+            record(4, "charmander", &["fire"], 65),
+            record(5, "charmeleon", &["fire"], 80),
+            record(6, "charizard", &["fire", "flying"], 100),
+            record(152, "chikorita", &["grass"], 45),
+        ]
+    }
+
+    fn speeds(summaries: &[StatSummary]) -> Vec<(&str, i64, f64, f64)> {
+        summaries
+            .iter()
+            .map(|summary| {
+                (
+                    summary.group.as_str(),
+                    summary.pokemon_count,
+                    summary.mean.speed,
+                    summary.median.speed,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_median_is_the_one_of_percentile_cont() {
+        assert_eq!(median(&mut [80.0, 65.0, 100.0]), 80.0);
+        assert_eq!(median(&mut [60.0, 45.0]), 52.5);
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), 2.5);
+        assert_eq!(median(&mut [7.0]), 7.0);
+    }
+
+    #[test]
+    fn summarizes_by_type_combination_and_generation() {
+        let pokemon: Vec<Pokemon> = records()
+            .iter()
+            .enumerate()
+            .map(|(id, record)| Pokemon::from_record(id as i32, record, None))
+            .collect();
+
+        assert_eq!(
+            speeds(&summarize(&pokemon, SummaryView::Types)),
+            [
+                ("fire", 3, 245.0 / 3.0, 80.0),
+                ("flying", 1, 100.0, 100.0),
+                ("grass", 3, 50.0, 45.0),
+                ("poison", 2, 52.5, 52.5),
+            ]
+        );
+        assert_eq!(
+            speeds(&summarize(&pokemon, SummaryView::TypeCombinations)),
+            [
+                ("fire", 2, 72.5, 72.5),
+                ("fire, flying", 1, 100.0, 100.0),
+                ("grass", 1, 45.0, 45.0),
+                ("grass, poison", 2, 52.5, 52.5),
+            ]
+        );
+        let generations = summarize(&pokemon, SummaryView::Generations);
+        assert_eq!(
+            speeds(&generations),
+            [("1", 5, 70.0, 65.0), ("2", 1, 45.0, 45.0)]
+        );
+        assert_eq!(generations[0].median.total, 315.0);
+    }
+
+    // The summaries of the backends without materialized views, read back after the refresh
+    async fn check_summaries(store: &dyn PokedexStore) {
+        store.create_tables().await.unwrap();
+        store.reset_types_table().await.unwrap();
+        for record in records() {
+            store.upsert_pokemon(&record).await.unwrap();
+        }
+        store.refresh_summaries().await.unwrap();
+
+        let types = store.load_summary(SummaryView::Types).await.unwrap();
+        assert_eq!(
+            speeds(&types),
+            [
+                ("fire", 3, 245.0 / 3.0, 80.0),
+                ("flying", 1, 100.0, 100.0),
+                ("grass", 3, 50.0, 45.0),
+                ("poison", 2, 52.5, 52.5),
+            ]
+        );
+        let generations = store.load_summary(SummaryView::Generations).await.unwrap();
+        assert_eq!(
+            speeds(&generations),
+            [("1", 5, 70.0, 65.0), ("2", 1, 45.0, 45.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn the_memory_store_has_the_medians_of_postgres() {
+        check_summaries(&MemoryStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn the_sqlite_store_has_the_medians_of_postgres() {
+        check_summaries(&SqliteStore::open(Path::new(":memory:")).unwrap()).await;
+    }
+}
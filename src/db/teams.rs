@@ -0,0 +1,1018 @@
+use crate::db::display_names::{name_from_slug, DisplayNames};
+use crate::db::name_search::{fuzzy_matches, normalize};
+use crate::db::pokemon_tables::{Learnset, PokemonForm, PokemonName};
+use crate::db::query::Pokemon;
+use crate::db::store::PokedexStore;
+use crate::error::Error;
+use deunicode::deunicode;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// The pokemon of a team, like in the games
+pub const TEAM_SIZE: usize = 6;
+const MAX_MOVES: usize = 4;
+const MAX_EV: i32 = 252;
+const MAX_EV_TOTAL: i32 = 510;
+const MAX_IV: i32 = 31;
+const MAX_LEVEL: i32 = 100;
+
+pub const NATURES: [&str; 25] = [
+    "hardy", "lonely", "brave", "adamant", "naughty", "bold", "docile", "relaxed", "impish", "lax",
+    "timid", "hasty", "serious", "jolly", "naive", "modest", "mild", "quiet", "bashful", "rash",
+    "calm", "gentle", "sassy", "careful", "quirky",
+];
+
+// The lines of Showdown that pokedb doesn't save, they are skipped in the import
+const IGNORED_KEYS: [&str; 6] = [
+    "Tera Type",
+    "Happiness",
+    "Dynamax Level",
+    "Gigantamax",
+    "Pokeball",
+    "Hidden Power",
+];
+
+// The EVs or the IVs of a pokemon
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StatSpread {
+    pub hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub special_attack: i32,
+    pub special_defense: i32,
+    pub speed: i32,
+}
+
+impl StatSpread {
+    // Every stat with the same value, 0 for the EVs and 31 for the IVs when they aren't given
+    pub fn filled(value: i32) -> Self {
+        StatSpread {
+            hp: value,
+            attack: value,
+            defense: value,
+            special_attack: value,
+            special_defense: value,
+            speed: value,
+        }
+    }
+
+    // The stats with their name in Showdown, e.g. ("SpA", 252)
+    pub fn values(&self) -> [(&'static str, i32); 6] {
+        [
+            ("HP", self.hp),
+            ("Atk", self.attack),
+            ("Def", self.defense),
+            ("SpA", self.special_attack),
+            ("SpD", self.special_defense),
+            ("Spe", self.speed),
+        ]
+    }
+
+    fn get_mut(&mut self, label: &str) -> Option<&mut i32> {
+        match label.to_lowercase().as_str() {
+            "hp" => Some(&mut self.hp),
+            "atk" => Some(&mut self.attack),
+            "def" => Some(&mut self.defense),
+            "spa" => Some(&mut self.special_attack),
+            "spd" => Some(&mut self.special_defense),
+            "spe" => Some(&mut self.speed),
+            _ => None,
+        }
+    }
+
+    pub fn total(&self) -> i32 {
+        self.values().iter().map(|(_, value)| value).sum()
+    }
+
+    // "252 Atk / 4 SpD / 252 Spe" into the spread, the stats that aren't there keep their value
+    fn parse(text: &str, default: i32) -> Result<Self, String> {
+        let mut spread = StatSpread::filled(default);
+        for part in text.split('/') {
+            let (value, label) = part
+                .trim()
+                .split_once(' ')
+                .ok_or_else(|| format!("Expected e.g. '252 Atk', got '{}'", part.trim()))?;
+            let value = value
+                .parse()
+                .map_err(|_| format!("Invalid value '{value}' for {label}"))?;
+            *spread
+                .get_mut(label.trim())
+                .ok_or_else(|| format!("Unknown stat '{}'", label.trim()))? = value;
+        }
+
+        Ok(spread)
+    }
+
+    // The stats that aren't the default, e.g. "252 Atk / 4 SpD / 252 Spe"
+    fn to_showdown(self, default: i32) -> Option<String> {
+        let parts: Vec<String> = self
+            .values()
+            .iter()
+            .filter(|(_, value)| *value != default)
+            .map(|(label, value)| format!("{value} {label}"))
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(" / "))
+    }
+}
+
+/* A pokemon of a team. The species, the ability and the moves are saved as the slugs of the API,
+e.g. "mr-mime" or "thunder-punch", the item as it was written*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TeamMember {
+    pub pokedex_number: i32,
+    pub species: String,
+    // The default form is an empty string, like in the collections
+    pub form: String,
+    pub nickname: Option<String>,
+    // "M" or "F"
+    pub gender: Option<String>,
+    pub item: Option<String>,
+    pub ability: Option<String>,
+    pub nature: Option<String>,
+    pub level: i32,
+    pub shiny: bool,
+    pub evs: StatSpread,
+    pub ivs: StatSpread,
+    pub moves: Vec<String>,
+}
+
+impl TeamMember {
+    fn new(species: String) -> Self {
+        TeamMember {
+            pokedex_number: 0,
+            species,
+            form: String::new(),
+            nickname: None,
+            gender: None,
+            item: None,
+            ability: None,
+            nature: None,
+            level: MAX_LEVEL,
+            shiny: false,
+            evs: StatSpread::filled(0),
+            ivs: StatSpread::filled(MAX_IV),
+            moves: Vec::new(),
+        }
+    }
+}
+
+// A team of a user, the members in their order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Team {
+    pub name: String,
+    // The game the moves are checked for, e.g. "sword", any game without it
+    pub game: Option<String>,
+    pub members: Vec<TeamMember>,
+}
+
+/* The id Showdown and the API use for a name: lowercase, without accents and punctuation and with
+dashes between the words, e.g. "Farfetch’d" is "farfetchd" and "King's Shield" "kings-shield". The
+gender signs are the ones of the slugs, "Nidoran♀" is "nidoran-f"*/
+pub fn showdown_id(name: &str) -> String {
+    let name = name.replace('♀', "-f").replace('♂', "-m");
+    let mut id = String::new();
+    for c in deunicode(&name).to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            id.push(c);
+        } else if (c == ' ' || c == '-' || c == '_') && !id.is_empty() && !id.ends_with('-') {
+            id.push('-');
+        }
+    }
+
+    id.trim_end_matches('-').to_string()
+}
+
+/* Parsing a team in the text format of Pokemon Showdown, the pokemon are separated by empty
+lines. The species are the ids of the names, 'resolve_species' finds their pokedex numbers*/
+pub fn parse_showdown(text: &str) -> Result<Vec<TeamMember>, Error> {
+    let mut members: Vec<TeamMember> = Vec::new();
+    let mut current: Option<TeamMember> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| Error::Input(format!("Line {}: {message}", index + 1));
+        // The header of the teams of a backup, e.g. "=== [gen9ou] Rain ==="
+        if line.starts_with("===") {
+            continue;
+        }
+        if line.is_empty() {
+            members.extend(current.take());
+            continue;
+        }
+        let Some(member) = current.as_mut() else {
+            current = Some(parse_first_line(line).map_err(error)?);
+            continue;
+        };
+
+        if let Some(move_name) = line.strip_prefix('-').or_else(|| line.strip_prefix('~')) {
+            // "Hidden Power [Fire]" is saved as hidden-power
+            let move_name = move_name.split('[').next().unwrap_or_default();
+            member.moves.push(showdown_id(move_name));
+        } else if let Some(nature) = line.strip_suffix(" Nature") {
+            member.nature = Some(nature.trim().to_lowercase());
+        } else if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim() {
+                "Ability" => member.ability = Some(showdown_id(value)),
+                "Level" => {
+                    member.level = value
+                        .parse()
+                        .map_err(|_| error(format!("Invalid level '{value}'")))?
+                }
+                "Shiny" => member.shiny = value.eq_ignore_ascii_case("yes"),
+                "EVs" => member.evs = StatSpread::parse(value, 0).map_err(error)?,
+                "IVs" => member.ivs = StatSpread::parse(value, MAX_IV).map_err(error)?,
+                key if IGNORED_KEYS.contains(&key) => {}
+                key => return Err(error(format!("Unknown line '{key}'"))),
+            }
+        } else {
+            return Err(error(format!("Unknown line '{line}'")));
+        }
+    }
+    members.extend(current);
+
+    if members.is_empty() {
+        return Err(Error::Input(
+            "The team doesn't have any pokemon".to_string(),
+        ));
+    }
+
+    Ok(members)
+}
+
+// "Sparky (Pikachu) (M) @ Light Ball", only the species is required
+fn parse_first_line(line: &str) -> Result<TeamMember, String> {
+    let (name, item) = match line.split_once(" @ ") {
+        Some((name, item)) => (name.trim(), Some(item.trim().to_string())),
+        None => (line, None),
+    };
+    let (name, gender) = match name
+        .strip_suffix(" (M)")
+        .map(|name| (name, "M"))
+        .or_else(|| name.strip_suffix(" (F)").map(|name| (name, "F")))
+    {
+        Some((name, gender)) => (name.trim(), Some(gender.to_string())),
+        None => (name, None),
+    };
+    let (nickname, species) = match name
+        .strip_suffix(')')
+        .and_then(|name| name.rsplit_once(" ("))
+    {
+        Some((nickname, species)) => (Some(nickname.trim().to_string()), species),
+        None => (None, name),
+    };
+
+    let species = showdown_id(species);
+    if species.is_empty() {
+        return Err(format!("Expected the species of a pokemon, got '{line}'"));
+    }
+    let mut member = TeamMember::new(species);
+    member.nickname = nickname;
+    member.gender = gender;
+    member.item = item;
+
+    Ok(member)
+}
+
+/* Finding the pokemon of each member by the slugs and the localized names of the species, so
+"Mimikyu", "Mr. Mime" and "landorus-incarnate" are found. A name that isn't saved is tried without
+its last words as a form, e.g. "landorus-therian" is landorus with the form "therian", and the form
+must be one of the saved forms of the species. The default forms are found by their slug and keep
+an empty form*/
+pub async fn resolve_species(
+    store: &dyn PokedexStore,
+    members: &mut [TeamMember],
+) -> Result<(), Error> {
+    let names = store.list_names().await?;
+    let normalized: Vec<(String, i32)> = names
+        .iter()
+        .map(|name| (normalize(&name.name), name.pokedex_number))
+        .collect();
+
+    for member in members.iter_mut() {
+        let mut species = member.species.as_str();
+        let pokedex_number = loop {
+            let wanted = normalize(species);
+            if let Some((_, pokedex_number)) = normalized.iter().find(|(name, _)| *name == wanted) {
+                break *pokedex_number;
+            }
+            match species.rsplit_once('-') {
+                Some((base, _)) => species = base,
+                None => return Err(unknown_species(&names, &member.species)),
+            }
+        };
+        let pokemon = store
+            .get_by_dex_number(pokedex_number)
+            .await?
+            .ok_or_else(|| unknown_species(&names, &member.species))?;
+
+        let form = member.species[species.len()..].trim_start_matches('-');
+        member.form = if form.is_empty() {
+            String::new()
+        } else {
+            let forms = store.get_forms(pokedex_number).await?;
+            find_form(&forms, form)
+                .ok_or_else(|| unknown_form(&pokemon.name, form, &forms))?
+                .form
+                .clone()
+        };
+        member.pokedex_number = pokemon.pokedex_number;
+        member.species = pokemon.name;
+    }
+
+    Ok(())
+}
+
+/* The saved form with the name, or the only one whose words start with its words, the short names
+of Showdown, e.g. "f" of indeedee is "female" and "original" of pikachu is "original-cap"*/
+fn find_form<'a>(forms: &'a [PokemonForm], form: &str) -> Option<&'a PokemonForm> {
+    if let Some(found) = forms.iter().find(|known| known.form == form) {
+        return Some(found);
+    }
+
+    let words: Vec<&str> = form.split('-').collect();
+    let mut matches = forms.iter().filter(|known| {
+        let known_words: Vec<&str> = known.form.split('-').collect();
+        words.len() <= known_words.len()
+            && words
+                .iter()
+                .zip(&known_words)
+                .all(|(word, known)| known.starts_with(word))
+    });
+    match (matches.next(), matches.next()) {
+        (Some(found), None) => Some(found),
+        _ => None,
+    }
+}
+
+// The error of a form the species doesn't have, with the ones it has
+fn unknown_form(species: &str, form: &str, forms: &[PokemonForm]) -> Error {
+    if forms.is_empty() {
+        return Error::Input(format!(
+            "Unknown form '{form}' of {species}, it doesn't have other forms saved. Run \
+            'pokedb sync' if they are missing"
+        ));
+    }
+
+    let names: Vec<&str> = forms.iter().map(|known| known.form.as_str()).collect();
+    Error::Input(format!(
+        "Unknown form '{form}' of {species}. Its forms are {}",
+        names.join(", ")
+    ))
+}
+
+// The error of a species that isn't saved, with the names that look like it
+fn unknown_species(names: &[PokemonName], species: &str) -> Error {
+    let matches = fuzzy_matches(names, species, 3);
+    if matches.is_empty() {
+        return Error::Input(format!("Unknown pokemon '{species}'"));
+    }
+
+    let suggestions: Vec<&str> = matches.iter().map(|m| m.name.as_str()).collect();
+    Error::Input(format!(
+        "Unknown pokemon '{species}'. Did you mean {}?",
+        suggestions.join(", ")
+    ))
+}
+
+/* The problems of a team with the saved data: the species and their forms, their abilities and the
+moves they can learn, in the version group of the game when the team has one. The forms are by
+pokedex number and name. Empty when it's valid*/
+pub fn validate_team(
+    team: &Team,
+    pokemon: &HashMap<i32, Pokemon>,
+    learnsets: &HashMap<i32, Learnset>,
+    forms: &HashMap<(i32, String), PokemonForm>,
+    version_group: Option<&str>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    if team.members.is_empty() || team.members.len() > TEAM_SIZE {
+        problems.push(format!(
+            "A team has from 1 to {TEAM_SIZE} pokemon, this one has {}",
+            team.members.len()
+        ));
+    }
+
+    for member in &team.members {
+        let label = if member.form.is_empty() {
+            member.species.clone()
+        } else {
+            format!("{} ({})", member.species, member.form)
+        };
+        let mut problem = |message: String| problems.push(format!("{label}: {message}"));
+        let Some(saved) = pokemon.get(&member.pokedex_number) else {
+            problem("the pokemon isn't saved, run 'pokedb sync'".to_string());
+            continue;
+        };
+        let form = if member.form.is_empty() {
+            None
+        } else {
+            match forms.get(&(member.pokedex_number, member.form.clone())) {
+                Some(form) => Some(form),
+                None => {
+                    problem("the form isn't saved, run 'pokedb sync'".to_string());
+                    continue;
+                }
+            }
+        };
+
+        /* The varieties have their own abilities and moves, e.g. Ninetales-Alola. The forms that
+        only change the look have the ones of the species*/
+        let abilities = form
+            .map(|form| &form.abilities)
+            .filter(|abilities| !abilities.is_empty())
+            .unwrap_or(&saved.abilities);
+        if let Some(ability) = &member.ability {
+            if !abilities.iter().any(|known| &known.name == ability) {
+                let abilities: Vec<&str> =
+                    abilities.iter().map(|known| known.name.as_str()).collect();
+                problem(format!(
+                    "it can't have the ability '{ability}', only {}",
+                    abilities.join(", ")
+                ));
+            }
+        }
+
+        if member.moves.len() > MAX_MOVES {
+            problem(format!(
+                "{} moves, the maximum is {MAX_MOVES}",
+                member.moves.len()
+            ));
+        }
+        let form_learnset = form
+            .filter(|form| !form.moves.is_empty())
+            .map(|form| Learnset {
+                pokedex_number: form.pokedex_number,
+                moves: form.moves.clone(),
+            });
+        let learnset = form_learnset
+            .as_ref()
+            .or_else(|| learnsets.get(&member.pokedex_number))
+            .filter(|learnset| !learnset.moves.is_empty());
+        if learnset.is_none() && !member.moves.is_empty() {
+            problem("its learnset isn't saved, run 'pokedb sync'".to_string());
+        }
+        let mut seen = HashSet::new();
+        for move_name in &member.moves {
+            if !seen.insert(move_name) {
+                problem(format!("the move '{move_name}' is repeated"));
+                continue;
+            }
+            if learnset.is_some_and(|learnset| !learnset.can_learn(move_name, version_group)) {
+                let game = version_group.map_or(String::new(), |group| format!(" in {group}"));
+                problem(format!("it can't learn '{move_name}'{game}"));
+            }
+        }
+
+        if let Some(nature) = &member.nature {
+            if !NATURES.contains(&nature.as_str()) {
+                problem(format!("unknown nature '{nature}'"));
+            }
+        }
+        if !(1..=MAX_LEVEL).contains(&member.level) {
+            problem(format!(
+                "the level must be from 1 to {MAX_LEVEL}, not {}",
+                member.level
+            ));
+        }
+        for (label, value) in member.evs.values() {
+            if !(0..=MAX_EV).contains(&value) {
+                problem(format!(
+                    "{value} {label} EVs, they must be from 0 to {MAX_EV}"
+                ));
+            }
+        }
+        if member.evs.total() > MAX_EV_TOTAL {
+            problem(format!(
+                "{} EVs in total, the maximum is {MAX_EV_TOTAL}",
+                member.evs.total()
+            ));
+        }
+        for (label, value) in member.ivs.values() {
+            if !(0..=MAX_IV).contains(&value) {
+                problem(format!(
+                    "{value} {label} IVs, they must be from 0 to {MAX_IV}"
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+/* Checking a team with the saved pokemon, learnsets and forms. The moves are checked for the
+version group of the game of the team*/
+pub async fn check_team(store: &dyn PokedexStore, team: &Team) -> Result<Vec<String>, Error> {
+    let version_group = match &team.game {
+        Some(name) => Some(
+            store
+                .find_game(name)
+                .await?
+                .ok_or_else(|| {
+                    Error::Input(format!(
+                        "Unknown game '{name}'. Run 'pokedb sync' or see 'pokedb games'"
+                    ))
+                })?
+                .group
+                .name,
+        ),
+        None => None,
+    };
+
+    let mut pokemon = HashMap::new();
+    let mut learnsets = HashMap::new();
+    let mut forms = HashMap::new();
+    for member in &team.members {
+        if let Some(saved) = store.get_by_dex_number(member.pokedex_number).await? {
+            pokemon.insert(member.pokedex_number, saved);
+        }
+        let learnset = store.get_learnset(member.pokedex_number).await?;
+        learnsets.insert(member.pokedex_number, learnset);
+        for form in store.get_forms(member.pokedex_number).await? {
+            forms.insert((form.pokedex_number, form.form.clone()), form);
+        }
+    }
+
+    Ok(validate_team(
+        team,
+        &pokemon,
+        &learnsets,
+        &forms,
+        version_group.as_deref(),
+    ))
+}
+
+// The team in the text format of Pokemon Showdown, it can be pasted in its teambuilder
+pub fn format_showdown(team: &Team, names: &DisplayNames) -> String {
+    let mut text = String::new();
+    for member in &team.members {
+        let mut species = names.get(member.pokedex_number, &member.species);
+        if !member.form.is_empty() {
+            species = format!(
+                "{species}-{}",
+                name_from_slug(&member.form).replace(' ', "-")
+            );
+        }
+        let mut first_line = match &member.nickname {
+            Some(nickname) => format!("{nickname} ({species})"),
+            None => species,
+        };
+        if let Some(gender) = &member.gender {
+            first_line = format!("{first_line} ({gender})");
+        }
+        if let Some(item) = &member.item {
+            first_line = format!("{first_line} @ {item}");
+        }
+
+        let mut lines = vec![first_line];
+        if let Some(ability) = &member.ability {
+            lines.push(format!("Ability: {}", name_from_slug(ability)));
+        }
+        if member.level != MAX_LEVEL {
+            lines.push(format!("Level: {}", member.level));
+        }
+        if member.shiny {
+            lines.push("Shiny: Yes".to_string());
+        }
+        if let Some(evs) = member.evs.to_showdown(0) {
+            lines.push(format!("EVs: {evs}"));
+        }
+        if let Some(nature) = &member.nature {
+            lines.push(format!("{} Nature", name_from_slug(nature)));
+        }
+        if let Some(ivs) = member.ivs.to_showdown(MAX_IV) {
+            lines.push(format!("IVs: {ivs}"));
+        }
+        for move_name in &member.moves {
+            lines.push(format!("- {}", name_from_slug(move_name)));
+        }
+
+        text.push_str(&lines.join("\n"));
+        text.push_str("\n\n");
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory_store::MemoryStore;
+    use crate::db::pokemon_tables::{
+        AbilityRecord, LearnedMove, LocalizedName, PokemonRecord, SpeciesRecord,
+    };
+
+    const TEAM: &str = "Sparky (Pikachu) (F) @ Light Ball
+Ability: Static
+Level: 50
+Shiny: Yes
+EVs: 252 SpA / 4 SpD / 252 Spe
+Timid Nature
+IVs: 0 Atk
+- Thunderbolt
+- Volt Switch
+
+Mr. Mime-Galar @ Life Orb
+Ability: Vital Spirit
+- Psychic
+- Ice Beam
+
+";
+
+    fn record(pokedex_number: i32, name: &str, abilities: &[&str]) -> PokemonRecord {
+        PokemonRecord {
+            pokedex_number,
+            name: name.to_string(),
+            height: "0.40".to_string(),
+            weight: "6.00".to_string(),
+            hp: 35,
+            attack: 55,
+            defense: 40,
+            special_attack: 50,
+            special_defense: 50,
+            speed: 90,
+            types: vec!["electric".to_string()],
+            abilities: abilities
+                .iter()
+                .map(|name| AbilityRecord {
+                    name: name.to_string(),
+                    is_hidden: false,
+                })
+                .collect(),
+        }
+    }
+
+    fn pikachu_team(member: TeamMember) -> Team {
+        Team {
+            name: "rain".to_string(),
+            game: None,
+            members: vec![member],
+        }
+    }
+
+    // Pikachu with its abilities and two moves of sword-shield, what 'check_team' loads
+    fn saved_pikachu() -> (HashMap<i32, Pokemon>, HashMap<i32, Learnset>) {
+        let pokemon = Pokemon::from_record(
+            1,
+            &record(25, "pikachu", &["static", "lightning-rod"]),
+            None,
+        );
+        let learnset = Learnset {
+            pokedex_number: 25,
+            moves: ["thunderbolt", "volt-switch"]
+                .iter()
+                .map(|move_name| LearnedMove {
+                    move_name: move_name.to_string(),
+                    version_group: "sword-shield".to_string(),
+                    method: "machine".to_string(),
+                    level: 0,
+                })
+                .collect(),
+        };
+        (
+            HashMap::from([(25, pokemon)]),
+            HashMap::from([(25, learnset)]),
+        )
+    }
+
+    fn pikachu(text: &str) -> TeamMember {
+        let mut member = parse_showdown(text).unwrap().remove(0);
+        member.pokedex_number = 25;
+        member
+    }
+
+    #[test]
+    fn the_ids_are_the_slugs_of_the_api() {
+        assert_eq!(showdown_id("Mr. Mime"), "mr-mime");
+        assert_eq!(showdown_id("Farfetch’d"), "farfetchd");
+        assert_eq!(showdown_id("King's Shield"), "kings-shield");
+        assert_eq!(showdown_id("Nidoran♀"), "nidoran-f");
+        assert_eq!(showdown_id("Flabébé"), "flabebe");
+        assert_eq!(showdown_id("  U-turn "), "u-turn");
+    }
+
+    #[test]
+    fn parses_every_line_of_a_member() {
+        let members = parse_showdown(TEAM).unwrap();
+
+        assert_eq!(members.len(), 2);
+        let sparky = &members[0];
+        assert_eq!(sparky.species, "pikachu");
+        assert_eq!(sparky.nickname.as_deref(), Some("Sparky"));
+        assert_eq!(sparky.gender.as_deref(), Some("F"));
+        assert_eq!(sparky.item.as_deref(), Some("Light Ball"));
+        assert_eq!(sparky.ability.as_deref(), Some("static"));
+        assert_eq!(sparky.level, 50);
+        assert!(sparky.shiny);
+        assert_eq!(sparky.nature.as_deref(), Some("timid"));
+        assert_eq!(sparky.evs.total(), 508);
+        assert_eq!(sparky.evs.speed, 252);
+        assert_eq!(sparky.ivs.attack, 0);
+        assert_eq!(sparky.ivs.speed, MAX_IV);
+        assert_eq!(sparky.moves, ["thunderbolt", "volt-switch"]);
+        assert_eq!(members[1].species, "mr-mime-galar");
+        assert_eq!(members[1].level, MAX_LEVEL);
+    }
+
+    #[test]
+    fn the_export_can_be_imported_again() {
+        let mut members = parse_showdown(TEAM).unwrap();
+        members[0].pokedex_number = 25;
+        // What 'resolve_species' finds for them
+        members[1].pokedex_number = 122;
+        members[1].species = "mr-mime".to_string();
+        members[1].form = "galar".to_string();
+        let team = Team {
+            name: "rain".to_string(),
+            game: None,
+            members,
+        };
+
+        assert_eq!(format_showdown(&team, &DisplayNames::default()), TEAM);
+    }
+
+    #[test]
+    fn skips_the_headers_and_the_lines_pokedb_doesnt_save() {
+        let text = "=== [gen9ou] Rain ===\n\n\
+                    Pelipper @ Damp Rock\n\
+                    Tera Type: Water\n\
+                    Happiness: 0\n\
+                    ~ Hidden Power [Fire]\n";
+        let members = parse_showdown(text).unwrap();
+
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].moves, ["hidden-power"]);
+    }
+
+    #[test]
+    fn the_errors_have_the_line() {
+        let error = |text: &str| parse_showdown(text).unwrap_err().to_string();
+
+        assert_eq!(
+            error("Pikachu\nColor: Yellow"),
+            "Line 2: Unknown line 'Color'"
+        );
+        assert_eq!(
+            error("Pikachu\nLevel: high"),
+            "Line 2: Invalid level 'high'"
+        );
+        assert_eq!(
+            error("Pikachu\nEVs: 252 Luck"),
+            "Line 2: Unknown stat 'Luck'"
+        );
+        assert_eq!(
+            error("Pikachu\nJust a line"),
+            "Line 2: Unknown line 'Just a line'"
+        );
+        assert_eq!(
+            error("\n=== Empty ===\n"),
+            "The team doesn't have any pokemon"
+        );
+    }
+
+    #[test]
+    fn a_valid_team_doesnt_have_problems() {
+        let (pokemon, learnsets) = saved_pikachu();
+        let team = pikachu_team(pikachu(TEAM));
+
+        assert!(validate_team(
+            &team,
+            &pokemon,
+            &learnsets,
+            &HashMap::new(),
+            Some("sword-shield")
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn reports_the_abilities_the_evs_and_the_moves() {
+        let (pokemon, learnsets) = saved_pikachu();
+        let team = pikachu_team(pikachu(
+            "Pikachu\nAbility: Levitate\nEVs: 252 HP / 252 Atk / 252 Spe\n- Surf\n- Surf\n",
+        ));
+        let problems = validate_team(
+            &team,
+            &pokemon,
+            &learnsets,
+            &HashMap::new(),
+            Some("sword-shield"),
+        );
+
+        assert_eq!(
+            problems,
+            [
+                "pikachu: it can't have the ability 'levitate', only lightning-rod, static",
+                "pikachu: it can't learn 'surf' in sword-shield",
+                "pikachu: the move 'surf' is repeated",
+                "pikachu: 756 EVs in total, the maximum is 510",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_moves_are_checked_in_the_version_group() {
+        let (pokemon, learnsets) = saved_pikachu();
+        let team = pikachu_team(pikachu("Pikachu\n- Thunderbolt\n"));
+
+        let forms = HashMap::new();
+        assert!(validate_team(&team, &pokemon, &learnsets, &forms, None).is_empty());
+        assert_eq!(
+            validate_team(&team, &pokemon, &learnsets, &forms, Some("red-blue")),
+            ["pikachu: it can't learn 'thunderbolt' in red-blue"]
+        );
+    }
+
+    /* Two forms of pikachu: rock-star, a variety with its own ability and moves, and gmax, that the
+    API has without them*/
+    fn pikachu_forms() -> HashMap<(i32, String), PokemonForm> {
+        let rock_star = PokemonForm {
+            pokedex_number: 25,
+            form: "rock-star".to_string(),
+            abilities: vec![AbilityRecord {
+                name: "lightning-rod".to_string(),
+                is_hidden: false,
+            }],
+            moves: vec![LearnedMove {
+                move_name: "meteor-mash".to_string(),
+                version_group: "omega-ruby-alpha-sapphire".to_string(),
+                method: "special".to_string(),
+                level: 0,
+            }],
+        };
+        let gmax = PokemonForm {
+            pokedex_number: 25,
+            form: "gmax".to_string(),
+            abilities: Vec::new(),
+            moves: Vec::new(),
+        };
+
+        [rock_star, gmax]
+            .into_iter()
+            .map(|form| ((form.pokedex_number, form.form.clone()), form))
+            .collect()
+    }
+
+    #[test]
+    fn a_form_is_checked_with_its_own_abilities_and_moves() {
+        let (pokemon, learnsets) = saved_pikachu();
+        let mut member = pikachu("Pikachu\nAbility: Static\n- Meteor Mash\n- Thunderbolt\n");
+        member.form = "rock-star".to_string();
+        let problems = validate_team(
+            &pikachu_team(member),
+            &pokemon,
+            &learnsets,
+            &pikachu_forms(),
+            None,
+        );
+
+        assert_eq!(
+            problems,
+            [
+                "pikachu (rock-star): it can't have the ability 'static', only lightning-rod",
+                "pikachu (rock-star): it can't learn 'thunderbolt'",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_form_without_abilities_and_moves_has_the_ones_of_the_species() {
+        let (pokemon, learnsets) = saved_pikachu();
+        let mut member = pikachu("Pikachu\nAbility: Static\n- Thunderbolt\n- Surf\n");
+        member.form = "gmax".to_string();
+        let problems = validate_team(
+            &pikachu_team(member),
+            &pokemon,
+            &learnsets,
+            &pikachu_forms(),
+            None,
+        );
+
+        assert_eq!(problems, ["pikachu (gmax): it can't learn 'surf'"]);
+    }
+
+    #[test]
+    fn a_form_that_isnt_saved_is_a_problem() {
+        let (pokemon, learnsets) = saved_pikachu();
+        let mut member = pikachu("Pikachu\nAbility: Levitate\n- Surf\n");
+        member.form = "xyz".to_string();
+        let problems = validate_team(
+            &pikachu_team(member),
+            &pokemon,
+            &learnsets,
+            &pikachu_forms(),
+            None,
+        );
+
+        assert_eq!(
+            problems,
+            ["pikachu (xyz): the form isn't saved, run 'pokedb sync'"]
+        );
+    }
+
+    #[test]
+    fn a_pokemon_that_isnt_saved_is_a_problem() {
+        let team = pikachu_team(pikachu("Pikachu\n"));
+        let problems = validate_team(
+            &team,
+            &HashMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+            None,
+        );
+
+        assert_eq!(
+            problems,
+            ["pikachu: the pokemon isn't saved, run 'pokedb sync'"]
+        );
+    }
+
+    #[tokio::test]
+    async fn resolves_the_species_by_their_names() {
+        let store = MemoryStore::new();
+        store.reset_types_table().await.unwrap();
+        for (pokedex_number, slug, name) in [
+            (25, "pikachu", "Pikachu"),
+            (122, "mr-mime", "Mr. Mime"),
+            (778, "mimikyu-disguised", "Mimikyu"),
+            (445, "garchomp", "Garchomp"),
+        ] {
+            store
+                .upsert_pokemon(&record(pokedex_number, slug, &[]))
+                .await
+                .unwrap();
+            store
+                .upsert_species(&SpeciesRecord {
+                    pokedex_number,
+                    generation: None,
+                    names: vec![LocalizedName {
+                        language: "en".to_string(),
+                        name: name.to_string(),
+                    }],
+                })
+                .await
+                .unwrap();
+        }
+
+        for (pokedex_number, form) in [
+            (122, "galar"),
+            (25, "alola-cap"),
+            (25, "original-cap"),
+            (445, "mega"),
+        ] {
+            store
+                .upsert_form(&PokemonForm {
+                    pokedex_number,
+                    form: form.to_string(),
+                    abilities: Vec::new(),
+                    moves: Vec::new(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let mut members =
+            parse_showdown("Mimikyu\n\nMr. Mime-Galar\n\nPikachu-Alola-Cap\n\nPikachu-Original\n")
+                .unwrap();
+        resolve_species(&store, &mut members).await.unwrap();
+        let found: Vec<(i32, &str, &str)> = members
+            .iter()
+            .map(|m| (m.pokedex_number, m.species.as_str(), m.form.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (778, "mimikyu-disguised", ""),
+                (122, "mr-mime", "galar"),
+                (25, "pikachu", "alola-cap"),
+                // The short name of Showdown
+                (25, "pikachu", "original-cap"),
+            ]
+        );
+
+        let error = |text: &str| {
+            let store = &store;
+            let mut members = parse_showdown(text).unwrap();
+            async move { resolve_species(store, &mut members).await.unwrap_err() }
+        };
+        assert_eq!(
+            error("Garchomp-Xyz\n").await.to_string(),
+            "Unknown form 'xyz' of garchomp. Its forms are mega"
+        );
+        assert_eq!(
+            error("Mimikyu-Busted\n").await.to_string(),
+            "Unknown form 'busted' of mimikyu-disguised, it doesn't have other forms saved. Run \
+            'pokedb sync' if they are missing"
+        );
+        // "cap" starts the words of two forms, so it's neither
+        assert!(matches!(error("Pikachu-Cap\n").await, Error::Input(_)));
+
+        let mut typo = parse_showdown("Pikchu\n").unwrap();
+        let error = resolve_species(&store, &mut typo).await.unwrap_err();
+        assert!(matches!(&error, Error::Input(_)));
+        assert_eq!(
+            error.to_string(),
+            "Unknown pokemon 'pikchu'. Did you mean pikachu?"
+        );
+    }
+}
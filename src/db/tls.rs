@@ -0,0 +1,331 @@
+use crate::config::SslConfig;
+use crate::error::Error;
+use native_tls::{Certificate, Identity, TlsConnector};
+use percent_encoding::percent_decode_str;
+use postgres_native_tls::MakeTlsConnector;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio_postgres::config::SslMode as PgSslMode;
+
+// Same modes than libpq
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum SslMode {
+    // Never use TLS
+    Disable,
+    // Use TLS if the server supports it, like libpq does by default
+    #[default]
+    Prefer,
+    // Always use TLS, the certificate is only checked if a root certificate is given
+    Require,
+    // Always use TLS and check the certificate was signed by a trusted CA
+    VerifyCa,
+    // Like VerifyCa and the host name must be the one of the certificate
+    VerifyFull,
+}
+
+impl FromStr for SslMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-ca" => Ok(SslMode::VerifyCa),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            _ => Err(format!(
+                "Invalid sslmode '{mode}'. Use disable, prefer, require, verify-ca or verify-full"
+            )),
+        }
+    }
+}
+
+// For 'sslmode' in the config files
+impl TryFrom<String> for SslMode {
+    type Error = String;
+
+    fn try_from(mode: String) -> Result<Self, Self::Error> {
+        mode.parse()
+    }
+}
+
+impl SslMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyCa => "verify-ca",
+            SslMode::VerifyFull => "verify-full",
+        }
+    }
+}
+
+// How the connections with PostgreSQL are encrypted
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TlsSettings {
+    pub mode: SslMode,
+    // PEM file with the CA that signed the server certificate, without it the system CAs are used
+    pub root_cert: Option<PathBuf>,
+    // PEM files of the client certificate and its PKCS#8 key, for servers that ask for them
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+impl TlsSettings {
+    /* Reading the settings from the query of the database URL, e.g.
+    postgresql://user@host/db?sslmode=verify-full&sslrootcert=ca.pem, then from PGSSLMODE,
+    PGSSLROOTCERT, PGSSLCERT and PGSSLKEY and then from the config files. The values of the URL
+    are percent-decoded, e.g. "sslrootcert=/certs/my%20ca.pem". The URL is returned without these
+    parameters because tokio-postgres only knows some of them*/
+    pub fn from_url_and_env(
+        database_url: &str,
+        config: &SslConfig,
+    ) -> Result<(Self, String), String> {
+        let (base_url, query) = match database_url.split_once('?') {
+            Some((base_url, query)) => (base_url, query),
+            None => (database_url, ""),
+        };
+
+        let mut settings = TlsSettings::default();
+        let mut mode = env::var("PGSSLMODE").ok();
+        let mut root_cert = env::var("PGSSLROOTCERT").ok();
+        let mut client_cert = env::var("PGSSLCERT").ok();
+        let mut client_key = env::var("PGSSLKEY").ok();
+        let mut other_params = Vec::new();
+
+        // The URL wins over the environment, like in libpq
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let decode = || {
+                percent_decode_str(value)
+                    .decode_utf8()
+                    .map(|value| value.into_owned())
+                    .map_err(|e| format!("Invalid {key} in the database URL: {e}"))
+            };
+            match key {
+                "sslmode" => mode = Some(decode()?),
+                "sslrootcert" => root_cert = Some(decode()?),
+                "sslcert" => client_cert = Some(decode()?),
+                "sslkey" => client_key = Some(decode()?),
+                _ => other_params.push(param),
+            }
+        }
+
+        settings.mode = match mode.filter(|mode| !mode.is_empty()) {
+            Some(mode) => mode.parse()?,
+            None => config.mode.unwrap_or_default(),
+        };
+        let path = |value: Option<String>, config: &Option<PathBuf>| {
+            value
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| config.clone())
+        };
+        settings.root_cert = path(root_cert, &config.root_cert);
+        settings.client_cert = path(client_cert, &config.client_cert);
+        settings.client_key = path(client_key, &config.client_key);
+
+        if settings.client_cert.is_some() != settings.client_key.is_some() {
+            return Err("sslcert and sslkey must be set together".to_string());
+        }
+
+        let url = if other_params.is_empty() {
+            base_url.to_string()
+        } else {
+            format!("{base_url}?{}", other_params.join("&"))
+        };
+
+        Ok((settings, url))
+    }
+
+    // The mode that tokio-postgres understands, the verification is done by the connector
+    pub fn pg_ssl_mode(&self) -> PgSslMode {
+        match self.mode {
+            SslMode::Disable => PgSslMode::Disable,
+            SslMode::Prefer => PgSslMode::Prefer,
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => PgSslMode::Require,
+        }
+    }
+
+    // Creating the TLS connector with the certificates of the settings
+    pub fn connector(&self) -> Result<MakeTlsConnector, Error> {
+        let mut builder = TlsConnector::builder();
+
+        if let Some(path) = &self.root_cert {
+            let pem = fs::read(path).map_err(|e| {
+                Error::Config(format!("Can't read sslrootcert {}: {e}", path.display()))
+            })?;
+            let root = Certificate::from_pem(&pem).map_err(|e| {
+                Error::Config(format!("Invalid sslrootcert {}: {e}", path.display()))
+            })?;
+            builder.add_root_certificate(root);
+        }
+
+        if let (Some(cert_path), Some(key_path)) = (&self.client_cert, &self.client_key) {
+            let cert = fs::read(cert_path).map_err(|e| {
+                Error::Config(format!("Can't read sslcert {}: {e}", cert_path.display()))
+            })?;
+            let key = fs::read(key_path).map_err(|e| {
+                Error::Config(format!("Can't read sslkey {}: {e}", key_path.display()))
+            })?;
+            let identity = Identity::from_pkcs8(&cert, &key)
+                .map_err(|e| Error::Config(format!("Invalid sslcert or sslkey: {e}")))?;
+            builder.identity(identity);
+        }
+
+        match self.mode {
+            // Like libpq, 'prefer' and 'require' only check the certificate with a root cert
+            SslMode::Disable | SslMode::Prefer | SslMode::Require => {
+                if self.root_cert.is_none() {
+                    builder.danger_accept_invalid_certs(true);
+                }
+                builder.danger_accept_invalid_hostnames(true);
+            }
+            SslMode::VerifyCa => {
+                builder.danger_accept_invalid_hostnames(true);
+            }
+            SslMode::VerifyFull => {}
+        }
+
+        let connector = builder
+            .build()
+            .map_err(|e| Error::Config(format!("Can't create the TLS connector: {e}")))?;
+        Ok(MakeTlsConnector::new(connector))
+    }
+}
+
+/* tokio-postgres reports every TLS problem as "error performing TLS handshake", this looks for it
+in the chain of errors so the message says the connection failed because of TLS*/
+pub fn is_tls_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if error.is::<native_tls::Error>()
+            || error.to_string().contains("error performing TLS handshake")
+            || error.to_string().contains("server does not support TLS")
+        {
+            return true;
+        }
+        current = error.source();
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PG_VARIABLES: [&str; 4] = ["PGSSLMODE", "PGSSLROOTCERT", "PGSSLCERT", "PGSSLKEY"];
+
+    #[test]
+    fn parses_the_modes_of_libpq() {
+        for mode in ["disable", "prefer", "require", "verify-ca", "verify-full"] {
+            assert_eq!(mode.parse::<SslMode>().unwrap().as_str(), mode);
+        }
+        assert!("verify_full"
+            .parse::<SslMode>()
+            .unwrap_err()
+            .contains("Invalid sslmode 'verify_full'"));
+        assert_eq!(SslMode::default(), SslMode::Prefer);
+    }
+
+    /* Every case that reads the PG* variables is in this test, the environment is shared by the
+    tests that run at the same time*/
+    #[test]
+    fn the_url_wins_over_the_environment_and_the_config() {
+        let saved: Vec<Option<String>> = PG_VARIABLES
+            .iter()
+            .map(|name| env::var(name).ok())
+            .collect();
+        for name in PG_VARIABLES {
+            env::remove_var(name);
+        }
+        let config = SslConfig {
+            mode: Some(SslMode::VerifyCa),
+            root_cert: Some(PathBuf::from("/config/ca.pem")),
+            ..SslConfig::default()
+        };
+        let url = "postgresql://ash@localhost/pokedex";
+
+        let (from_config, _) = TlsSettings::from_url_and_env(url, &config).unwrap();
+        let (default, _) = TlsSettings::from_url_and_env(url, &SslConfig::default()).unwrap();
+        env::set_var("PGSSLMODE", "require");
+        env::set_var("PGSSLROOTCERT", "/env/ca.pem");
+        let (from_env, _) = TlsSettings::from_url_and_env(url, &config).unwrap();
+        let (from_url, base_url) = TlsSettings::from_url_and_env(
+            "postgresql://ash@localhost/pokedex?sslmode=verify-full&connect_timeout=5\
+             &sslrootcert=/certs/my%20ca.pem",
+            &config,
+        )
+        .unwrap();
+        env::set_var("PGSSLMODE", "");
+        let (empty_env, _) = TlsSettings::from_url_and_env(url, &config).unwrap();
+        env::set_var("PGSSLMODE", "secure");
+        let invalid_env = TlsSettings::from_url_and_env(url, &config);
+        for (name, value) in PG_VARIABLES.iter().zip(saved) {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+
+        assert_eq!(from_config.mode, SslMode::VerifyCa);
+        assert_eq!(from_config.root_cert, Some(PathBuf::from("/config/ca.pem")));
+        assert_eq!(default.mode, SslMode::Prefer);
+        assert_eq!(from_env.mode, SslMode::Require);
+        assert_eq!(from_env.root_cert, Some(PathBuf::from("/env/ca.pem")));
+        assert_eq!(from_url.mode, SslMode::VerifyFull);
+        assert_eq!(from_url.root_cert, Some(PathBuf::from("/certs/my ca.pem")));
+        assert_eq!(
+            base_url,
+            "postgresql://ash@localhost/pokedex?connect_timeout=5"
+        );
+        // An empty variable is taken as not set
+        assert_eq!(empty_env.mode, SslMode::VerifyCa);
+        assert!(invalid_env
+            .unwrap_err()
+            .contains("Invalid sslmode 'secure'"));
+    }
+
+    #[test]
+    fn an_invalid_mode_in_the_url_is_an_error() {
+        let url = "postgresql://ash@localhost/pokedex?sslmode=always";
+        let error = TlsSettings::from_url_and_env(url, &SslConfig::default()).unwrap_err();
+
+        assert!(error.starts_with("Invalid sslmode 'always'"));
+    }
+
+    #[test]
+    fn the_client_certificate_needs_its_key() {
+        let config = SslConfig {
+            client_cert: Some(PathBuf::from("/certs/client.pem")),
+            ..SslConfig::default()
+        };
+        let url = "postgresql://ash@localhost/pokedex?sslmode=require";
+
+        assert_eq!(
+            TlsSettings::from_url_and_env(url, &config).unwrap_err(),
+            "sslcert and sslkey must be set together"
+        );
+    }
+
+    #[test]
+    fn tokio_postgres_only_knows_three_modes() {
+        let settings = |mode| TlsSettings {
+            mode,
+            ..TlsSettings::default()
+        };
+
+        assert_eq!(settings(SslMode::Disable).pg_ssl_mode(), PgSslMode::Disable);
+        assert_eq!(settings(SslMode::Prefer).pg_ssl_mode(), PgSslMode::Prefer);
+        assert_eq!(
+            settings(SslMode::VerifyFull).pg_ssl_mode(),
+            PgSslMode::Require
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use crate::db::pokemon_tables::PokemonName;
+use crate::db::query::{Pokemon, PokemonSearch, Stat, GENERATION_RANGES};
+use crate::db::store::PokedexStore;
+use crate::error::Error;
+use std::collections::{BTreeMap, BTreeSet};
+
+// What a check asks of the data
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    // Every pokemon has between 'min' and 'max' types
+    TypeCount { min: usize, max: usize },
+    // Every pokemon has at least one ability, and one hidden ability at most
+    Abilities,
+    // Every pokemon has the stat between 'min' and 'max' (both included)
+    StatRange { stat: Stat, min: i32, max: i32 },
+    // Height and weight are more than 0
+    PositiveSize,
+    // The pokedex numbers go from 1 to the last one without gaps
+    ContinuousPokedexNumbers,
+    // Every generation has all of its pokemon, from GENERATION_RANGES
+    CountPerGeneration,
+    // No two pokemon have the same name
+    UniqueNames,
+    // Every pokemon has a name in the language, from its species
+    LocalizedName { language: &'static str },
+}
+
+// A check of the suite, the name is what the report shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub rule: Rule,
+}
+
+// The result of a check, with what failed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub failures: Vec<String>,
+}
+
+impl CheckResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub pokemon_checked: usize,
+    pub results: Vec<CheckResult>,
+}
+
+impl ValidationReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(CheckResult::passed)
+    }
+
+    pub fn failed_checks(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| !result.passed())
+            .count()
+    }
+}
+
+/* The checks run after every sync. The base stats of the games go from 1 to 255, and the totals
+from 175 (Wishiwashi) to 720 (Arceus)*/
+pub fn default_checks() -> Vec<Check> {
+    let mut checks = vec![
+        Check {
+            name: "one or two types",
+            rule: Rule::TypeCount { min: 1, max: 2 },
+        },
+        Check {
+            name: "abilities",
+            rule: Rule::Abilities,
+        },
+    ];
+    for stat in Stat::ALL {
+        let (min, max) = match stat {
+            Stat::Total => (175, 720),
+            _ => (1, 255),
+        };
+        checks.push(Check {
+            name: stat.name(),
+            rule: Rule::StatRange { stat, min, max },
+        });
+    }
+    checks.extend([
+        Check {
+            name: "height and weight",
+            rule: Rule::PositiveSize,
+        },
+        Check {
+            name: "pokedex numbers without gaps",
+            rule: Rule::ContinuousPokedexNumbers,
+        },
+        Check {
+            name: "pokemon per generation",
+            rule: Rule::CountPerGeneration,
+        },
+        Check {
+            name: "unique names",
+            rule: Rule::UniqueNames,
+        },
+        Check {
+            name: "english names",
+            rule: Rule::LocalizedName { language: "en" },
+        },
+    ]);
+
+    checks
+}
+
+// Running the checks with every pokemon of the store
+pub async fn validate(
+    store: &dyn PokedexStore,
+    checks: &[Check],
+) -> Result<ValidationReport, Error> {
+    let pokemon = store.search(&PokemonSearch::default()).await?.pokemon;
+    let names = store.list_names().await?;
+
+    Ok(ValidationReport {
+        pokemon_checked: pokemon.len(),
+        results: checks
+            .iter()
+            .map(|check| CheckResult {
+                name: check.name,
+                failures: run_check(&check.rule, &pokemon, &names),
+            })
+            .collect(),
+    })
+}
+
+// What failed, one line for each pokemon or group
+fn run_check(rule: &Rule, pokemon: &[Pokemon], names: &[PokemonName]) -> Vec<String> {
+    let label = |p: &Pokemon| format!("#{} {}", p.pokedex_number, p.name);
+
+    match rule {
+        Rule::TypeCount { min, max } => pokemon
+            .iter()
+            .filter(|p| p.types.len() < *min || p.types.len() > *max)
+            .map(|p| format!("{} has {} types", label(p), p.types.len()))
+            .collect(),
+        Rule::Abilities => pokemon
+            .iter()
+            .filter_map(|p| {
+                let hidden = p.abilities.iter().filter(|a| a.is_hidden).count();
+                if p.abilities.is_empty() {
+                    Some(format!("{} has no abilities", label(p)))
+                } else if hidden > 1 {
+                    Some(format!("{} has {hidden} hidden abilities", label(p)))
+                } else {
+                    None
+                }
+            })
+            .collect(),
+        Rule::StatRange { stat, min, max } => pokemon
+            .iter()
+            .filter(|p| !(*min..=*max).contains(&p.stats.get(*stat)))
+            .map(|p| {
+                format!(
+                    "{} has {} {} (expected {min} to {max})",
+                    label(p),
+                    stat.name(),
+                    p.stats.get(*stat)
+                )
+            })
+            .collect(),
+        Rule::PositiveSize => pokemon
+            .iter()
+            .filter(|p| p.height <= 0.0 || p.weight <= 0.0)
+            .map(|p| {
+                format!(
+                    "{} has height {} and weight {}",
+                    label(p),
+                    p.height,
+                    p.weight
+                )
+            })
+            .collect(),
+        Rule::ContinuousPokedexNumbers => {
+            let saved: BTreeSet<i32> = pokemon.iter().map(|p| p.pokedex_number).collect();
+            let last = saved.last().copied().unwrap_or(0);
+            missing_ranges((1..=last).filter(|number| !saved.contains(number)))
+                .into_iter()
+                .map(|(first, last)| {
+                    if first == last {
+                        format!("#{first} is missing")
+                    } else {
+                        format!("#{first} to #{last} are missing")
+                    }
+                })
+                .collect()
+        }
+        Rule::CountPerGeneration => GENERATION_RANGES
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (first, last))| {
+                let generation = index as i32 + 1;
+                let expected = (last - first + 1) as usize;
+                let found = pokemon
+                    .iter()
+                    .filter(|p| p.generation == Some(generation))
+                    .count();
+                (found != expected)
+                    .then(|| format!("generation {generation} has {found} of {expected} pokemon"))
+            })
+            .collect(),
+        Rule::UniqueNames => {
+            let mut by_name: BTreeMap<&str, Vec<i32>> = BTreeMap::new();
+            for p in pokemon {
+                by_name.entry(&p.name).or_default().push(p.pokedex_number);
+            }
+            by_name
+                .into_iter()
+                .filter(|(_, numbers)| numbers.len() > 1)
+                .map(|(name, numbers)| format!("{name} is the name of {numbers:?}"))
+                .collect()
+        }
+        Rule::LocalizedName { language } => {
+            let named: BTreeSet<i32> = names
+                .iter()
+                .filter(|name| name.language.as_deref() == Some(*language))
+                .map(|name| name.pokedex_number)
+                .collect();
+            pokemon
+                .iter()
+                .filter(|p| !named.contains(&p.pokedex_number))
+                .map(|p| format!("{} doesn't have a '{language}' name", label(p)))
+                .collect()
+        }
+    }
+}
+
+// Joining the missing numbers in ranges, so a big gap is one line of the report
+fn missing_ranges(numbers: impl Iterator<Item = i32>) -> Vec<(i32, i32)> {
+    let mut ranges: Vec<(i32, i32)> = Vec::new();
+    for number in numbers {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == number => *last = number,
+            _ => ranges.push((number, number)),
+        }
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory_store::MemoryStore;
+    use crate::db::pokemon_tables::{AbilityRecord, PokemonRecord};
+    use crate::db::query::Stats;
+
+    fn record(pokedex_number: i32) -> PokemonRecord {
+        PokemonRecord {
+            pokedex_number,
+            name: format!("pokemon-{pokedex_number}"),
+            height: "1.00".to_string(),
+            weight: "10.00".to_string(),
+            hp: 50,
+            attack: 50,
+            defense: 50,
+            special_attack: 50,
+            special_defense: 50,
+            speed: 50,
+            types: vec!["normal".to_string()],
+            abilities: vec![
+                AbilityRecord {
+                    name: "run-away".to_string(),
+                    is_hidden: false,
+                },
+                AbilityRecord {
+                    name: "guts".to_string(),
+                    is_hidden: true,
+                },
+            ],
+        }
+    }
+
+    // Every pokemon of the national pokedex with an English name, the data of a good sync
+    fn pokedex() -> (Vec<Pokemon>, Vec<PokemonName>) {
+        let last = GENERATION_RANGES[GENERATION_RANGES.len() - 1].1;
+        let pokemon = (1..=last)
+            .map(|number| Pokemon::from_record(number, &record(number), None))
+            .collect();
+        let names = (1..=last)
+            .map(|number| PokemonName {
+                pokedex_number: number,
+                language: Some("en".to_string()),
+                name: format!("Pokemon {number}"),
+            })
+            .collect();
+
+        (pokemon, names)
+    }
+
+    fn failures(pokemon: &[Pokemon], names: &[PokemonName]) -> BTreeMap<&'static str, Vec<String>> {
+        default_checks()
+            .iter()
+            .map(|check| (check.name, run_check(&check.rule, pokemon, names)))
+            .filter(|(_, failures)| !failures.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn a_good_sync_passes_every_check() {
+        let (pokemon, names) = pokedex();
+
+        assert_eq!(failures(&pokemon, &names), BTreeMap::new());
+    }
+
+    #[test]
+    fn each_check_reports_what_fails_it() {
+        let (mut pokemon, mut names) = pokedex();
+        pokemon[0].types.clear();
+        pokemon[1].types.push("flying".to_string());
+        pokemon[1].types.push("fire".to_string());
+        pokemon[2].abilities.clear();
+        for ability in &mut pokemon[3].abilities {
+            ability.is_hidden = true;
+        }
+        pokemon[4].stats.speed = 0;
+        pokemon[5].height = 0.0;
+        pokemon[6].name = "pokemon-8".to_string();
+        pokemon.retain(|p| !(10..=12).contains(&p.pokedex_number) && p.pokedex_number != 200);
+        // Every stat in its range, but a total under the one of Wishiwashi
+        pokemon[9].stats = Stats {
+            hp: 25,
+            attack: 25,
+            defense: 25,
+            special_attack: 25,
+            special_defense: 25,
+            speed: 25,
+        };
+        // A name in another language isn't the English one
+        names[8].language = Some("fr".to_string());
+
+        let failures = failures(&pokemon, &names);
+        assert_eq!(
+            failures.keys().copied().collect::<BTreeSet<_>>(),
+            BTreeSet::from([
+                "one or two types",
+                "abilities",
+                Stat::Speed.name(),
+                Stat::Total.name(),
+                "height and weight",
+                "pokedex numbers without gaps",
+                "pokemon per generation",
+                "unique names",
+                "english names",
+            ])
+        );
+        assert_eq!(
+            failures["one or two types"],
+            ["#1 pokemon-1 has 0 types", "#2 pokemon-2 has 3 types"]
+        );
+        assert_eq!(
+            failures["abilities"],
+            [
+                "#3 pokemon-3 has no abilities",
+                "#4 pokemon-4 has 2 hidden abilities"
+            ]
+        );
+        assert_eq!(
+            failures[Stat::Speed.name()],
+            ["#5 pokemon-5 has speed 0 (expected 1 to 255)"]
+        );
+        assert_eq!(
+            failures[Stat::Total.name()],
+            ["#13 pokemon-13 has total 150 (expected 175 to 720)"]
+        );
+        assert_eq!(
+            failures["height and weight"],
+            ["#6 pokemon-6 has height 0 and weight 10"]
+        );
+        assert_eq!(
+            failures["pokedex numbers without gaps"],
+            ["#10 to #12 are missing", "#200 is missing"]
+        );
+        assert_eq!(
+            failures["pokemon per generation"],
+            [
+                "generation 1 has 148 of 151 pokemon",
+                "generation 2 has 99 of 100 pokemon"
+            ]
+        );
+        assert_eq!(
+            failures["unique names"],
+            ["pokemon-8 is the name of [7, 8]"]
+        );
+        assert_eq!(
+            failures["english names"],
+            ["#9 pokemon-9 doesn't have a 'en' name"]
+        );
+    }
+
+    #[tokio::test]
+    async fn the_report_counts_the_failed_checks() {
+        let store = MemoryStore::new();
+        store.reset_types_table().await.unwrap();
+        store.upsert_pokemon(&record(1)).await.unwrap();
+        store.upsert_pokemon(&record(3)).await.unwrap();
+
+        let report = validate(&store, &default_checks()).await.unwrap();
+        assert_eq!(report.pokemon_checked, 2);
+        assert!(!report.passed());
+        let failed: Vec<&str> = report
+            .results
+            .iter()
+            .filter(|result| !result.passed())
+            .map(|result| result.name)
+            .collect();
+        // The store doesn't have species, so it doesn't have names either
+        assert_eq!(
+            failed,
+            [
+                "pokedex numbers without gaps",
+                "pokemon per generation",
+                "english names"
+            ]
+        );
+        assert_eq!(report.failed_checks(), 3);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::db::postgres_store::ConnectionProblem;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/* The errors of the pokedex, by what failed. The callers can match on them, and the CLI ends with
+a different exit code for each one (see 'exit_code')*/
+#[derive(Debug)]
+pub enum Error {
+    // The settings: the config files, the .env file, the environment or the flags
+    Config(String),
+    // A request to the API, with its URL
+    Http { url: String, message: String },
+    /* A document of the API or a snapshot that doesn't have what the pokedex needs. The path is the
+    field, e.g. "stats[0].base_stat"*/
+    Decode { path: String, message: String },
+    Database(DatabaseError),
+    // Building the DataFrame or drawing the plots
+    Plot(String),
+    Io(io::Error),
+    /* What was asked can't be done with the saved data, e.g. a pokemon or a user that doesn't
+    exist or a team that isn't valid*/
+    Input(String),
+    // The saved data failed the checks of the validation, e.g. after a sync that broke it
+    Validation(String),
+}
+
+// The database that failed, with the error of its driver
+#[derive(Debug)]
+pub enum DatabaseError {
+    Postgres(tokio_postgres::Error),
+    Pool(deadpool_postgres::PoolError),
+    PoolBuild(deadpool_postgres::BuildError),
+    Sqlite(rusqlite::Error),
+    // Why the connection with PostgreSQL failed, e.g. a wrong password
+    Connection(ConnectionProblem),
+    // The TLS negotiation with PostgreSQL failed, e.g. the server doesn't trust the certificate
+    Tls(String),
+    /* A row the in-memory backend can't save, where the SQL backends break a foreign key, e.g. the
+    collection of a user that doesn't exist*/
+    Memory(String),
+}
+
+impl Error {
+    // A field of a document that is missing or has another type, e.g. "height"
+    pub fn missing_field(path: impl Into<String>) -> Self {
+        Error::Decode {
+            path: path.into(),
+            message: "missing or with the wrong type".to_string(),
+        }
+    }
+
+    // A file that can't be read or written, with its path, e.g. "Can't read team.txt: ..."
+    pub fn file(error: io::Error, action: &str, path: &Path) -> Self {
+        Error::Io(io::Error::new(
+            error.kind(),
+            format!("Can't {action} {}: {error}", path.display()),
+        ))
+    }
+
+    /* The exit code of the CLI. 2 is left for the wrong arguments, clap uses it, and 1 for what
+    can't be done with the saved data*/
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Input(_) => 1,
+            Error::Config(_) => 3,
+            Error::Http { .. } => 4,
+            Error::Decode { .. } => 5,
+            Error::Database(_) => 6,
+            Error::Plot(_) => 7,
+            Error::Io(_) => 8,
+            Error::Validation(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(message)
+            | Error::Plot(message)
+            | Error::Input(message)
+            | Error::Validation(message) => write!(f, "{message}"),
+            Error::Http { url, message } if url.is_empty() => write!(f, "{message}"),
+            Error::Http { url, message } => write!(f, "The request to {url} failed: {message}"),
+            Error::Decode { path, message } if path.is_empty() => write!(f, "{message}"),
+            Error::Decode { path, message } => write!(f, "Can't decode '{path}': {message}"),
+            Error::Database(error) => write!(f, "{error}"),
+            Error::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatabaseError::Postgres(error) => write!(f, "PostgreSQL: {error}"),
+            DatabaseError::Pool(error) => write!(f, "PostgreSQL: {error}"),
+            DatabaseError::PoolBuild(error) => write!(f, "PostgreSQL: {error}"),
+            DatabaseError::Sqlite(error) => write!(f, "SQLite: {error}"),
+            DatabaseError::Connection(problem) => write!(f, "{problem}"),
+            DatabaseError::Tls(message) => write!(f, "PostgreSQL: {message}"),
+            DatabaseError::Memory(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(DatabaseError::Postgres(error)) => Some(error),
+            Error::Database(DatabaseError::Pool(error)) => Some(error),
+            Error::Database(DatabaseError::PoolBuild(error)) => Some(error),
+            Error::Database(DatabaseError::Sqlite(error)) => Some(error),
+            Error::Database(DatabaseError::Connection(problem)) => Some(problem),
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        let url = error.url().map(|url| url.to_string()).unwrap_or_default();
+        Error::Http {
+            url,
+            message: error.without_url().to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Decode {
+            path: String::new(),
+            message: format!("Invalid JSON: {error}"),
+        }
+    }
+}
+
+impl From<tokio_postgres::Error> for Error {
+    fn from(error: tokio_postgres::Error) -> Self {
+        Error::Database(DatabaseError::Postgres(error))
+    }
+}
+
+impl From<deadpool_postgres::PoolError> for Error {
+    fn from(error: deadpool_postgres::PoolError) -> Self {
+        Error::Database(DatabaseError::Pool(error))
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        Error::Database(DatabaseError::Sqlite(error))
+    }
+}
+
+impl From<ConnectionProblem> for Error {
+    fn from(problem: ConnectionProblem) -> Self {
+        Error::Database(DatabaseError::Connection(problem))
+    }
+}
+
+impl From<polars::error::PolarsError> for Error {
+    fn from(error: polars::error::PolarsError) -> Self {
+        Error::Plot(error.to_string())
+    }
+}
+
+impl<E: std::error::Error + Send + Sync> From<plotters::drawing::DrawingAreaErrorKind<E>>
+    for Error
+{
+    fn from(error: plotters::drawing::DrawingAreaErrorKind<E>) -> Self {
+        Error::Plot(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn every_error_has_its_own_exit_code() {
+        let errors = [
+            Error::Input(String::new()),
+            Error::Config(String::new()),
+            Error::Http {
+                url: String::new(),
+                message: String::new(),
+            },
+            Error::missing_field("height"),
+            Error::Database(DatabaseError::Memory(String::new())),
+            Error::Plot(String::new()),
+            Error::Io(io::Error::other("")),
+            Error::Validation(String::new()),
+        ];
+        let codes: BTreeSet<u8> = errors.iter().map(Error::exit_code).collect();
+
+        assert_eq!(codes.len(), errors.len());
+        // 2 is the one of clap for the wrong arguments
+        assert!(!codes.contains(&2));
+        assert_eq!(Error::Validation(String::new()).exit_code(), 9);
+    }
+}
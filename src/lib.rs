@@ -1,3 +1,5 @@
+pub mod config;
 pub mod data_collect;
 pub mod db;
+pub mod error;
 pub mod users;
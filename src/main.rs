@@ -1,52 +1,1120 @@
-mod data_collect;
-mod db;
-mod users;
+mod commands;
 
-use data_collect::pokemon_data::load_pokemon_data;
-use db::connection::AppConfig;
-use db::pokemon_tables::{insert_pokemon_data, reset_types_table};
+use clap::{Parser, Subcommand};
+use commands::annotations::{
+    annotation_filter, favorite_pokemon, list_annotations, tag_pokemon, write_note, AnnotationArgs,
+};
+use commands::settings::{manage_profiles, show_config, ProfileCommand};
+use commands::snapshot::{export_to_file, import_from_file};
+use commands::teams::{manage_teams, TeamCommand};
+use commands::users::{manage_users, mark_pokemon, show_collection, unmark_pokemon, UserCommand};
 use dotenv::dotenv;
-use pokedb::users::user_config::setup_env_file;
-use std::path::Path;
+use pokedb::config::{Config, ConfigOverrides};
+use pokedb::data_collect::pokemon_data::{load_pokemon_data, AnalysisFilter};
+use pokedb::db::annotations::AnnotationFilter;
+use pokedb::db::archive::backfill_pokemon_from_archive;
+use pokedb::db::connection::AppConfig;
+use pokedb::db::display_names::DisplayNames;
+use pokedb::db::ingestion::{sync_games, sync_pokemon, sync_regional_dexes};
+use pokedb::db::name_search::NameMatch;
+use pokedb::db::query::{GenerationRange, Pokemon, PokemonSearch, SortField, Stat, StatRange};
+use pokedb::db::summary::{summarize, SummaryView};
+use pokedb::db::validation::{self, default_checks};
+use pokedb::error::Error;
+use pokedb::users::user_config::{init_env_file, setup_env_file, InitOptions};
+use std::collections::HashSet;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 const TOTAL_POKEMON: u32 = 1025;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+#[derive(Parser)]
+#[command(name = "pokedb", about = "Pokedex with the data of the Poke API")]
+struct Cli {
+    // Without a command the pokemon are fetched and then analyzed
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// The game of the searches and analyses, e.g. sword or sword-shield (POKEDB_GAME by default)
+    #[arg(long, global = true)]
+    game: Option<String>,
+    /// Read only this config file instead of the user and project ones (POKEDB_CONFIG)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// The database, e.g. sqlite://pokedex.db (DATABASE_URL)
+    #[arg(long, global = true)]
+    database_url: Option<String>,
+    /// The pokemon endpoint of the API (POKEMON_BASE_API_URL)
+    #[arg(long, global = true)]
+    api_url: Option<String>,
+    /// Pokemon fetched at the same time (POKEDB_CONCURRENCY)
+    #[arg(long, global = true)]
+    concurrency: Option<usize>,
+    /// Where the plots are saved (POKEDB_OUTPUT_DIR)
+    #[arg(long, global = true)]
+    output_dir: Option<PathBuf>,
+    /// Request every resource to the API even if it's in the cache
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// A profile of the config files, e.g. dev (POKEDB_PROFILE)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+}
+
+impl Cli {
+    // The flags that replace the values of the config files and the environment
+    fn overrides(&self) -> ConfigOverrides {
+        ConfigOverrides {
+            database_url: self.database_url.clone(),
+            api_url: self.api_url.clone(),
+            concurrency: self.concurrency,
+            game: self.game.clone(),
+            cache_enabled: self.no_cache.then_some(false),
+            output_dir: self.output_dir.clone(),
+            profile: self.profile.clone(),
+            ..ConfigOverrides::default()
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the .env file without questions, from the flags and the PG* variables
+    Init {
+        /// PostgreSQL host (PGHOST, localhost by default)
+        #[arg(long)]
+        host: Option<String>,
+        /// PostgreSQL port (PGPORT, 5432 by default)
+        #[arg(long)]
+        port: Option<u16>,
+        /// PostgreSQL user (PGUSER)
+        #[arg(long)]
+        user: Option<String>,
+        /// The name of the database (PGDATABASE)
+        #[arg(long)]
+        database: Option<String>,
+        /// Write the password of PGPASSWORD in the .env file
+        #[arg(long)]
+        save_password: bool,
+        /// Use the defaults for what isn't given, create the database and replace the .env file
+        #[arg(long, short)]
+        yes: bool,
+        /// Show the .env file without connecting or writing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't connect to the database and the API before writing the file
+        #[arg(long)]
+        no_check: bool,
+    },
+    /// Fetch every pokemon from the API and save it in the database
+    Sync,
+    /// Parse again the API documents archived by the syncs and update the pokemon, without requests
+    Backfill,
+    /// Show the settings and the config files they were read from
+    Config,
+    /// List, add or remove the profiles of the config files
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommand,
+    },
+    /// Check the saved data: types, abilities, stats, gaps, counts per generation and names
+    Validate,
+    /// Create the DataFrame and plot the distributions, types and correlations
+    Analyze {
+        /// Only the pokemon of these generations, e.g. 1-3 or 2
+        #[arg(long)]
+        generation: Option<GenerationRange>,
+        /// Only the pokemon of a regional pokedex, e.g. galar
+        #[arg(long)]
+        dex: Option<String>,
+        /// Types and stats as they were in a generation, e.g. 5 (fairy didn't exist yet)
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..=9))]
+        as_of: Option<i32>,
+        #[command(flatten)]
+        annotations: AnnotationArgs,
+    },
+    /// Recalculate the summaries of types, type combinations and generations
+    Refresh,
+    /// Show a summary: types, combinations or generations
+    Summary {
+        #[arg(default_value = "types")]
+        view: SummaryView,
+        /// Only the pokemon of these generations, e.g. 1-3 or 2
+        #[arg(long)]
+        generation: Option<GenerationRange>,
+        /// Only the pokemon of a regional pokedex, e.g. galar
+        #[arg(long)]
+        dex: Option<String>,
+        /// Types and stats as they were in a generation, e.g. 5 (fairy didn't exist yet)
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..=9))]
+        as_of: Option<i32>,
+        #[command(flatten)]
+        annotations: AnnotationArgs,
+    },
+    /// List the pokedexes of the API: national, regional and of each game
+    Dexes,
+    /// List the games with their generation, regions and pokedexes
+    Games,
+    /// Save every table in a compressed snapshot that can be imported in another database
+    Export {
+        #[arg(default_value = "pokedex-snapshot.tar.gz")]
+        path: PathBuf,
+    },
+    /// Restore a snapshot made with 'export' in an empty database
+    Import { path: PathBuf },
+    /// List the ingestion runs, the last one first
+    Runs,
+    /// Show what changed in the pokemon between two ingestion runs
+    Changes {
+        /// The run to compare from
+        from: i32,
+        /// The run to compare to (the last run by default)
+        to: Option<i32>,
+    },
+    /// Add or list the users, each one has their own collection
+    User {
+        #[command(subcommand)]
+        action: UserCommand,
+    },
+    /// Save a pokemon in the collection of a user, from the game of --game
+    Mark {
+        /// e.g. "pikachu", "mr mime" or 25
+        #[arg(required = true, num_args = 1..)]
+        pokemon: Vec<String>,
+        /// The user (POKEDB_USER by default)
+        #[arg(long)]
+        user: Option<String>,
+        /// The form, e.g. alola or galar (the default form without it)
+        #[arg(long, default_value = "")]
+        form: String,
+        #[arg(long)]
+        shiny: bool,
+    },
+    /// Remove a pokemon from the collection of a user
+    Unmark {
+        #[arg(required = true, num_args = 1..)]
+        pokemon: Vec<String>,
+        /// The user (POKEDB_USER by default)
+        #[arg(long)]
+        user: Option<String>,
+        #[arg(long, default_value = "")]
+        form: String,
+        #[arg(long)]
+        shiny: bool,
+    },
+    /// Show the living dex of a user: the species caught of every generation or of a pokedex
+    Collection {
+        /// The user (POKEDB_USER by default)
+        #[arg(long)]
+        user: Option<String>,
+        /// Only the species of a pokedex, e.g. galar (see 'dexes')
+        #[arg(long)]
+        dex: Option<String>,
+        /// Only the shiny pokemon count
+        #[arg(long)]
+        shiny: bool,
+        /// List the species that are missing
+        #[arg(long)]
+        missing: bool,
+    },
+    /// Add a pokemon to the favorites of a user, or remove it with --remove
+    Favorite {
+        #[arg(required = true, num_args = 1..)]
+        pokemon: Vec<String>,
+        /// The user (POKEDB_USER by default)
+        #[arg(long)]
+        user: Option<String>,
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Add or remove the tags of a pokemon, without them its tags are shown
+    Tag {
+        #[arg(required = true, num_args = 1..)]
+        pokemon: Vec<String>,
+        /// A tag to add, e.g. ou-candidates. It can be given several times
+        #[arg(long)]
+        add: Vec<String>,
+        /// A tag to remove
+        #[arg(long)]
+        remove: Vec<String>,
+        /// The user (POKEDB_USER by default)
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Write the note of a user about a pokemon, in markdown. Without --text or --file it's shown
+    Note {
+        #[arg(required = true, num_args = 1..)]
+        pokemon: Vec<String>,
+        /// The note, it replaces the saved one
+        #[arg(long, conflicts_with_all = ["file", "clear"])]
+        text: Option<String>,
+        /// Read the note from a file, "-" for the standard input
+        #[arg(long, conflicts_with = "clear")]
+        file: Option<PathBuf>,
+        /// Remove the note
+        #[arg(long)]
+        clear: bool,
+        /// The user (POKEDB_USER by default)
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// List the favorites, tags and notes of a user
+    Annotations {
+        #[command(flatten)]
+        filter: AnnotationArgs,
+    },
+    /// Import, export and list the teams of a user, in the format of Pokemon Showdown
+    Team {
+        #[command(subcommand)]
+        action: TeamCommand,
+    },
+    /// Show a pokemon by its name or pokedex number
+    Show {
+        /// e.g. "pikachu", "mr mime" or 25
+        #[arg(required = true, num_args = 1..)]
+        pokemon: Vec<String>,
+    },
+    /// Find pokemon by name, with typos, spaces, accents or in other languages
+    Find {
+        /// e.g. "mr mime", "farfetchd" or "nidoran f"
+        #[arg(required = true, num_args = 1..)]
+        query: Vec<String>,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// List the pokemon whose name starts with the text
+    Complete {
+        #[arg(required = true, num_args = 1..)]
+        prefix: Vec<String>,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Search the pokemon by type, ability, generation and stats
+    Search {
+        /// Type the pokemon must have, it can be given twice
+        #[arg(long = "type")]
+        types: Vec<String>,
+        /// Ability the pokemon must have
+        #[arg(long)]
+        ability: Option<String>,
+        /// Generation or range of generations, e.g. 1 or 1-3
+        #[arg(long)]
+        generation: Option<GenerationRange>,
+        /// Only the pokemon of a regional pokedex, e.g. galar (see 'dexes')
+        #[arg(long)]
+        dex: Option<String>,
+        /// Types and stats as they were in a generation, e.g. 5 (fairy didn't exist yet)
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..=9))]
+        as_of: Option<i32>,
+        /// Minimum of a stat, e.g. speed=100 (the stat can be "total")
+        #[arg(long, value_parser = parse_stat_bound)]
+        min: Vec<(Stat, i32)>,
+        /// Maximum of a stat, e.g. hp=50
+        #[arg(long, value_parser = parse_stat_bound)]
+        max: Vec<(Stat, i32)>,
+        /// number, name, height, weight, a stat or regional (the number in --dex)
+        #[arg(long, default_value = "number")]
+        sort: SortField,
+        /// Sort from the highest to the lowest
+        #[arg(long)]
+        desc: bool,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+        #[arg(long, default_value_t = 0)]
+        offset: i64,
+        #[command(flatten)]
+        annotations: AnnotationArgs,
+    },
+}
+
+// "speed=100" into the stat and the value
+fn parse_stat_bound(bound: &str) -> Result<(Stat, i32), String> {
+    let (stat, value) = bound
+        .split_once('=')
+        .ok_or_else(|| format!("Expected STAT=VALUE, got '{bound}'"))?;
+    let value = value
+        .parse()
+        .map_err(|_| format!("Invalid value '{value}' for {stat}"))?;
+
+    Ok((stat.parse()?, value))
+}
+
+/* The errors are printed with their message, and the exit code says what failed (see the README),
+so the scripts can tell a wrong config from an API that is down*/
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+async fn run() -> Result<(), Error> {
+    let cli = Cli::parse();
+    // Before reading the .env file, the init replaces it
+    if let Some(Command::Init {
+        host,
+        port,
+        user,
+        database,
+        save_password,
+        yes,
+        dry_run,
+        no_check,
+    }) = &cli.command
+    {
+        let options = InitOptions {
+            database_url: cli.database_url.clone(),
+            host: host.clone(),
+            port: *port,
+            user: user.clone(),
+            database: database.clone(),
+            api_url: cli.api_url.clone(),
+            save_password: *save_password,
+            yes: *yes,
+            dry_run: *dry_run,
+            no_check: *no_check,
+            // Only the files, the .env file is the one the init writes
+            ssl: Config::load(cli.config.as_deref(), &cli.overrides())?.ssl,
+        };
+        init_env_file(&options, Path::new(".env")).await?;
+        return Ok(());
+    }
     dotenv().ok();
-    /*If the '.env' file doesn't exists, the Pokedex will send the user to configure it's
-    credentials. But if the .'env' exists, the pokedex will send a message that everything is
-    correct and the pokedex has been init*/
-    if !Path::new(".env").exists() {
+    let mut settings = Config::load(cli.config.as_deref(), &cli.overrides())?;
+    if let Some(Command::Config) = cli.command {
+        show_config(&settings);
+        return Ok(());
+    }
+    // The profiles are in the config files, they don't need the database
+    if let Some(Command::Profile { action }) = &cli.command {
+        return manage_profiles(&cli, &settings, action).await;
+    }
+    /*If the '.env' file doesn't exists and no config has the database, the Pokedex will send the
+    user to configure it's credentials. But if the .'env' exists, the pokedex will send a message
+    that everything is correct and the pokedex has been init*/
+    if Path::new(".env").exists() {
+        println!(".env found. Wait");
+    } else if settings.database_url.is_none() {
+        // Without a terminal the questions can't be answered, e.g. in CI or a container
+        if !io::stdin().is_terminal() {
+            return Err(Error::Config(
+                "There isn't a .env file or a database URL, and the input isn't a \
+                terminal. Run 'pokedb init' with the flags or the PG* variables"
+                    .to_string(),
+            ));
+        }
         println!(".env not found. Insert your data");
         // Here the user will set the correct data for the db
-        setup_env_file()?;
-    } else {
-        println!(".env found. Wait");
+        setup_env_file(&settings.ssl).await?;
+        // The new .env is loaded here, so the command goes on without running the pokedex again
+        dotenv().ok();
+        settings = Config::load(cli.config.as_deref(), &cli.overrides())?;
+        if !matches!(cli.command, None | Some(Command::Sync)) {
+            println!("The database is empty, run 'pokedb sync' to fetch the pokemon");
+        }
     }
 
     // Importing the creation of the tables
-    let mut config = AppConfig::new()?;
+    let config = AppConfig::new(settings).await?;
     // Init the creation of the tables
-    config.init_database()?;
-
-    reset_types_table(&mut config.db_client)?;
+    config.init_database().await?;
 
-    for i in 1..=TOTAL_POKEMON {
-        match config.fetch_pokemon(i) {
-            Ok(Some(pokemon_data)) => {
-                insert_pokemon_data(&mut config.db_client, &pokemon_data)?;
-                println!("Inserted pokemon {i}");
+    match cli.command {
+        Some(Command::Sync) => {
+            sync(&config).await?;
+            validate(&config).await?;
+        }
+        Some(Command::Backfill) => {
+            backfill(&config).await?;
+            validate(&config).await?;
+        }
+        Some(Command::Validate) => validate(&config).await?,
+        Some(Command::Config) => show_config(&config.settings),
+        // The init and the profiles return before the database is opened
+        Some(Command::Init { .. }) | Some(Command::Profile { .. }) => {}
+        Some(Command::Analyze {
+            generation,
+            dex,
+            as_of,
+            annotations,
+        }) => {
+            let filter = AnalysisFilter {
+                generations: generation,
+                regional_dex: dex,
+                as_of_generation: as_of,
+                annotations: annotation_filter(&config, annotations).await?,
+            };
+            analyze(&config, &filter).await?
+        }
+        Some(Command::Refresh) => {
+            config.store.refresh_summaries().await?;
+            println!("Summaries refreshed");
+        }
+        Some(Command::Summary {
+            view,
+            generation,
+            dex,
+            as_of,
+            annotations,
+        }) => {
+            let annotations = annotation_filter(&config, annotations).await?;
+            show_summary(&config, view, generation, dex, as_of, annotations).await?
+        }
+        Some(Command::Dexes) => list_regional_dexes(&config).await?,
+        Some(Command::Games) => list_games(&config).await?,
+        Some(Command::Export { path }) => export_to_file(&config, &path).await?,
+        Some(Command::Import { path }) => import_from_file(&config, &path).await?,
+        Some(Command::Runs) => list_runs(&config).await?,
+        Some(Command::Changes { from, to }) => show_changes(&config, from, to).await?,
+        Some(Command::User { action }) => manage_users(&config, &action).await?,
+        Some(Command::Mark {
+            pokemon,
+            user,
+            form,
+            shiny,
+        }) => mark_pokemon(&config, &pokemon.join(" "), user, &form, shiny).await?,
+        Some(Command::Unmark {
+            pokemon,
+            user,
+            form,
+            shiny,
+        }) => unmark_pokemon(&config, &pokemon.join(" "), user, &form, shiny).await?,
+        Some(Command::Collection {
+            user,
+            dex,
+            shiny,
+            missing,
+        }) => show_collection(&config, user, dex, shiny, missing).await?,
+        Some(Command::Favorite {
+            pokemon,
+            user,
+            remove,
+        }) => favorite_pokemon(&config, &pokemon.join(" "), user, remove).await?,
+        Some(Command::Tag {
+            pokemon,
+            add,
+            remove,
+            user,
+        }) => tag_pokemon(&config, &pokemon.join(" "), &add, &remove, user).await?,
+        Some(Command::Note {
+            pokemon,
+            text,
+            file,
+            clear,
+            user,
+        }) => write_note(&config, &pokemon.join(" "), text, file, clear, user).await?,
+        Some(Command::Annotations { filter }) => list_annotations(&config, filter).await?,
+        Some(Command::Team { action }) => manage_teams(&config, action).await?,
+        Some(Command::Show { pokemon }) => show_pokemon(&config, &pokemon.join(" ")).await?,
+        Some(Command::Find { query, limit }) => {
+            let matches = config.store.search_names(&query.join(" "), limit).await?;
+            print_name_matches(&config, &matches).await?
+        }
+        Some(Command::Complete { prefix, limit }) => {
+            let matches = config.store.autocomplete(&prefix.join(" "), limit).await?;
+            print_name_matches(&config, &matches).await?
+        }
+        Some(Command::Search {
+            types,
+            ability,
+            generation,
+            dex,
+            as_of,
+            min,
+            max,
+            sort,
+            desc,
+            limit,
+            offset,
+            annotations,
+        }) => {
+            let mut stats: Vec<StatRange> = Vec::new();
+            for (stat, value) in min {
+                stats.push(StatRange {
+                    stat,
+                    min: Some(value),
+                    max: None,
+                });
+            }
+            for (stat, value) in max {
+                stats.push(StatRange {
+                    stat,
+                    min: None,
+                    max: Some(value),
+                });
+            }
+            let search = PokemonSearch {
+                types,
+                ability,
+                generation,
+                regional_dex: dex,
+                as_of_generation: as_of,
+                stats,
+                annotations: annotation_filter(&config, annotations).await?,
+                sort_by: sort,
+                descending: desc,
+                limit: Some(limit),
+                offset,
+                ..PokemonSearch::default()
+            };
+            let game = config.game().await?;
+            if let Some(game) = &game {
+                println!("Pokemon of {}", game.name());
             }
-            Ok(None) => println!("Unavaiable to obtain data from pokemon {i}. Skkiping"),
-            Err(e) => eprintln!("Error to fetching pokemon {i}: {e}"),
+            let search = search.for_game(game.as_ref());
+            if search.sort_by == SortField::RegionalNumber && search.regional_dex.is_none() {
+                return Err(Error::Input(
+                    "Sorting by regional number needs a pokedex, e.g. --dex galar".to_string(),
+                ));
+            }
+            search_pokemon(&config, &search).await?
+        }
+        None => {
+            sync(&config).await?;
+            analyze(&config, &AnalysisFilter::default()).await?;
+            validate(&config).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn sync(config: &AppConfig) -> Result<(), Error> {
+    config.store.reset_types_table().await?;
+
+    let run = sync_pokemon(config, TOTAL_POKEMON, config.settings.api.concurrency).await?;
+    println!(
+        "Run {} finished: {} inserted, {} updated, {} unchanged, {} skipped, {} failed",
+        run.id, run.inserted, run.updated, run.unchanged, run.skipped, run.failed
+    );
+
+    // A pokedex that fails doesn't stop the sync, the pokemon are already saved
+    match sync_regional_dexes(config).await {
+        Ok(saved) => println!("{saved} pokedexes saved"),
+        Err(e) => eprintln!("Error to fetching the pokedexes: {e}"),
+    }
+    match sync_games(config).await {
+        Ok((groups, versions)) => println!("{groups} version groups and {versions} versions saved"),
+        Err(e) => eprintln!("Error to fetching the games: {e}"),
+    }
+
+    // The summaries are only recalculated here, after the data changed
+    config.store.refresh_summaries().await?;
+
+    Ok(())
+}
+
+/* Filling the tables again from 'raw_documents', e.g. after an update of pokedb added a column.
+Only the documents of the API of the config are parsed*/
+async fn backfill(config: &AppConfig) -> Result<(), Error> {
+    config.store.reset_types_table().await?;
+
+    let upserted =
+        backfill_pokemon_from_archive(config.store.as_ref(), config.api_base_url()).await?;
+    if upserted == 0 {
+        println!(
+            "There are no archived pokemon of {}, run 'pokedb sync'",
+            config.api_base_url()
+        );
+        return Ok(());
+    }
+    println!("{upserted} pokemon updated from the archive");
+    config.store.refresh_summaries().await?;
+
+    Ok(())
+}
+
+/* Running the checks of the data and printing the report. A failed check is a validation error,
+so a scheduled sync ends with its own exit code and a script can tell it from a wrong argument*/
+async fn validate(config: &AppConfig) -> Result<(), Error> {
+    // Only the first failures of each check are printed, a broken sync could have hundreds
+    const MAX_FAILURES_SHOWN: usize = 5;
+
+    let report = validation::validate(config.store.as_ref(), &default_checks()).await?;
+    println!("Validation of {} pokemon", report.pokemon_checked);
+    for result in &report.results {
+        if result.passed() {
+            println!("  PASS  {}", result.name);
+            continue;
+        }
+        println!(
+            "  FAIL  {} ({} problems)",
+            result.name,
+            result.failures.len()
+        );
+        for failure in result.failures.iter().take(MAX_FAILURES_SHOWN) {
+            println!("          {failure}");
+        }
+        if result.failures.len() > MAX_FAILURES_SHOWN {
+            println!(
+                "          ... and {} more",
+                result.failures.len() - MAX_FAILURES_SHOWN
+            );
         }
     }
 
+    if !report.passed() {
+        return Err(Error::Validation(format!(
+            "Validation failed: {} checks failed",
+            report.failed_checks()
+        )));
+    }
+    println!("Every check passed");
+
+    Ok(())
+}
+
+async fn analyze(config: &AppConfig, filter: &AnalysisFilter) -> Result<(), Error> {
+    let game = config.game().await?;
+    if let Some(game) = &game {
+        println!("Analyzing the pokemon of {}", game.name());
+    }
+    let filter = filter.clone().for_game(game.as_ref());
     // Making the DataFrame with Polars
-    load_pokemon_data(&mut config.db_client)?;
+    let settings = &config.settings;
+    load_pokemon_data(
+        config.store.as_ref(),
+        &filter,
+        &settings.plots,
+        &settings.output_dir,
+    )
+    .await?;
 
     // Printing the DataFrame
     println!("DataFrame created.");
 
     Ok(())
 }
+
+/* The saved summaries have every pokemon, with a range of generations, a pokedex, an older
+generation or a target game the summary is calculated from their pokemon*/
+async fn show_summary(
+    config: &AppConfig,
+    view: SummaryView,
+    generation: Option<GenerationRange>,
+    dex: Option<String>,
+    as_of: Option<i32>,
+    annotations: Option<AnnotationFilter>,
+) -> Result<(), Error> {
+    let game = config.game().await?;
+    let filtered =
+        generation.is_some() || dex.is_some() || as_of.is_some() || annotations.is_some();
+    let summaries = if filtered || game.is_some() {
+        let search = PokemonSearch {
+            generation,
+            regional_dex: dex,
+            as_of_generation: as_of,
+            annotations,
+            ..PokemonSearch::default()
+        }
+        .for_game(game.as_ref());
+        summarize(&config.store.search(&search).await?.pokemon, view)
+    } else {
+        config.store.load_summary(view).await?
+    };
+    if summaries.is_empty() {
+        println!("The summary is empty. Run 'pokedb sync' or 'pokedb refresh' first");
+        return Ok(());
+    }
+
+    println!(
+        "{:<20}  {:>5}  {:>6}  {:>6}  {:>6}  {:>6}  {:>6}  {:>6}  {:>10}  {:>12}",
+        view.group_column(),
+        "Count",
+        "HP",
+        "Atk",
+        "Def",
+        "SpA",
+        "SpD",
+        "Spe",
+        "Mean total",
+        "Median total"
+    );
+    for summary in summaries {
+        let mean = summary.mean;
+        println!(
+            "{:<20}  {:>5}  {:>6.1}  {:>6.1}  {:>6.1}  {:>6.1}  {:>6.1}  {:>6.1}  {:>10.1}  {:>12.1}",
+            summary.group,
+            summary.pokemon_count,
+            mean.hp,
+            mean.attack,
+            mean.defense,
+            mean.special_attack,
+            mean.special_defense,
+            mean.speed,
+            mean.total,
+            summary.median.total
+        );
+    }
+
+    Ok(())
+}
+
+// The saved pokedexes with how many species they have and how many of them are in the database
+async fn list_regional_dexes(config: &AppConfig) -> Result<(), Error> {
+    let dexes = config.store.list_regional_dexes().await?;
+    if dexes.is_empty() {
+        println!("There are no pokedexes yet. Run 'pokedb sync' first");
+        return Ok(());
+    }
+
+    let saved: HashSet<i32> = config
+        .store
+        .search(&PokemonSearch::default())
+        .await?
+        .pokemon
+        .iter()
+        .map(|pokemon| pokemon.pokedex_number)
+        .collect();
+    println!(
+        "{:<24}  {:<10}  {:>7}  {:>5}",
+        "Pokedex", "Region", "Species", "Saved"
+    );
+    for dex in dexes {
+        let in_database = dex
+            .entries
+            .iter()
+            .filter(|entry| saved.contains(&entry.pokedex_number))
+            .count();
+        println!(
+            "{:<24}  {:<10}  {:>7}  {:>5}",
+            dex.name,
+            dex.region.as_deref().unwrap_or("-"),
+            dex.entries.len(),
+            in_database
+        );
+    }
+
+    Ok(())
+}
+
+// The version groups in the order of the releases, the target game is marked with a '*'
+async fn list_games(config: &AppConfig) -> Result<(), Error> {
+    let groups = config.store.list_version_groups().await?;
+    if groups.is_empty() {
+        println!("There are no games yet. Run 'pokedb sync' first");
+        return Ok(());
+    }
+
+    let versions = config.store.list_versions().await?;
+    let target = config.game().await?;
+    println!(
+        "  {:<34}  {:>3}  {:<16}  {:<20}  Versions",
+        "Version group", "Gen", "Regions", "Pokedexes"
+    );
+    for group in groups {
+        let names: Vec<&str> = versions
+            .iter()
+            .filter(|version| version.version_group == group.name)
+            .map(|version| version.display_name.as_deref().unwrap_or(&version.name))
+            .collect();
+        let marker = if target
+            .as_ref()
+            .is_some_and(|game| game.group.name == group.name)
+        {
+            "*"
+        } else {
+            " "
+        };
+        println!(
+            "{marker} {:<34}  {:>3}  {:<16}  {:<20}  {}",
+            group.name,
+            group
+                .generation
+                .map_or("-".to_string(), |generation| generation.to_string()),
+            group.regions.join(", "),
+            group.pokedexes.join(", "),
+            names.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+async fn list_runs(config: &AppConfig) -> Result<(), Error> {
+    let runs = config.store.list_ingestion_runs().await?;
+    if runs.is_empty() {
+        println!("There are no ingestion runs yet. Run 'pokedb sync' first");
+        return Ok(());
+    }
+
+    println!(
+        "{:>4}  {:<19}  {:>9}  {:>8}  {:>7}  {:>9}  {:>7}  {:>6}  Source",
+        "Run", "Started", "Duration", "Inserted", "Updated", "Unchanged", "Skipped", "Failed"
+    );
+    for run in runs {
+        let duration = match run.duration_ms {
+            Some(ms) => format!("{:.1}s", ms as f64 / 1000.0),
+            None => "running".to_string(),
+        };
+        println!(
+            "{:>4}  {:<19}  {:>9}  {:>8}  {:>7}  {:>9}  {:>7}  {:>6}  {}",
+            run.id,
+            run.started_at.format("%Y-%m-%d %H:%M:%S"),
+            duration,
+            run.inserted,
+            run.updated,
+            run.unchanged,
+            run.skipped,
+            run.failed,
+            run.source
+        );
+    }
+
+    Ok(())
+}
+
+async fn show_changes(config: &AppConfig, from: i32, to: Option<i32>) -> Result<(), Error> {
+    let to = match to {
+        Some(to) => to,
+        None => match config.store.list_ingestion_runs().await?.first() {
+            Some(last) => last.id,
+            None => return Err(Error::Input("There are no ingestion runs yet".to_string())),
+        },
+    };
+    if from >= to {
+        return Err(Error::Input(format!(
+            "The first run ({from}) must be before the second one ({to})"
+        )));
+    }
+
+    let changes = config.store.changes_between_runs(from, to).await?;
+    if changes.is_empty() {
+        println!("Nothing changed between run {from} and run {to}");
+        return Ok(());
+    }
+
+    let names = config.store.display_names().await?;
+    println!("Changes between run {from} and run {to}");
+    let mut current_run = None;
+    for change in changes {
+        if current_run != Some(change.run_id) {
+            current_run = Some(change.run_id);
+            println!(
+                "Run {} ({})",
+                change.run_id,
+                change.changed_at.format("%Y-%m-%d %H:%M:%S")
+            );
+        }
+        println!(
+            "  #{:<4} {:<16} {:<16} {} -> {}",
+            change.pokedex_number,
+            names.get(change.pokedex_number, &change.pokemon_name),
+            change.field,
+            change.old_value.as_deref().unwrap_or("-"),
+            change.new_value.as_deref().unwrap_or("-")
+        );
+    }
+
+    Ok(())
+}
+
+// A pokemon by its pokedex number or its name, with typos too
+async fn find_pokemon(config: &AppConfig, pokemon: &str) -> Result<Pokemon, Error> {
+    let found = match pokemon.parse::<i32>() {
+        Ok(pokedex_number) => config.store.get_by_dex_number(pokedex_number).await?,
+        Err(_) => find_by_name(config, pokemon).await?,
+    };
+    found.ok_or_else(|| Error::Input(format!("Pokemon '{pokemon}' not found")))
+}
+
+async fn show_pokemon(config: &AppConfig, pokemon: &str) -> Result<(), Error> {
+    let pokemon = find_pokemon(config, pokemon).await?;
+
+    let names = config.store.display_names().await?;
+    println!(
+        "#{} {}",
+        pokemon.pokedex_number,
+        names.get(pokemon.pokedex_number, &pokemon.name)
+    );
+    if let Some(generation) = pokemon.generation {
+        println!("  Generation  {generation}");
+    }
+    println!("  Types       {}", pokemon.types.join(", "));
+    let abilities: Vec<String> = pokemon
+        .abilities
+        .iter()
+        .map(|ability| {
+            if ability.is_hidden {
+                format!("{} (hidden)", ability.name)
+            } else {
+                ability.name.clone()
+            }
+        })
+        .collect();
+    println!("  Abilities   {}", abilities.join(", "));
+    println!("  Height      {:.2} m", pokemon.height);
+    println!("  Weight      {:.2} kg", pokemon.weight);
+    let stats = pokemon.stats;
+    println!(
+        "  Stats       HP {} / Atk {} / Def {} / SpA {} / SpD {} / Spe {} (total {})",
+        stats.hp,
+        stats.attack,
+        stats.defense,
+        stats.special_attack,
+        stats.special_defense,
+        stats.speed,
+        stats.total()
+    );
+    let history = config
+        .store
+        .list_pokemon_history()
+        .await?
+        .into_iter()
+        .find(|history| history.pokedex_number == pokemon.pokedex_number);
+    for past in history.iter().flat_map(|history| &history.past_types) {
+        println!(
+            "  Until gen{:>2} {}",
+            past.generation,
+            past.types.join(", ")
+        );
+    }
+    for past in history.iter().flat_map(|history| &history.past_stats) {
+        let stats: Vec<String> = past
+            .stats
+            .iter()
+            .map(|(stat, value)| format!("{stat} {value}"))
+            .collect();
+        println!("  Until gen{:>2} {}", past.generation, stats.join(" / "));
+    }
+
+    // What the user of the config wrote about it, if there is one
+    let user = match &config.settings.user {
+        Some(name) => config.store.find_user(name).await?,
+        None => None,
+    };
+    if let Some(user) = user {
+        let annotation = config
+            .store
+            .get_annotation(user.id, pokemon.pokedex_number)
+            .await?;
+        if annotation.favorite {
+            println!("  Favorite of {}", user.name);
+        }
+        if !annotation.tags.is_empty() {
+            println!("  Tags        {}", annotation.tags.join(", "));
+        }
+        if let Some(note) = &annotation.note {
+            println!(
+                "  Note of {} ({})",
+                user.name,
+                note.updated_at.format("%Y-%m-%d")
+            );
+            for line in note.text.lines() {
+                println!("    {line}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/* The slug first, then any name that is the same without spaces, punctuation or accents, e.g.
+"Mr. Mime" or "nidoran f". If there isn't one, the error has the closest names*/
+async fn find_by_name(config: &AppConfig, name: &str) -> Result<Option<Pokemon>, Error> {
+    if let Some(pokemon) = config.store.get_by_name(name).await? {
+        return Ok(Some(pokemon));
+    }
+
+    let matches = config.store.search_names(name, 5).await?;
+    match matches.first() {
+        Some(best) if best.score >= 1.0 => {
+            config.store.get_by_dex_number(best.pokedex_number).await
+        }
+        Some(_) => {
+            let names: Vec<&str> = matches.iter().map(|m| m.name.as_str()).collect();
+            Err(Error::Input(format!(
+                "Pokemon '{name}' not found. Did you mean {}?",
+                names.join(", ")
+            )))
+        }
+        None => Ok(None),
+    }
+}
+
+async fn print_name_matches(config: &AppConfig, matches: &[NameMatch]) -> Result<(), Error> {
+    if matches.is_empty() {
+        println!("No pokemon found");
+        return Ok(());
+    }
+
+    let names = config.store.display_names().await?;
+    for name_match in matches {
+        let Some(pokemon) = config
+            .store
+            .get_by_dex_number(name_match.pokedex_number)
+            .await?
+        else {
+            continue;
+        };
+        // The name is always shown, and the name that matched when it's a localized one
+        let matched = match &name_match.language {
+            Some(language) => format!("{} ({language})", name_match.name),
+            None => String::new(),
+        };
+        println!(
+            "{:>5}  {:<16}  {:.2}  {}",
+            pokemon.pokedex_number,
+            names.get(pokemon.pokedex_number, &pokemon.name),
+            name_match.score,
+            matched
+        );
+    }
+
+    Ok(())
+}
+
+async fn search_pokemon(config: &AppConfig, search: &PokemonSearch) -> Result<(), Error> {
+    let results = config.store.search(search).await?;
+    if results.pokemon.is_empty() {
+        println!("No pokemon found ({} in total)", results.total);
+        return Ok(());
+    }
+
+    let names = config.store.display_names().await?;
+    // With a pokedex the regional number goes before the national one
+    let regional = search.regional_dex.is_some();
+    if regional {
+        print!("{:>5}  ", "Reg");
+    }
+    println!(
+        "{:>5}  {:<16}  {:<18}  {:>3}  {:>3}  {:>3}  {:>3}  {:>3}  {:>3}  {:>5}",
+        "#", "Name", "Types", "HP", "Atk", "Def", "SpA", "SpD", "Spe", "Total"
+    );
+    for pokemon in &results.pokemon {
+        if regional {
+            print!("{:>5}  ", pokemon.regional_number.unwrap_or_default());
+        }
+        print_pokemon_line(pokemon, &names);
+    }
+    let first = search.offset + 1;
+    println!(
+        "Showing {} to {} of {}",
+        first,
+        first + results.pokemon.len() as i64 - 1,
+        results.total
+    );
+
+    Ok(())
+}
+
+fn print_pokemon_line(pokemon: &Pokemon, names: &DisplayNames) {
+    let stats = pokemon.stats;
+    println!(
+        "{:>5}  {:<16}  {:<18}  {:>3}  {:>3}  {:>3}  {:>3}  {:>3}  {:>3}  {:>5}",
+        pokemon.pokedex_number,
+        names.get(pokemon.pokedex_number, &pokemon.name),
+        pokemon.types.join(", "),
+        stats.hp,
+        stats.attack,
+        stats.defense,
+        stats.special_attack,
+        stats.special_defense,
+        stats.speed,
+        stats.total()
+    );
+}